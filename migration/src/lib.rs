@@ -2,6 +2,22 @@ pub use sea_orm_migration::prelude::*;
 
 mod m20230816_032228_create_post_table;
 mod m20230816_032636_add_post_fields;
+mod m20240415_090000_create_post_revision_table;
+mod m20240422_100000_create_tag_tables;
+mod m20240506_083000_create_comment_table;
+mod m20240513_091500_add_post_status;
+mod m20240520_140000_create_user_table;
+mod m20240603_100000_add_post_slug;
+mod m20240610_090000_create_attachment_table;
+mod m20240617_090000_create_webhook_tables;
+mod m20240624_090000_create_outbox_tables;
+mod m20240701_090000_create_audit_log_table;
+mod m20240708_090000_create_todo_table;
+mod m20240715_090000_add_todo_fields;
+mod m20240722_090000_add_todo_parent;
+mod m20240729_090000_hash_user_tokens;
+mod m20240805_090000_unique_webhook_delivery_event;
+mod m20240812_090000_add_comment_author;
 
 pub struct Migrator;
 
@@ -11,6 +27,22 @@ impl MigratorTrait for Migrator {
         vec![
             Box::new(m20230816_032228_create_post_table::Migration),
             Box::new(m20230816_032636_add_post_fields::Migration),
+            Box::new(m20240415_090000_create_post_revision_table::Migration),
+            Box::new(m20240422_100000_create_tag_tables::Migration),
+            Box::new(m20240506_083000_create_comment_table::Migration),
+            Box::new(m20240513_091500_add_post_status::Migration),
+            Box::new(m20240520_140000_create_user_table::Migration),
+            Box::new(m20240603_100000_add_post_slug::Migration),
+            Box::new(m20240610_090000_create_attachment_table::Migration),
+            Box::new(m20240617_090000_create_webhook_tables::Migration),
+            Box::new(m20240624_090000_create_outbox_tables::Migration),
+            Box::new(m20240701_090000_create_audit_log_table::Migration),
+            Box::new(m20240708_090000_create_todo_table::Migration),
+            Box::new(m20240715_090000_add_todo_fields::Migration),
+            Box::new(m20240722_090000_add_todo_parent::Migration),
+            Box::new(m20240729_090000_hash_user_tokens::Migration),
+            Box::new(m20240805_090000_unique_webhook_delivery_event::Migration),
+            Box::new(m20240812_090000_add_comment_author::Migration),
         ]
     }
 }
@@ -6,35 +6,39 @@ pub struct Migration;
 #[async_trait::async_trait]
 impl MigrationTrait for Migration {
     async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
-        manager
-            .alter_table(
-                Table::alter()
-                    .table(Posts::Table)
-                    .add_column_if_not_exists(
-                        ColumnDef::new(Posts::CreatedAt)
-                            .timestamp_with_time_zone()
-                            .not_null(),
-                    )
-                    .add_column_if_not_exists(
-                        ColumnDef::new(Posts::UpdatedAt)
-                            .timestamp_with_time_zone()
-                            .not_null(),
-                    )
-                    .to_owned(),
-            )
-            .await
+        let sqlite = manager.get_database_backend() == sea_orm::DatabaseBackend::Sqlite;
+        // one column per statement for SQLite
+        for col in [Posts::CreatedAt, Posts::UpdatedAt] {
+            let mut def = ColumnDef::new(col);
+            def.timestamp_with_time_zone().not_null();
+            if sqlite {
+                // SQLite cannot add NOT NULL columns without a constant default
+                def.default("1970-01-01 00:00:00+00:00");
+            }
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Posts::Table)
+                        .add_column_if_not_exists(&mut def)
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
     }
 
     async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
-        manager
-            .alter_table(
-                Table::alter()
-                    .table(Posts::Table)
-                    .drop_column(Posts::CreatedAt)
-                    .drop_column(Posts::UpdatedAt)
-                    .to_owned(),
-            )
-            .await
+        for col in [Posts::CreatedAt, Posts::UpdatedAt] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Posts::Table)
+                        .drop_column(col)
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
     }
 }
 
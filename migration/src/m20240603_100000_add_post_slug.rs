@@ -0,0 +1,129 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Posts::Table)
+                    .add_column_if_not_exists(ColumnDef::new(Posts::Slug).string_len(128))
+                    .to_owned(),
+            )
+            .await?;
+
+        // existing posts get slugs from their ids
+        let backfill = Query::update()
+            .table(Posts::Table)
+            .value(Posts::Slug, Expr::cust("'post-' || CAST(id AS TEXT)"))
+            .and_where(Expr::col(Posts::Slug).is_null())
+            .to_owned();
+        let db = manager.get_connection();
+        db.execute(db.get_database_backend().build(&backfill))
+            .await?;
+
+        // SQLite cannot alter columns, the app always sets slugs anyway
+        if manager.get_database_backend() != sea_orm::DatabaseBackend::Sqlite {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Posts::Table)
+                        .modify_column(ColumnDef::new(Posts::Slug).string_len(128).not_null())
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_posts_slug")
+                    .table(Posts::Table)
+                    .col(Posts::Slug)
+                    .unique()
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await?;
+
+        // old slugs of renamed posts
+        manager
+            .create_table(
+                Table::create()
+                    .table(PostSlugs::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(PostSlugs::Slug)
+                            .string_len(128)
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(PostSlugs::PostId).integer().not_null())
+                    .col(
+                        ColumnDef::new(PostSlugs::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_post_slugs_post_id")
+                            .from(PostSlugs::Table, PostSlugs::PostId)
+                            .to(Posts::Table, Posts::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_post_slugs_post_id")
+                    .table(PostSlugs::Table)
+                    .col(PostSlugs::PostId)
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(PostSlugs::Table).to_owned())
+            .await?;
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_posts_slug")
+                    .table(Posts::Table)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Posts::Table)
+                    .drop_column(Posts::Slug)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Posts {
+    Table,
+    Id,
+    Slug,
+}
+
+#[derive(DeriveIden)]
+enum PostSlugs {
+    Table,
+    Slug,
+    PostId,
+    CreatedAt,
+}
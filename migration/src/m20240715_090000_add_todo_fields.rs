@@ -0,0 +1,107 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let sqlite = manager.get_database_backend() == sea_orm::DatabaseBackend::Sqlite;
+        let mut created_at = ColumnDef::new(Todos::CreatedAt);
+        created_at.timestamp_with_time_zone().not_null();
+        if sqlite {
+            // SQLite cannot add NOT NULL columns without a constant default
+            created_at.default("1970-01-01 00:00:00+00:00");
+        } else {
+            created_at.default(Expr::current_timestamp());
+        }
+        // one column per statement for SQLite
+        for mut def in [
+            ColumnDef::new(Todos::DueAt)
+                .timestamp_with_time_zone()
+                .to_owned(),
+            ColumnDef::new(Todos::Priority)
+                .integer()
+                .not_null()
+                .default(1)
+                .to_owned(),
+            created_at,
+            ColumnDef::new(Todos::CompletedAt)
+                .timestamp_with_time_zone()
+                .to_owned(),
+            ColumnDef::new(Todos::Position)
+                .integer()
+                .not_null()
+                .default(0)
+                .to_owned(),
+        ] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Todos::Table)
+                        .add_column_if_not_exists(&mut def)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        // existing todos keep their order
+        let backfill = Query::update()
+            .table(Todos::Table)
+            .value(Todos::Position, Expr::col(Todos::Id))
+            .to_owned();
+        let db = manager.get_connection();
+        db.execute(db.get_database_backend().build(&backfill))
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_todos_position")
+                    .table(Todos::Table)
+                    .col(Todos::Position)
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_todos_position")
+                    .table(Todos::Table)
+                    .to_owned(),
+            )
+            .await?;
+        for col in [
+            Todos::DueAt,
+            Todos::Priority,
+            Todos::CreatedAt,
+            Todos::CompletedAt,
+            Todos::Position,
+        ] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Todos::Table)
+                        .drop_column(col)
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Todos {
+    Table,
+    Id,
+    DueAt,
+    Priority,
+    CreatedAt,
+    CompletedAt,
+    Position,
+}
@@ -0,0 +1,68 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // one column per statement for SQLite
+        for mut def in [
+            ColumnDef::new(Todos::ParentId).integer().to_owned(),
+            ColumnDef::new(Todos::AutoComplete)
+                .boolean()
+                .not_null()
+                .default(false)
+                .to_owned(),
+        ] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Todos::Table)
+                        .add_column_if_not_exists(&mut def)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_todos_parent_id")
+                    .table(Todos::Table)
+                    .col(Todos::ParentId)
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_todos_parent_id")
+                    .table(Todos::Table)
+                    .to_owned(),
+            )
+            .await?;
+        for col in [Todos::ParentId, Todos::AutoComplete] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Todos::Table)
+                        .drop_column(col)
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Todos {
+    Table,
+    ParentId,
+    AutoComplete,
+}
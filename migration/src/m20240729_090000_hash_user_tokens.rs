@@ -0,0 +1,59 @@
+use sea_orm_migration::prelude::*;
+use sha2::{Digest, Sha256};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Users::Table)
+                    .rename_column(Users::Token, Users::TokenHash)
+                    .to_owned(),
+            )
+            .await?;
+
+        // tokens already given out keep working
+        let db = manager.get_connection();
+        let backend = db.get_database_backend();
+        let select = Query::select()
+            .columns([Users::Id, Users::TokenHash])
+            .from(Users::Table)
+            .to_owned();
+        for row in db.query_all(backend.build(&select)).await? {
+            let id: i32 = row.try_get("", "id")?;
+            let token: String = row.try_get("", "token_hash")?;
+            let hash = format!("{:x}", Sha256::digest(token.as_bytes()));
+            let update = Query::update()
+                .table(Users::Table)
+                .value(Users::TokenHash, hash)
+                .and_where(Expr::col(Users::Id).eq(id))
+                .to_owned();
+            db.execute(backend.build(&update)).await?;
+        }
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // hashes cannot be turned back, Users need new tokens
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Users::Table)
+                    .rename_column(Users::TokenHash, Users::Token)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Users {
+    Table,
+    Id,
+    Token,
+    TokenHash,
+}
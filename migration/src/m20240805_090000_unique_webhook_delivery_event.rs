@@ -0,0 +1,41 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // an event relayed again is not delivered to a webhook twice
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_webhook_deliveries_event_id_webhook_id")
+                    .table(WebhookDeliveries::Table)
+                    .col(WebhookDeliveries::EventId)
+                    .col(WebhookDeliveries::WebhookId)
+                    .unique()
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_webhook_deliveries_event_id_webhook_id")
+                    .table(WebhookDeliveries::Table)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum WebhookDeliveries {
+    Table,
+    EventId,
+    WebhookId,
+}
@@ -0,0 +1,72 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // SQLite cannot add foreign keys to existing tables
+        let sqlite = manager.get_database_backend() == sea_orm::DatabaseBackend::Sqlite;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Comments::Table)
+                    .add_column_if_not_exists(ColumnDef::new(Comments::AuthorId).integer())
+                    .to_owned(),
+            )
+            .await?;
+        if !sqlite {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Comments::Table)
+                        .add_foreign_key(
+                            TableForeignKey::new()
+                                .name("fk_comments_author_id")
+                                .from_tbl(Comments::Table)
+                                .from_col(Comments::AuthorId)
+                                .to_tbl(Users::Table)
+                                .to_col(Users::Id)
+                                .on_delete(ForeignKeyAction::SetNull),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        if manager.get_database_backend() != sea_orm::DatabaseBackend::Sqlite {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Comments::Table)
+                        .drop_foreign_key(Alias::new("fk_comments_author_id"))
+                        .to_owned(),
+                )
+                .await?;
+        }
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Comments::Table)
+                    .drop_column(Comments::AuthorId)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Comments {
+    Table,
+    AuthorId,
+}
+
+#[derive(DeriveIden)]
+enum Users {
+    Table,
+    Id,
+}
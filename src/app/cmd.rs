@@ -1,5 +1,6 @@
 use crate::{
-    infrastructure::{config, persistence, shell},
+    app::log::*,
+    infrastructure::{config, outbox, persistence, shell, webhook},
     interface::route,
 };
 
@@ -9,7 +10,21 @@ pub async fn run() -> super::Result<()> {
         tokio::spawn(config::local_conf_watch());
     }
     let db = persistence::Db::setup().await?;
+    let webhooks = webhook::spawn_dispatcher(db.webhook.clone(), &db.events);
+    outbox::spawn_relay(db.outbox.clone(), webhooks);
     let child_workers = shell::ChildWorkers::setup().await?;
 
-    route::serve(db, child_workers).await
+    let views = db.views.clone();
+    let todo = db.todo.clone();
+
+    // save what is buffered even if the server failed
+    let served = route::serve(db, child_workers).await;
+
+    if let Err(e) = views.flush().await {
+        error!(%e, "flush post views on shutdown failed");
+    }
+    if let Err(e) = todo.flush().await {
+        error!(%e, "snapshot todos on shutdown failed");
+    }
+    served
 }
@@ -0,0 +1,57 @@
+use serde::Serialize;
+use similar::{ChangeTag, TextDiff};
+use utoipa::ToSchema;
+
+/// One line of a line-based diff
+#[derive(Debug, PartialEq, Eq, Serialize, ToSchema)]
+pub struct DiffLine {
+    /// one of: equal, insert, delete
+    #[schema(example = "insert")]
+    pub tag: &'static str,
+    /// line number in the old text, starting from 1
+    pub old_line: Option<usize>,
+    /// line number in the new text, starting from 1
+    pub new_line: Option<usize>,
+    #[schema(example = "something to write")]
+    pub value: String,
+}
+
+/// line diff from `old` to `new`
+pub fn diff_lines(old: &str, new: &str) -> Vec<DiffLine> {
+    TextDiff::from_lines(old, new)
+        .iter_all_changes()
+        .map(|c| DiffLine {
+            tag: match c.tag() {
+                ChangeTag::Equal => "equal",
+                ChangeTag::Insert => "insert",
+                ChangeTag::Delete => "delete",
+            },
+            old_line: c.old_index().map(|i| i + 1),
+            new_line: c.new_index().map(|i| i + 1),
+            value: c.value().trim_end_matches(['\r', '\n']).to_owned(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_lines() {
+        let ret = diff_lines("a\nb\nc\n", "a\nc\nd");
+        let tags: Vec<_> = ret.iter().map(|x| (x.tag, x.value.as_str())).collect();
+        assert_eq!(
+            tags,
+            [
+                ("equal", "a"),
+                ("delete", "b"),
+                ("equal", "c"),
+                ("insert", "d")
+            ]
+        );
+        assert_eq!(ret[1].old_line, Some(2));
+        assert_eq!(ret[1].new_line, None);
+        assert_eq!(ret[3].new_line, Some(3));
+    }
+}
@@ -0,0 +1,210 @@
+use std::{collections::HashSet, sync::Mutex};
+
+use cached::{Cached, SizedCache};
+use once_cell::sync::Lazy;
+use pulldown_cmark::{html, CowStr, Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::entity::DateTimeTZ;
+
+/// max chars of the excerpt
+const EXCERPT_LEN: usize = 200;
+/// max Posts kept rendered
+const CACHE_SIZE: usize = 1000;
+/// prefix of ids in rendered content, not to clash with ids of the page showing it
+const ID_PREFIX: &str = "user-content-";
+
+/// Rendered contents by Post id, with the update time they were rendered from
+static CACHE: Lazy<Mutex<SizedCache<i32, (DateTimeTZ, Rendered)>>> =
+    Lazy::new(|| Mutex::new(SizedCache::with_size(CACHE_SIZE)));
+
+/// Markdown rendered as sanitized HTML
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct Rendered {
+    #[schema(example = "<h1 id=\"user-content-hello\">Hello</h1>\n<p>something to write</p>\n")]
+    pub html: String,
+    /// plain text from the start of the content
+    #[schema(example = "Hello something to write")]
+    pub excerpt: String,
+    pub toc: Vec<TocEntry>,
+}
+
+/// Heading of rendered content
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct TocEntry {
+    /// 1 to 6
+    #[schema(example = 1)]
+    pub level: u8,
+    /// anchor id of the heading
+    #[schema(example = "user-content-hello")]
+    pub id: String,
+    #[schema(example = "Hello")]
+    pub title: String,
+}
+
+/// render Markdown of the Post, reusing the last result if it was not updated since
+pub fn render_cached(id: i32, updated_at: DateTimeTZ, src: &str) -> Rendered {
+    if let Ok(mut cache) = CACHE.lock() {
+        if let Some((at, ret)) = cache.cache_get(&id) {
+            if *at == updated_at {
+                return ret.clone();
+            }
+        }
+    }
+    let ret = render(src);
+    if let Ok(mut cache) = CACHE.lock() {
+        cache.cache_set(id, (updated_at, ret.clone()));
+    }
+    ret
+}
+
+/// render Markdown to sanitized HTML, with excerpt and table of contents
+pub fn render(src: &str) -> Rendered {
+    let mut events: Vec<Event> = Parser::new_ext(src, Options::all()).collect();
+    let mut toc = Vec::new();
+    let mut ids = HashSet::new();
+    let mut text = String::new();
+
+    let mut heading: Option<(usize, HeadingLevel, String)> = None;
+    let mut hidden = false;
+    for i in 0..events.len() {
+        match &events[i] {
+            Event::Start(Tag::Heading { level, .. }) => {
+                heading = Some((i, *level, String::new()));
+            }
+            Event::End(TagEnd::Heading(_)) => {
+                if let Some((start, level, title)) = heading.take() {
+                    let id = unique_id(&mut ids, &title);
+                    if let Event::Start(Tag::Heading { id: ref mut v, .. }) = events[start] {
+                        *v = Some(CowStr::from(id.clone()));
+                    }
+                    toc.push(TocEntry {
+                        level: level as u8,
+                        // as the sanitizer prefixes it in the HTML
+                        id: format!("{ID_PREFIX}{id}"),
+                        title: title.trim().to_owned(),
+                    });
+                }
+                text.push(' ');
+            }
+            Event::InlineHtml(s) => {
+                // text of inline scripts and styles is dropped by the sanitizer, keep it out of the excerpt too
+                let tag = s.trim_start_matches('<').to_ascii_lowercase();
+                if tag.starts_with("script") || tag.starts_with("style") {
+                    hidden = true;
+                } else if tag.starts_with("/script") || tag.starts_with("/style") {
+                    hidden = false;
+                }
+            }
+            Event::Text(s) | Event::Code(s) if !hidden => {
+                if let Some((_, _, title)) = heading.as_mut() {
+                    title.push_str(s);
+                }
+                if text.chars().count() <= EXCERPT_LEN {
+                    text.push_str(s);
+                }
+            }
+            Event::SoftBreak | Event::HardBreak | Event::End(TagEnd::Paragraph) => {
+                text.push(' ');
+            }
+            _ => {}
+        }
+    }
+
+    let mut raw = String::with_capacity(src.len() * 3 / 2);
+    html::push_html(&mut raw, events.into_iter());
+    let html = ammonia::Builder::default()
+        .add_tag_attributes("h1", &["id"])
+        .add_tag_attributes("h2", &["id"])
+        .add_tag_attributes("h3", &["id"])
+        .add_tag_attributes("h4", &["id"])
+        .add_tag_attributes("h5", &["id"])
+        .add_tag_attributes("h6", &["id"])
+        .id_prefix(Some(ID_PREFIX))
+        .clean(&raw)
+        .to_string();
+
+    Rendered {
+        html,
+        excerpt: excerpt(&text),
+        toc,
+    }
+}
+
+/// collapse whitespace and cut at `EXCERPT_LEN` chars
+fn excerpt(text: &str) -> String {
+    let words = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    match words.char_indices().nth(EXCERPT_LEN) {
+        Some((i, _)) => format!("{}…", words[..i].trim_end()),
+        None => words,
+    }
+}
+
+/// anchor id from heading title, suffixed when taken
+fn unique_id(ids: &mut HashSet<String>, title: &str) -> String {
+    let mut base = String::new();
+    for c in title.trim().chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            base.push(c);
+        } else if !base.ends_with('-') {
+            base.push('-');
+        }
+    }
+    let base = match base.trim_matches('-') {
+        "" => "section".to_owned(),
+        v => v.to_owned(),
+    };
+    let mut id = base.clone();
+    let mut n = 1;
+    while !ids.insert(id.clone()) {
+        id = format!("{base}-{n}");
+        n += 1;
+    }
+    id
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_markdown() {
+        let ret = render("# Hello *World*\n\ntext\n\n## Hello World\n");
+        assert_eq!(
+            ret.toc,
+            vec![
+                TocEntry {
+                    level: 1,
+                    id: "user-content-hello-world".to_owned(),
+                    title: "Hello World".to_owned(),
+                },
+                TocEntry {
+                    level: 2,
+                    id: "user-content-hello-world-1".to_owned(),
+                    title: "Hello World".to_owned(),
+                },
+            ]
+        );
+        assert!(ret
+            .html
+            .contains(r#"<h1 id="user-content-hello-world">Hello <em>World</em></h1>"#));
+        assert_eq!(ret.excerpt, "Hello World text Hello World");
+    }
+
+    #[test]
+    fn sanitize_html() {
+        let ret = render("text <script>alert(1)</script> [x](javascript:alert(1))\n\n<img src=x onerror=alert(1)>\n\n<h2 id=\"app\">x</h2>");
+        assert!(!ret.html.contains("script"));
+        assert!(ret.html.contains(r#"<h2 id="user-content-app">"#));
+        assert!(!ret.html.contains("onerror"));
+        assert_eq!(ret.excerpt, "text x");
+    }
+
+    #[test]
+    fn cut_excerpt() {
+        let ret = render(&"word ".repeat(100));
+        assert_eq!(ret.excerpt.chars().count(), EXCERPT_LEN);
+        assert!(ret.excerpt.ends_with("word…"));
+    }
+}
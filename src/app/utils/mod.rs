@@ -3,5 +3,11 @@
 mod string;
 pub use string::*;
 
+pub mod diff;
+pub use diff::*;
+
+pub mod markdown;
+pub use markdown::{Rendered, TocEntry};
+
 pub mod mtime;
 pub use mtime::*;
@@ -1,4 +1,5 @@
 use serde::{Deserialize, Deserializer, Serializer};
+use time::PrimitiveDateTime;
 
 use crate::{entity::DateTimeTZ, infrastructure::config};
 
@@ -31,7 +32,50 @@ where
     D: Deserializer<'de>,
 {
     let s = String::deserialize(deserializer)?;
-    Ok(DateTimeTZ::parse(&s, &config::JSON_TIME_FORMAT)
-        .map_err(serde::de::Error::custom)?
-        .replace_offset(*config::LOCAL_OFFSET))
+    parse_json_time(&s).map_err(serde::de::Error::custom)
+}
+
+/// parse timestamp in JSON format, which is in local time
+pub fn parse_json_time(s: &str) -> Result<DateTimeTZ, time::error::Parse> {
+    Ok(
+        PrimitiveDateTime::parse(s, &config::JSON_TIME_FORMAT)?
+            .assume_offset(*config::LOCAL_OFFSET),
+    )
+}
+
+/// JSON optional timestamp, use with `#[serde(default, with = "utils::mtime::option")]`
+pub mod option {
+    use super::*;
+
+    pub fn serialize<S>(date: &Option<DateTimeTZ>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match date {
+            Some(v) => super::serialize(v, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<DateTimeTZ>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Option::<String>::deserialize(deserializer)?
+            .map(|s| parse_json_time(&s).map_err(serde::de::Error::custom))
+            .transpose()
+    }
+}
+
+/// JSON timestamp that may be set to null, use with `#[serde(default, with = "utils::mtime::double_option")]`;
+/// None if missing, Some(None) if null
+pub mod double_option {
+    use super::*;
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Option<DateTimeTZ>>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        super::option::deserialize(deserializer).map(Some)
+    }
 }
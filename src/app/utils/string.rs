@@ -16,3 +16,34 @@ pub fn trim_end_inplace(s: &mut String) {
 pub fn get_uuid_str() -> String {
     Uuid::new_v4().to_string()
 }
+
+/// max length of slugs
+pub const SLUG_MAX_LEN: usize = 100;
+
+/// URL-friendly lowercase ascii words joined by `-`, transliterating non-ascii letters
+pub fn slugify(s: &str) -> String {
+    let mut ret = String::with_capacity(s.len());
+    for c in deunicode::deunicode(s).chars() {
+        if c.is_ascii_alphanumeric() {
+            ret.push(c.to_ascii_lowercase());
+        } else if !ret.is_empty() && !ret.ends_with('-') {
+            ret.push('-');
+        }
+    }
+    ret.truncate(SLUG_MAX_LEN);
+    ret.trim_end_matches('-').to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slugify() {
+        assert_eq!(slugify("  Hello, World! "), "hello-world");
+        assert_eq!(slugify("Crème brûlée"), "creme-brulee");
+        assert_eq!(slugify("你好 世界"), "ni-hao-shi-jie");
+        assert_eq!(slugify("--"), "");
+        assert_eq!(slugify(&"a".repeat(200)).len(), SLUG_MAX_LEN);
+    }
+}
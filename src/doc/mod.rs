@@ -1,6 +1,15 @@
-use utoipa::OpenApi;
+use utoipa::{
+    openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+    Modify, OpenApi,
+};
 
-use crate::entity::{Post, Todo};
+use crate::app::utils::{DiffLine, Rendered, TocEntry};
+use crate::entity::{
+    Attachment, AuditLog, Comment, DeliveryStatus, Post, PostRevision, PostStatus, Todo,
+    TodoPriority, User, UserRole, UserSummary, Webhook, WebhookDelivery,
+};
+use crate::event::{Action, DomainEvent, Resource};
+use crate::interface::dto::{ContentFormat, DataFormat};
 use crate::interface::handler::*;
 use crate::interface::resp::*;
 use crate::repository::*;
@@ -13,25 +22,89 @@ use crate::repository::*;
             todo::create,
             todo::mark_done,
             todo::edit,
+            todo::reorder,
+            todo::tree,
+            todo::reparent,
             todo::delete,
 
             post::list,
             post::get,
+            post::get_by_slug,
             post::create,
+            post::transit,
             post::edit,
             post::delete,
+            post::bulk_create,
+            post::bulk_edit,
+            post::cache_stats,
+            post::revisions,
+            post::revision,
+            post::diff,
+            post::revert,
+            post_transfer::export,
+            post_transfer::import,
+
+            attachment::list,
+            attachment::upload,
+            attachment::download,
+            attachment::delete,
+
+            comment::list,
+            comment::create,
+            comment::edit,
+            comment::delete,
+
+            tag::list,
+
+            user::create,
+            user::me,
+            user::get,
+
+            webhook::list,
+            webhook::create,
+            webhook::delete,
+            webhook::deliveries,
+
+            audit::list,
 
             read_xls::parse,
+
+            health::ready,
         ),
         components(
             schemas(IdData, Void, VoidRes,
-                Todo, TodoUpdate,
-                Post, PostNew, PostList, PostUpdate,
+                Todo, TodoList, TodoUpdate, TodoPriority, TodoSort, SortOrder, TodoReorder,
+                TodoMove, TodoNode, TodoProgress,
+                Post, PostNew, PostList, PostUpdate, PostStatus, PostTransition,
+                BulkMode, PostBulkNew, PostBulkUpdate, BulkItem, DeleteReport,
+                PostRevision, PostRevisionList, VersionData, RevisionDiff, DiffLine,
+                TagMatch, TagCount,
+                Attachment,
+                Comment, CommentNew, CommentUpdate, CommentList,
+                User, UserRole, UserSummary, UserNew, UserToken,
+                DataFormat, ImportSummary, ImportError, CacheStats,
+                ContentFormat, Rendered, TocEntry,
+                Webhook, WebhookNew, WebhookSecret, WebhookDelivery, WebhookDeliveryList,
+                DeliveryStatus, DomainEvent, Resource, Action,
+                AuditLog, AuditLogList,
             )
         ),
-        // modifiers(&SecurityAddon),
+        modifiers(&SecurityAddon),
         // tags(
         //     (name = "todo", description = "Todo items management API")
         // )
     )]
 pub struct ApiDoc;
+
+struct SecurityAddon;
+
+impl Modify for SecurityAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme(
+                "token",
+                SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+            )
+        }
+    }
+}
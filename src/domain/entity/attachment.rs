@@ -0,0 +1,67 @@
+use async_trait::async_trait;
+use sea_orm::{entity::prelude::*, Set};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::app::utils;
+
+use super::DateTimeTZ;
+
+/// File attached to a Post, stored on local disk by content hash
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize, ToSchema)]
+#[schema(as = Attachment)]
+#[sea_orm(table_name = "attachments")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    #[schema(example = 1)]
+    pub id: i32,
+    #[schema(example = 1)]
+    pub post_id: i32,
+    /// original file name
+    #[schema(example = "photo.png")]
+    pub name: String,
+    #[schema(example = "image/png")]
+    pub content_type: String,
+    /// in bytes
+    #[schema(example = 1024)]
+    pub size: i64,
+    /// hex digest of the content
+    pub sha256: String,
+    /// where the content is stored, shared by identical files
+    #[serde(skip)]
+    pub path: String,
+    #[schema(value_type = String)]
+    #[serde(with = "utils::mtime")]
+    pub created_at: DateTimeTZ,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::post::Entity",
+        from = "Column::PostId",
+        to = "super::post::Column::Id",
+        on_delete = "Cascade"
+    )]
+    Post,
+}
+
+impl Related<super::post::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Post.def()
+    }
+}
+
+#[async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    /// Will be triggered before insert / update
+    async fn before_save<C>(mut self, _db: &C, insert: bool) -> Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        if insert {
+            self.created_at = Set(utils::get_current_time());
+        }
+        Ok(self)
+    }
+}
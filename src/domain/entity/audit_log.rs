@@ -0,0 +1,58 @@
+use async_trait::async_trait;
+use sea_orm::{entity::prelude::*, Set};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::app::utils;
+
+use super::DateTimeTZ;
+
+/// Record of a change made through the API
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize, ToSchema)]
+#[schema(as = AuditLog)]
+#[sea_orm(table_name = "audit_log")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    #[schema(example = 1)]
+    pub id: i32,
+    /// User making the change, None if anonymous
+    #[schema(example = 1)]
+    pub actor_id: Option<i32>,
+    #[schema(example = "delete")]
+    pub action: String,
+    /// kind of the changed objects
+    #[schema(example = "post")]
+    pub resource: String,
+    /// comma separated ids of the changed objects
+    #[schema(example = "1,2")]
+    pub resource_ids: String,
+    pub request_id: Option<String>,
+    #[schema(example = "127.0.0.1")]
+    pub client_ip: Option<String>,
+    /// the objects before the change
+    #[schema(value_type = Option<Object>)]
+    pub before: Option<Json>,
+    /// the objects after the change
+    #[schema(value_type = Option<Object>)]
+    pub after: Option<Json>,
+    #[schema(value_type = String)]
+    #[serde(with = "utils::mtime")]
+    pub created_at: DateTimeTZ,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+#[async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    /// Will be triggered before insert / update
+    async fn before_save<C>(mut self, _db: &C, insert: bool) -> Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        if insert {
+            self.created_at = Set(utils::get_current_time());
+        }
+        Ok(self)
+    }
+}
@@ -0,0 +1,81 @@
+use async_trait::async_trait;
+use sea_orm::{entity::prelude::*, Set};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::app::utils;
+
+use super::DateTimeTZ;
+
+/// Comment on a Post, optionally replying to another comment
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize, ToSchema)]
+#[schema(as = Comment)]
+#[sea_orm(table_name = "comments")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    #[schema(read_only, example = 1)]
+    pub id: i32,
+    #[schema(read_only, example = 1)]
+    pub post_id: i32,
+    /// comment replied to
+    #[schema(read_only)]
+    pub parent_id: Option<i32>,
+    #[schema(example = "nice post")]
+    pub content: String,
+    /// None if written anonymously
+    #[schema(read_only)]
+    pub author_id: Option<i32>,
+    #[schema(read_only, value_type = String)]
+    #[serde(with = "utils::mtime")]
+    pub created_at: DateTimeTZ,
+    #[schema(read_only, value_type = String)]
+    #[serde(with = "utils::mtime")]
+    pub updated_at: DateTimeTZ,
+    /// number of direct replies
+    #[sea_orm(ignore)]
+    #[schema(read_only, example = 0)]
+    #[serde(default)]
+    pub replies: i64,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::post::Entity",
+        from = "Column::PostId",
+        to = "super::post::Column::Id",
+        on_delete = "Cascade"
+    )]
+    Post,
+    #[sea_orm(
+        belongs_to = "Entity",
+        from = "Column::ParentId",
+        to = "Column::Id",
+        on_delete = "Cascade"
+    )]
+    Parent,
+}
+
+impl Related<super::post::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Post.def()
+    }
+}
+
+#[async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    /// Will be triggered before insert / update
+    async fn before_save<C>(mut self, _db: &C, insert: bool) -> Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        if insert {
+            self.created_at = Set(utils::get_current_time());
+            self.updated_at = self.created_at.clone();
+        } else if self.content.is_set() {
+            self.updated_at = Set(utils::get_current_time());
+        }
+
+        Ok(self)
+    }
+}
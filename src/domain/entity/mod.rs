@@ -1,7 +1,40 @@
 pub mod todo;
-pub use todo::Todo;
+pub use todo::{Model as Todo, TodoPriority};
+
+pub mod attachment;
+pub use attachment::Model as Attachment;
+
+pub mod audit_log;
+pub use audit_log::Model as AuditLog;
+
+pub mod comment;
+pub use comment::Model as Comment;
+
+pub mod lease;
+
+pub mod outbox_event;
+pub use outbox_event::Model as OutboxEvent;
 
 pub mod post;
-pub use post::Model as Post;
+pub use post::{Model as Post, PostStatus};
+
+pub mod post_revision;
+pub use post_revision::Model as PostRevision;
+
+pub mod post_slug;
+
+pub mod post_tag;
+
+pub mod tag;
+pub use tag::Model as Tag;
+
+pub mod user;
+pub use user::{Model as User, UserRole, UserSummary};
+
+pub mod webhook;
+pub use webhook::Model as Webhook;
+
+pub mod webhook_delivery;
+pub use webhook_delivery::{DeliveryStatus, Model as WebhookDelivery};
 
 pub use time::OffsetDateTime as DateTimeTZ;
@@ -0,0 +1,40 @@
+use async_trait::async_trait;
+use sea_orm::{entity::prelude::*, Set};
+
+use crate::app::utils;
+
+use super::DateTimeTZ;
+
+/// Domain event written in the same transaction as the change, until relayed
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+#[sea_orm(table_name = "outbox")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    /// id of the domain event
+    pub event_id: String,
+    /// kind of the domain event, e.g. `post.created`
+    pub kind: String,
+    /// the domain event as JSON
+    pub payload: String,
+    pub created_at: DateTimeTZ,
+    /// when the relay handed it to the sink, None while pending
+    pub delivered_at: Option<DateTimeTZ>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+#[async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    /// Will be triggered before insert / update
+    async fn before_save<C>(mut self, _db: &C, insert: bool) -> Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        if insert {
+            self.created_at = Set(utils::get_current_time());
+        }
+        Ok(self)
+    }
+}
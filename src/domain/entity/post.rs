@@ -1,11 +1,11 @@
 use async_trait::async_trait;
-use sea_orm::{entity::prelude::*, Set};
+use sea_orm::{entity::prelude::*, ActiveValue, NotSet, Set};
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
 use crate::app::utils;
 
-use super::DateTimeTZ;
+use super::{DateTimeTZ, UserSummary};
 
 /// Post to publish
 #[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize, ToSchema)]
@@ -17,35 +17,271 @@ pub struct Model {
     pub id: i32,
     #[schema(example = "Hello World")]
     pub title: String,
+    /// unique URL name, generated from title if not given
+    #[schema(example = "hello-world")]
+    pub slug: String,
     #[schema(example = "something to write")]
     pub content: String,
     #[schema(read_only)]
     pub views: i32,
+    #[schema(read_only)]
+    pub status: PostStatus,
+    /// when the Post was or will be published
+    #[schema(read_only, value_type = Option<String>)]
+    #[serde(default, with = "utils::mtime::option")]
+    pub publish_at: Option<DateTimeTZ>,
+    #[schema(read_only)]
+    pub author_id: Option<i32>,
     #[schema(read_only, value_type = String)]
     #[serde(with = "utils::mtime")]
     pub created_at: DateTimeTZ,
     #[schema(read_only, value_type = String)]
     #[serde(with = "utils::mtime")]
     pub updated_at: DateTimeTZ,
+    #[sea_orm(ignore)]
+    #[schema(read_only, example = json!(["rust", "web"]))]
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[sea_orm(ignore)]
+    #[schema(read_only, example = 0)]
+    #[serde(default)]
+    pub comment_count: i64,
+    #[sea_orm(ignore)]
+    #[schema(read_only)]
+    #[serde(default)]
+    pub author: Option<UserSummary>,
+    /// content rendered from Markdown, when asked with `format=html`
+    #[sea_orm(ignore)]
+    #[schema(read_only)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rendered: Option<utils::Rendered>,
+}
+
+/// Publishing status of a Post, only published ones are public
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    Hash,
+    EnumIter,
+    DeriveActiveEnum,
+    Serialize,
+    Deserialize,
+    ToSchema,
+)]
+#[sea_orm(rs_type = "String", db_type = "String(Some(16))")]
+#[serde(rename_all = "lowercase")]
+pub enum PostStatus {
+    #[default]
+    #[sea_orm(string_value = "draft")]
+    Draft,
+    #[sea_orm(string_value = "scheduled")]
+    Scheduled,
+    #[sea_orm(string_value = "published")]
+    Published,
+    #[sea_orm(string_value = "archived")]
+    Archived,
+}
+
+impl PostStatus {
+    /// Whether a Post may move from this status to `to`
+    pub fn can_transition_to(self, to: Self) -> bool {
+        use PostStatus::*;
+        matches!(
+            (self, to),
+            (Draft, Scheduled | Published)
+                | (Scheduled, Draft | Scheduled | Published)
+                | (Published, Draft | Archived)
+                | (Archived, Draft | Published)
+        )
+    }
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
-pub enum Relation {}
+pub enum Relation {
+    #[sea_orm(has_many = "super::post_revision::Entity")]
+    Revision,
+    #[sea_orm(has_many = "super::post_tag::Entity")]
+    PostTag,
+    #[sea_orm(has_many = "super::post_slug::Entity")]
+    Slug,
+    #[sea_orm(has_many = "super::comment::Entity")]
+    Comment,
+    #[sea_orm(
+        belongs_to = "super::user::Entity",
+        from = "Column::AuthorId",
+        to = "super::user::Column::Id",
+        on_delete = "SetNull"
+    )]
+    Author,
+}
+
+impl Related<super::post_revision::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Revision.def()
+    }
+}
+
+impl Related<super::post_slug::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Slug.def()
+    }
+}
+
+impl Related<super::comment::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Comment.def()
+    }
+}
+
+impl Related<super::user::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Author.def()
+    }
+}
+
+impl Related<super::post_tag::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::PostTag.def()
+    }
+}
+
+impl Related<super::tag::Entity> for Entity {
+    fn to() -> RelationDef {
+        super::post_tag::Relation::Tag.def()
+    }
+
+    fn via() -> Option<RelationDef> {
+        Some(super::post_tag::Relation::Post.def().rev())
+    }
+}
 
 #[async_trait]
 impl ActiveModelBehavior for ActiveModel {
     /// Will be triggered before insert / update
-    async fn before_save<C>(mut self, _db: &C, insert: bool) -> Result<Self, DbErr>
+    async fn before_save<C>(mut self, db: &C, insert: bool) -> Result<Self, DbErr>
     where
         C: ConnectionTrait,
     {
         if insert {
             self.created_at = Set(utils::get_current_time());
             self.updated_at = self.created_at.clone();
-        } else if self.title.is_set() || self.content.is_set() {
+            if !self.slug.is_set() {
+                let title = set_value(&self.title).map_or("", String::as_str);
+                self.slug = Set(unique_slug(db, title, None).await?);
+            }
+            return Ok(self);
+        }
+
+        if self.slug.is_set() || self.title.is_set() {
+            self.rename(db).await?;
+        }
+        if self.title.is_set() || self.content.is_set() || self.slug.is_set() {
             self.updated_at = Set(utils::get_current_time());
         }
 
         Ok(self)
     }
 }
+
+impl ActiveModel {
+    /// Follow a title change with a new slug unless one is given, keeping the old slug as a redirect
+    async fn rename<C: ConnectionTrait>(&mut self, db: &C) -> Result<(), DbErr> {
+        let Some(&id) = set_value(&self.id) else {
+            return Ok(());
+        };
+        let Some(current) = Entity::find_by_id(id).one(db).await? else {
+            return Ok(());
+        };
+        let slug = match (set_value(&self.slug), set_value(&self.title)) {
+            (Some(slug), _) if self.slug.is_set() => slug.clone(),
+            (None, Some(title)) if *title != current.title => {
+                unique_slug(db, title, Some(id)).await?
+            }
+            _ => return Ok(()),
+        };
+        if slug == current.slug {
+            self.slug = NotSet;
+            return Ok(());
+        }
+        // a former slug of the Post in use again is no longer a redirect
+        super::post_slug::Entity::delete_by_id(slug.clone())
+            .filter(super::post_slug::Column::PostId.eq(id))
+            .exec(db)
+            .await?;
+        super::post_slug::ActiveModel {
+            slug: Set(current.slug),
+            post_id: Set(id),
+            created_at: Set(utils::get_current_time()),
+        }
+        .insert(db)
+        .await?;
+        self.slug = Set(slug);
+        Ok(())
+    }
+}
+
+fn set_value<V: Into<Value>>(v: &ActiveValue<V>) -> Option<&V> {
+    match v {
+        ActiveValue::Set(v) | ActiveValue::Unchanged(v) => Some(v),
+        ActiveValue::NotSet => None,
+    }
+}
+
+/// Whether the slug is used by any other Post, now or formerly
+pub async fn slug_taken<C: ConnectionTrait>(
+    db: &C,
+    slug: &str,
+    id: Option<i32>,
+) -> Result<bool, DbErr> {
+    let mut cur = Entity::find().filter(Column::Slug.eq(slug));
+    let mut old = super::post_slug::Entity::find().filter(super::post_slug::Column::Slug.eq(slug));
+    if let Some(id) = id {
+        cur = cur.filter(Column::Id.ne(id));
+        old = old.filter(super::post_slug::Column::PostId.ne(id));
+    }
+    Ok(cur.count(db).await? > 0 || old.count(db).await? > 0)
+}
+
+/// Slug from the title, suffixed by a number if taken
+async fn unique_slug<C: ConnectionTrait>(
+    db: &C,
+    title: &str,
+    id: Option<i32>,
+) -> Result<String, DbErr> {
+    let mut base = utils::slugify(title);
+    if base.is_empty() {
+        base = "post".to_owned();
+    }
+    // room for the suffix
+    base.truncate(utils::SLUG_MAX_LEN - 8);
+    let mut slug = base.clone();
+    let mut n = 1;
+    while slug_taken(db, &slug, id).await? {
+        n += 1;
+        slug = format!("{}-{n}", base.trim_end_matches('-'));
+    }
+    Ok(slug)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PostStatus::*;
+
+    #[test]
+    fn test_status_transition() {
+        assert!(Draft.can_transition_to(Scheduled));
+        assert!(Draft.can_transition_to(Published));
+        assert!(!Draft.can_transition_to(Draft));
+        assert!(!Draft.can_transition_to(Archived));
+        assert!(Scheduled.can_transition_to(Scheduled));
+        assert!(!Scheduled.can_transition_to(Archived));
+        assert!(Published.can_transition_to(Archived));
+        assert!(!Published.can_transition_to(Scheduled));
+        assert!(!Archived.can_transition_to(Scheduled));
+        assert!(Archived.can_transition_to(Published));
+    }
+}
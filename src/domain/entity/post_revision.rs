@@ -0,0 +1,48 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::app::utils;
+
+use super::DateTimeTZ;
+
+/// Snapshot of a Post's title and content after a change
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize, ToSchema)]
+#[schema(as = PostRevision)]
+#[sea_orm(table_name = "post_revisions")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    #[serde(skip)]
+    pub id: i32,
+    #[schema(example = 1)]
+    pub post_id: i32,
+    /// revision number, starting from 1 for each Post
+    #[schema(example = 1)]
+    pub version: i32,
+    #[schema(example = "Hello World")]
+    pub title: String,
+    #[schema(example = "something to write")]
+    pub content: String,
+    #[schema(value_type = String)]
+    #[serde(with = "utils::mtime")]
+    pub created_at: DateTimeTZ,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::post::Entity",
+        from = "Column::PostId",
+        to = "super::post::Column::Id",
+        on_delete = "Cascade"
+    )]
+    Post,
+}
+
+impl Related<super::post::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Post.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -1,9 +1,17 @@
+use sea_orm::entity::prelude::*;
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
+use crate::app::utils;
+
+use super::DateTimeTZ;
+
 /// Item to do
-#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
-pub struct Todo {
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize, ToSchema)]
+#[schema(as = Todo)]
+#[sea_orm(table_name = "todos")]
+pub struct Model {
+    #[sea_orm(primary_key)]
     #[serde(skip_deserializing)]
     #[schema(read_only, example = 1)]
     pub id: i32,
@@ -11,4 +19,75 @@ pub struct Todo {
     pub value: String,
     #[schema(example = false)]
     pub done: bool,
+    /// when it should be done by
+    #[schema(value_type = Option<String>, example = "2024-07-10 18:00:00")]
+    #[serde(default, with = "utils::mtime::option")]
+    pub due_at: Option<DateTimeTZ>,
+    #[serde(default)]
+    pub priority: TodoPriority,
+    /// place in the manually ordered list, from 1
+    #[serde(skip_deserializing)]
+    #[schema(read_only, example = 1)]
+    pub position: i32,
+    #[schema(read_only, value_type = String)]
+    #[serde(
+        skip_deserializing,
+        default = "utils::get_current_time",
+        serialize_with = "utils::mtime::serialize"
+    )]
+    pub created_at: DateTimeTZ,
+    /// when it was marked done, None while not done
+    #[schema(read_only, value_type = Option<String>)]
+    #[serde(skip_deserializing, serialize_with = "utils::mtime::option::serialize")]
+    pub completed_at: Option<DateTimeTZ>,
+    /// Todo it is a subtask of, None at top level
+    #[serde(default)]
+    #[schema(example = json!(null))]
+    pub parent_id: Option<i32>,
+    /// done on its own once all its subtasks are done
+    #[serde(default)]
+    #[schema(example = false)]
+    pub auto_complete: bool,
 }
+
+impl Model {
+    /// Not done after its due time
+    pub fn is_overdue(&self, now: DateTimeTZ) -> bool {
+        !self.done && self.due_at.is_some_and(|v| v < now)
+    }
+}
+
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    EnumIter,
+    DeriveActiveEnum,
+    Serialize,
+    Deserialize,
+    ToSchema,
+)]
+#[sea_orm(rs_type = "i32", db_type = "Integer")]
+#[serde(rename_all = "lowercase")]
+pub enum TodoPriority {
+    #[sea_orm(num_value = 0)]
+    Low,
+    #[default]
+    #[sea_orm(num_value = 1)]
+    Medium,
+    #[sea_orm(num_value = 2)]
+    High,
+    #[sea_orm(num_value = 3)]
+    Urgent,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
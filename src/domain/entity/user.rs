@@ -0,0 +1,108 @@
+use async_trait::async_trait;
+use sea_orm::{entity::prelude::*, Set};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::app::utils;
+
+use super::DateTimeTZ;
+
+/// User of the service, authenticated by token
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize, ToSchema)]
+#[schema(as = User)]
+#[sea_orm(table_name = "users")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    #[schema(read_only, example = 1)]
+    pub id: i32,
+    #[sea_orm(unique)]
+    #[schema(example = "alice")]
+    pub name: String,
+    pub role: UserRole,
+    /// SHA-256 hex digest of the token, which is not kept
+    #[sea_orm(unique)]
+    #[serde(skip)]
+    pub token_hash: String,
+    #[schema(read_only, value_type = String)]
+    #[serde(with = "utils::mtime")]
+    pub created_at: DateTimeTZ,
+}
+
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    EnumIter,
+    DeriveActiveEnum,
+    Serialize,
+    Deserialize,
+    ToSchema,
+)]
+#[sea_orm(rs_type = "String", db_type = "String(Some(16))")]
+#[serde(rename_all = "lowercase")]
+pub enum UserRole {
+    #[default]
+    #[sea_orm(string_value = "user")]
+    User,
+    #[sea_orm(string_value = "admin")]
+    Admin,
+}
+
+/// Brief of a User shown along with their content
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct UserSummary {
+    #[schema(example = 1)]
+    pub id: i32,
+    #[schema(example = "alice")]
+    pub name: String,
+}
+
+impl Model {
+    pub fn is_admin(&self) -> bool {
+        self.role == UserRole::Admin
+    }
+
+    /// Only the author or an admin may change a piece of content
+    pub fn can_edit(&self, author_id: Option<i32>) -> bool {
+        self.is_admin() || author_id == Some(self.id)
+    }
+}
+
+impl From<Model> for UserSummary {
+    fn from(value: Model) -> Self {
+        Self {
+            id: value.id,
+            name: value.name,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(has_many = "super::post::Entity")]
+    Post,
+}
+
+impl Related<super::post::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Post.def()
+    }
+}
+
+#[async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    /// Will be triggered before insert / update
+    async fn before_save<C>(mut self, _db: &C, insert: bool) -> Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        if insert {
+            self.created_at = Set(utils::get_current_time());
+        }
+
+        Ok(self)
+    }
+}
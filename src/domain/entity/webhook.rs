@@ -0,0 +1,63 @@
+use async_trait::async_trait;
+use sea_orm::{entity::prelude::*, Set};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::app::utils;
+
+use super::DateTimeTZ;
+
+/// Subscription to domain events, delivered by POST to the url
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize, ToSchema)]
+#[schema(as = Webhook)]
+#[sea_orm(table_name = "webhooks")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    #[schema(example = 1)]
+    pub id: i32,
+    #[schema(example = "https://example.com/hooks/posts")]
+    pub url: String,
+    /// key of the HMAC signature of deliveries
+    #[serde(skip)]
+    pub secret: String,
+    /// comma separated event kinds subscribed to, empty for all
+    #[schema(example = "post.created,post.updated")]
+    pub events: String,
+    pub active: bool,
+    #[schema(value_type = String)]
+    #[serde(with = "utils::mtime")]
+    pub created_at: DateTimeTZ,
+}
+
+impl Model {
+    /// Whether the webhook takes events of the kind
+    pub fn subscribes(&self, kind: &str) -> bool {
+        self.events.is_empty() || self.events.split(',').any(|v| v == kind)
+    }
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(has_many = "super::webhook_delivery::Entity")]
+    Delivery,
+}
+
+impl Related<super::webhook_delivery::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Delivery.def()
+    }
+}
+
+#[async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    /// Will be triggered before insert / update
+    async fn before_save<C>(mut self, _db: &C, insert: bool) -> Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        if insert {
+            self.created_at = Set(utils::get_current_time());
+        }
+        Ok(self)
+    }
+}
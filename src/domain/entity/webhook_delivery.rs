@@ -0,0 +1,103 @@
+use async_trait::async_trait;
+use sea_orm::{entity::prelude::*, Set};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::app::utils;
+
+use super::DateTimeTZ;
+
+/// Delivery of an event to a webhook, with its attempts so far
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize, ToSchema)]
+#[schema(as = WebhookDelivery)]
+#[sea_orm(table_name = "webhook_deliveries")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    #[schema(example = 1)]
+    pub id: i32,
+    #[schema(example = 1)]
+    pub webhook_id: i32,
+    /// id of the domain event
+    pub event_id: String,
+    /// kind of the domain event
+    #[schema(example = "post.created")]
+    pub event: String,
+    /// JSON body posted
+    pub payload: String,
+    pub status: DeliveryStatus,
+    #[schema(example = 1)]
+    pub attempts: i32,
+    /// HTTP status of the last attempt, if answered
+    #[schema(example = 200)]
+    pub response_status: Option<i32>,
+    /// why the last attempt failed
+    pub error: Option<String>,
+    /// when to try again, while pending
+    #[schema(value_type = Option<String>)]
+    #[serde(with = "utils::mtime::option")]
+    pub next_attempt_at: Option<DateTimeTZ>,
+    #[schema(value_type = String)]
+    #[serde(with = "utils::mtime")]
+    pub created_at: DateTimeTZ,
+    #[schema(value_type = Option<String>)]
+    #[serde(with = "utils::mtime::option")]
+    pub delivered_at: Option<DateTimeTZ>,
+}
+
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    EnumIter,
+    DeriveActiveEnum,
+    Serialize,
+    Deserialize,
+    ToSchema,
+)]
+#[sea_orm(rs_type = "String", db_type = "String(Some(16))")]
+#[serde(rename_all = "lowercase")]
+pub enum DeliveryStatus {
+    /// waiting for the first or next attempt
+    #[default]
+    #[sea_orm(string_value = "pending")]
+    Pending,
+    #[sea_orm(string_value = "succeeded")]
+    Succeeded,
+    /// given up after the max attempts
+    #[sea_orm(string_value = "failed")]
+    Failed,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::webhook::Entity",
+        from = "Column::WebhookId",
+        to = "super::webhook::Column::Id",
+        on_delete = "Cascade"
+    )]
+    Webhook,
+}
+
+impl Related<super::webhook::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Webhook.def()
+    }
+}
+
+#[async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    /// Will be triggered before insert / update
+    async fn before_save<C>(mut self, _db: &C, insert: bool) -> Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        if insert {
+            self.created_at = Set(utils::get_current_time());
+        }
+        Ok(self)
+    }
+}
@@ -0,0 +1,88 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{app::utils, entity::DateTimeTZ};
+
+/// Kinds of all events, `resource.action`
+pub const EVENT_KINDS: [&str; 6] = [
+    "post.created",
+    "post.updated",
+    "post.deleted",
+    "todo.created",
+    "todo.updated",
+    "todo.deleted",
+];
+
+/// Kind of domain object changed
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Resource {
+    Post,
+    Todo,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Created,
+    Updated,
+    Deleted,
+}
+
+/// Change of domain objects
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct DomainEvent {
+    /// unique, for receivers to drop duplicates
+    #[schema(example = "5f0c6d1e-3b7a-4d52-9a63-0e1f2a3b4c5d")]
+    pub id: String,
+    pub resource: Resource,
+    pub action: Action,
+    /// ids of the changed objects
+    #[schema(example = json!([1, 2]))]
+    pub ids: Vec<i32>,
+    /// fields set by the change, empty for deletions
+    #[schema(example = json!(["title", "content"]))]
+    pub changed: Vec<String>,
+    #[schema(value_type = String)]
+    #[serde(with = "utils::mtime")]
+    pub at: DateTimeTZ,
+}
+
+impl DomainEvent {
+    pub fn new(resource: Resource, action: Action, ids: Vec<i32>, changed: &[&str]) -> Self {
+        Self {
+            id: utils::get_uuid_str(),
+            resource,
+            action,
+            ids,
+            changed: changed.iter().map(|&v| v.to_owned()).collect(),
+            at: utils::get_current_time(),
+        }
+    }
+
+    /// e.g. `post.created`, what webhooks subscribe to
+    pub fn kind(&self) -> String {
+        format!("{}.{}", self.resource, self.action)
+    }
+}
+
+impl fmt::Display for Resource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Post => "post",
+            Self::Todo => "todo",
+        })
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Created => "created",
+            Self::Updated => "updated",
+            Self::Deleted => "deleted",
+        })
+    }
+}
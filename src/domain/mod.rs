@@ -1,2 +1,3 @@
 pub mod entity;
+pub mod event;
 pub mod repository;
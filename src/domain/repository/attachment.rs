@@ -0,0 +1,32 @@
+use async_trait::async_trait;
+
+use crate::entity::Attachment;
+
+use super::Result;
+
+#[async_trait]
+pub trait AttachmentRepo {
+    async fn create(&self, item: AttachmentNew) -> Result<Attachment>;
+    async fn fetch(&self, post_id: i32, id: i32) -> Result<Attachment>;
+    async fn list(&self, post_id: i32) -> Result<Vec<Attachment>>;
+    /// Delete the attachment, returning its storage path if no other attachment shares it
+    async fn delete(&self, post_id: i32, id: i32) -> Result<Option<String>>;
+    /// Storage path of the content with the hash, if already stored
+    async fn find_path(&self, sha256: &str) -> Result<Option<String>>;
+    /// Storage paths of all attachments of the Posts
+    async fn paths(&self, post_ids: Vec<i32>) -> Result<Vec<String>>;
+    /// Those of the storage paths no attachment refers to any more
+    async fn unused(&self, paths: Vec<String>) -> Result<Vec<String>>;
+    /// Total size in bytes of all attachments of the Post
+    async fn total_size(&self, post_id: i32) -> Result<u64>;
+}
+
+#[derive(Debug)]
+pub struct AttachmentNew {
+    pub post_id: i32,
+    pub name: String,
+    pub content_type: String,
+    pub size: u64,
+    pub sha256: String,
+    pub path: String,
+}
@@ -0,0 +1,93 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use utoipa::IntoParams;
+
+use crate::{
+    app::utils,
+    entity::{AuditLog, DateTimeTZ},
+    event::Resource,
+};
+
+use super::Result;
+
+#[async_trait]
+pub trait AuditRepo {
+    async fn record(&self, item: AuditNew) -> Result<()>;
+    async fn query(&self, params: AuditQuery) -> Result<(Vec<AuditLog>, u64)>;
+}
+
+/// Change to record
+#[derive(Debug)]
+pub struct AuditNew {
+    pub actor_id: Option<i32>,
+    pub action: String,
+    pub resource: Resource,
+    pub resource_ids: Vec<i32>,
+    pub request_id: Option<String>,
+    pub client_ip: Option<String>,
+    pub before: Option<serde_json::Value>,
+    pub after: Option<serde_json::Value>,
+}
+
+/// Who makes a change and how, to record it by
+#[derive(Clone, Debug)]
+pub struct AuditContext {
+    pub actor_id: Option<i32>,
+    pub action: String,
+    pub request_id: Option<String>,
+    pub client_ip: Option<String>,
+}
+
+impl AuditContext {
+    /// The change of the objects, with snapshots of them before and after, empty if they did not exist
+    pub fn change<T: Serialize>(
+        self,
+        resource: Resource,
+        ids: Vec<i32>,
+        before: &[T],
+        after: &[T],
+    ) -> AuditNew {
+        let snapshot = |v: &[T]| match v {
+            [] => None,
+            v => serde_json::to_value(v).ok(),
+        };
+        AuditNew {
+            actor_id: self.actor_id,
+            action: self.action,
+            resource,
+            resource_ids: ids,
+            request_id: self.request_id,
+            client_ip: self.client_ip,
+            before: snapshot(before),
+            after: snapshot(after),
+        }
+    }
+}
+
+/// Audit log query
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct AuditQuery {
+    /// id of the User making changes
+    pub actor_id: Option<i32>,
+    #[param(example = "delete")]
+    pub action: Option<String>,
+    #[param(inline)]
+    pub resource: Option<Resource>,
+    /// id of a changed object
+    pub resource_id: Option<i32>,
+    pub request_id: Option<String>,
+    /// changes at or after the time
+    #[param(value_type = Option<String>, example = "2024-07-01 00:00:00")]
+    #[serde(default, with = "utils::mtime::option")]
+    pub since: Option<DateTimeTZ>,
+    /// changes before the time
+    #[param(value_type = Option<String>, example = "2024-07-02 00:00:00")]
+    #[serde(default, with = "utils::mtime::option")]
+    pub until: Option<DateTimeTZ>,
+    /// page
+    #[param(default = 1)]
+    pub page: Option<u64>,
+    /// page size
+    #[param(default = 10)]
+    pub size: Option<u64>,
+}
@@ -0,0 +1,50 @@
+use async_trait::async_trait;
+use serde::Deserialize;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::entity::Comment;
+
+use super::Result;
+
+#[async_trait]
+pub trait CommentRepo {
+    async fn create(&self, post_id: i32, item: CommentNew) -> Result<i32>;
+    async fn update(&self, post_id: i32, item: CommentUpdate) -> Result<()>;
+    /// Delete the comment together with all replies to it
+    async fn delete(&self, post_id: i32, id: i32) -> Result<()>;
+    /// Author id of the comment
+    async fn author(&self, post_id: i32, id: i32) -> Result<Option<i32>>;
+    async fn query(&self, post_id: i32, params: CommentQuery) -> Result<(Vec<Comment>, u64)>;
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CommentNew {
+    /// comment to reply to
+    pub parent_id: Option<i32>,
+    #[schema(example = "nice post")]
+    pub content: String,
+    #[serde(skip)]
+    pub author_id: Option<i32>,
+}
+
+/// Comment update params
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CommentUpdate {
+    #[serde(skip)]
+    pub id: i32,
+    #[schema(example = "nice post!")]
+    pub content: String,
+}
+
+/// Comment list query
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct CommentQuery {
+    /// List replies to this comment, or top-level comments if not set
+    pub parent_id: Option<i32>,
+    /// page
+    #[param(default = 1)]
+    pub page: Option<u64>,
+    /// page size
+    #[param(default = 10)]
+    pub size: Option<u64>,
+}
@@ -2,13 +2,25 @@ use std::{fmt, io, string, sync};
 
 use axum::extract::multipart::MultipartError;
 
+use crate::entity::PostStatus;
+
 /// entity repo errors
 #[derive(Debug)]
 // #[serde(tag = "type", content = "detail")]
 pub enum Error {
     BadRequest,
+    Unauthorized,
+    Forbidden,
     BadMultipart(String),
     IdNotFound { id: i32 },
+    RevisionNotFound { id: i32, version: i32 },
+    SlugNotFound { slug: String },
+    SlugTaken { slug: String },
+    InvalidTransition { from: PostStatus, to: PostStatus },
+    InvalidPublishAt,
+    TodoCycle { id: i32, parent_id: i32 },
+    BulkItemFailed { index: usize, error: String },
+    FileTooLarge { limit: u64 },
     DbError(String),
     LockFailed(String),
     IoError(String),
@@ -1,8 +1,29 @@
+mod attachment;
+pub use attachment::*;
+
+mod audit;
+pub use audit::*;
+
+mod comment;
+pub use comment::*;
+
 mod error;
 pub use error::*;
 
+mod outbox;
+pub use outbox::*;
+
 mod post;
 pub use post::*;
 
+mod tag;
+pub use tag::*;
+
 mod todo;
 pub use todo::*;
+
+mod user;
+pub use user::*;
+
+mod webhook;
+pub use webhook::*;
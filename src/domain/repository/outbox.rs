@@ -0,0 +1,18 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+
+use crate::entity::{DateTimeTZ, OutboxEvent};
+
+use super::Result;
+
+#[async_trait]
+pub trait OutboxRepo {
+    /// Take or renew the named lease for the holder, false if another holder has it
+    async fn acquire_lease(&self, name: &str, holder: &str, ttl: Duration) -> Result<bool>;
+    /// Up to `limit` undelivered events, in the order they were written
+    async fn pending(&self, limit: u64) -> Result<Vec<OutboxEvent>>;
+    async fn mark_delivered(&self, ids: Vec<i32>) -> Result<()>;
+    /// Remove events delivered before the time, returning how many
+    async fn purge_delivered(&self, before: DateTimeTZ) -> Result<u64>;
+}
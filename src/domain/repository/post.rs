@@ -1,41 +1,136 @@
 use async_trait::async_trait;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use utoipa::{IntoParams, ToSchema};
 
-use crate::entity::Post;
+use crate::{
+    app::utils,
+    entity::{DateTimeTZ, Post, PostRevision, PostStatus},
+};
 
-use super::Result;
+use super::{AuditContext, Result};
 
+/// Changes given an audit context are recorded in the audit log along with them, or not at all
 #[async_trait]
 pub trait PostRepo {
-    async fn create(&self, item: PostNew) -> Result<i32>;
-    async fn update(&self, item: PostUpdate) -> Result<()>;
-    async fn delete(&self, ids: Vec<i32>) -> Result<()>;
-    async fn fetch(&self, id: i32) -> Result<Post>;
+    async fn create(&self, item: PostNew, audit: Option<AuditContext>) -> Result<i32>;
+    async fn update(&self, item: PostUpdate, audit: Option<AuditContext>) -> Result<()>;
+    async fn delete(&self, ids: Vec<i32>, audit: Option<AuditContext>) -> Result<DeleteReport>;
+    async fn create_many(
+        &self,
+        items: Vec<PostNew>,
+        mode: BulkMode,
+        audit: Option<AuditContext>,
+    ) -> Result<Vec<BulkItem>>;
+    async fn update_many(
+        &self,
+        items: Vec<PostUpdate>,
+        mode: BulkMode,
+        audit: Option<AuditContext>,
+    ) -> Result<Vec<BulkItem>>;
+    /// Fetch a Post and count a view of it by the viewer
+    async fn fetch(&self, id: i32, viewer: Option<String>) -> Result<Post>;
+    /// Fetch a Post not published yet, for its author, without counting a view
+    async fn fetch_unpublished(&self, id: i32) -> Result<Post>;
+    /// Posts by ids as stored, whatever their status, without counting views
+    async fn snapshot(&self, ids: Vec<i32>) -> Result<Vec<Post>>;
+    /// Id and current slug of the Post known by the slug, now or formerly
+    async fn find_slug(&self, slug: String) -> Result<(i32, String)>;
     async fn query(&self, params: PostQuery) -> Result<(Vec<Post>, u64)>;
+    /// Posts matching the query with ids greater than `after`, in ascending id order
+    async fn scan(&self, params: PostQuery, after: i32, limit: u64) -> Result<Vec<Post>>;
+    async fn revisions(&self, id: i32, params: RevisionQuery) -> Result<(Vec<PostRevision>, u64)>;
+    async fn revision(&self, id: i32, version: i32) -> Result<PostRevision>;
+    /// Restore title and content from an earlier revision, returning the new version
+    async fn revert(&self, id: i32, version: i32, audit: Option<AuditContext>) -> Result<i32>;
+    /// Move a Post to another publishing status
+    async fn transition(
+        &self,
+        id: i32,
+        to: PostTransition,
+        audit: Option<AuditContext>,
+    ) -> Result<()>;
+    /// Publish all scheduled Posts whose time has come, returning their ids
+    async fn publish_due(&self) -> Result<Vec<i32>>;
+    /// Author ids of the Posts, leaving out missing ones
+    async fn authors(&self, ids: Vec<i32>) -> Result<Vec<(i32, Option<i32>)>>;
+    /// Hit and miss counters, if the store is cached
+    fn cache_stats(&self) -> Option<CacheStats> {
+        None
+    }
+    /// Drop the Post and list pages showing it from the cache, if the store is cached,
+    /// after a change to what it shows from elsewhere
+    fn evict(&self, _id: i32) -> Result<()> {
+        Ok(())
+    }
 }
 
 #[derive(Debug, Deserialize, ToSchema)]
 pub struct PostNew {
     pub title: String,
     pub content: String,
+    /// generated from title if not set
+    #[schema(example = "hello-world")]
+    pub slug: Option<String>,
+    #[serde(default)]
+    #[schema(example = json!(["rust", "web"]))]
+    pub tags: Vec<String>,
+    /// draft if not set
+    #[schema(example = "draft")]
+    pub status: Option<PostStatus>,
+    /// required when scheduling, in the future
+    #[schema(value_type = Option<String>, example = "2024-06-01 08:00:00")]
+    #[serde(default, with = "utils::mtime::option")]
+    pub publish_at: Option<DateTimeTZ>,
+    #[serde(skip)]
+    pub author_id: Option<i32>,
+    /// status and publish time taken as exported, rather than as a change from draft
+    #[serde(skip)]
+    pub imported: bool,
+}
+
+/// Publishing status change
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct PostTransition {
+    #[schema(example = "scheduled")]
+    pub status: PostStatus,
+    /// required when scheduling, in the future
+    #[schema(value_type = Option<String>, example = "2024-06-01 08:00:00")]
+    #[serde(default, with = "utils::mtime::option")]
+    pub publish_at: Option<DateTimeTZ>,
 }
 
 /// Post update params
 #[derive(Debug, Deserialize, ToSchema)]
 pub struct PostUpdate {
     pub id: i32,
+    /// a changed title also changes the slug, unless one is given
     pub title: Option<String>,
     pub content: Option<String>,
+    /// former slugs keep redirecting to the Post
+    pub slug: Option<String>,
+    /// replace all tags of the Post if set
+    pub tags: Option<Vec<String>>,
 }
 
 /// Post search query
-#[derive(Debug, Deserialize, IntoParams)]
+#[derive(Clone, Debug, Deserialize, IntoParams)]
 pub struct PostQuery {
     /// Search by title, case insensitive
     pub title: Option<String>,
     /// Search by content, case insensitive
     pub content: Option<String>,
+    /// Search by author
+    pub author_id: Option<i32>,
+    /// Only list Posts in this status, published by default.
+    /// Others need a signed-in User, and are limited to their own Posts unless an admin
+    #[param(inline)]
+    pub status: Option<PostStatus>,
+    /// Search by comma-separated tags
+    #[param(example = "rust,web")]
+    pub tags: Option<String>,
+    /// Match any or all of the tags
+    #[param(inline)]
+    pub tag_match: Option<TagMatch>,
     /// page
     #[param(default = 1)]
     pub page: Option<u64>,
@@ -44,8 +139,97 @@ pub struct PostQuery {
     pub size: Option<u64>,
 }
 
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum TagMatch {
+    #[default]
+    Any,
+    All,
+}
+
+/// How a bulk operation treats failed items
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkMode {
+    /// roll back everything if any item fails
+    #[default]
+    AllOrNothing,
+    /// keep the items that succeed, and report the failed ones
+    BestEffort,
+}
+
+/// Bulk Post creation
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct PostBulkNew {
+    #[serde(default)]
+    pub mode: BulkMode,
+    pub items: Vec<PostNew>,
+}
+
+/// Bulk Post update
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct PostBulkUpdate {
+    #[serde(default)]
+    pub mode: BulkMode,
+    pub items: Vec<PostUpdate>,
+}
+
+/// Result of one item in a bulk operation
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BulkItem {
+    /// position of the item in the request
+    pub index: usize,
+    /// id of the Post, if succeeded
+    pub id: Option<i32>,
+    /// reason of failure
+    pub error: Option<String>,
+}
+
+/// Post ids deleted, and those not found
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct DeleteReport {
+    pub deleted: Vec<i32>,
+    pub not_found: Vec<i32>,
+}
+
 #[derive(Deserialize, IntoParams)]
 pub struct PostDelete {
     #[param(example = "1,2,3")]
     pub ids: String,
 }
+
+/// Post revision list query
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct RevisionQuery {
+    /// page
+    #[param(default = 1)]
+    pub page: Option<u64>,
+    /// page size
+    #[param(default = 10)]
+    pub size: Option<u64>,
+}
+
+/// Post revisions to compare
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct RevisionDiffQuery {
+    /// base version
+    #[param(example = 1)]
+    pub from: i32,
+    /// target version
+    #[param(example = 2)]
+    pub to: i32,
+}
+
+/// Post cache settings and counters
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct CacheStats {
+    /// max cached Posts, and max cached list pages
+    pub capacity: usize,
+    pub ttl_secs: u64,
+    /// Posts and list pages cached now
+    pub entries: usize,
+    pub fetch_hits: u64,
+    pub fetch_misses: u64,
+    pub query_hits: u64,
+    pub query_misses: u64,
+}
@@ -0,0 +1,20 @@
+use async_trait::async_trait;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use super::Result;
+
+#[async_trait]
+pub trait TagRepo {
+    /// All tags with the number of Posts using each, most used first
+    async fn list(&self) -> Result<Vec<TagCount>>;
+}
+
+/// Tag usage
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TagCount {
+    #[schema(example = "rust")]
+    pub name: String,
+    #[schema(example = 3)]
+    pub posts: i64,
+}
@@ -1,16 +1,58 @@
+use async_trait::async_trait;
 use serde::Deserialize;
 use utoipa::{IntoParams, ToSchema};
 
-use crate::entity::Todo;
+use crate::{
+    app::utils,
+    entity::{DateTimeTZ, Todo, TodoPriority},
+};
 
-use super::Result;
+use super::{AuditContext, Result};
 
+#[async_trait]
 pub trait TodoRepo {
-    fn create(&self, item: Todo) -> Result<i32>;
-    fn update(&self, item: TodoUpdate) -> Result<()>;
-    fn delete(&self, ids: Vec<i32>) -> Result<()>;
-    fn fetch(&self, id: i32) -> Result<Todo>;
-    fn query(&self, params: TodoQuery) -> Result<Vec<Todo>>;
+    /// Changes are audited along with every Todo they reach, if given the context
+    async fn create(&self, item: Todo, audit: Option<AuditContext>) -> Result<(i32, TodoChanges)>;
+    /// Completing a Todo may complete its ancestors set to auto-complete
+    async fn update(&self, item: TodoUpdate, audit: Option<AuditContext>) -> Result<TodoChanges>;
+    /// Delete the Todos with all their subtasks
+    async fn delete(&self, ids: Vec<i32>, audit: Option<AuditContext>) -> Result<TodoChanges>;
+    async fn fetch(&self, id: i32) -> Result<Todo>;
+    /// Page of matching Todos in the asked order, with the total of them
+    async fn query(&self, params: TodoQuery) -> Result<(Vec<Todo>, u64)>;
+    /// Move a Todo to the position, from 1, shifting those between.
+    /// Positions beyond the list move it to the end.
+    async fn reorder(
+        &self,
+        id: i32,
+        position: i32,
+        audit: Option<AuditContext>,
+    ) -> Result<TodoChanges>;
+    /// The Todo and its subtasks at any depth, parents before their children
+    async fn subtree(&self, id: i32) -> Result<Vec<Todo>>;
+    /// Move the Todo with its subtasks under another parent, or to top level
+    async fn reparent(
+        &self,
+        id: i32,
+        parent_id: Option<i32>,
+        audit: Option<AuditContext>,
+    ) -> Result<TodoChanges>;
+    /// Save what is kept only in memory, if the store does so
+    async fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Ids of the Todos a change reached, including the side effects on others
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct TodoChanges {
+    pub deleted: Vec<i32>,
+    /// marked done, by the change itself, cascading or auto-completion
+    pub completed: Vec<i32>,
+    /// given another position
+    pub moved: Vec<i32>,
+    /// given another parent
+    pub reparented: Vec<i32>,
 }
 
 /// Todo update params
@@ -19,6 +61,41 @@ pub struct TodoUpdate {
     pub id: i32,
     pub value: Option<String>,
     pub done: Option<bool>,
+    /// null to clear it
+    #[schema(value_type = Option<String>, example = "2024-07-10 18:00:00")]
+    #[serde(default, with = "utils::mtime::double_option")]
+    pub due_at: Option<Option<DateTimeTZ>>,
+    pub priority: Option<TodoPriority>,
+    pub auto_complete: Option<bool>,
+    /// when marking done, also mark all its subtasks done
+    #[serde(default)]
+    pub cascade: bool,
+}
+
+impl TodoUpdate {
+    pub fn is_empty(&self) -> bool {
+        self.value.is_none()
+            && self.done.is_none()
+            && self.due_at.is_none()
+            && self.priority.is_none()
+            && self.auto_complete.is_none()
+    }
+}
+
+/// New place of a Todo in the manual order
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct TodoReorder {
+    /// from 1
+    #[schema(example = 1)]
+    pub position: i32,
+}
+
+/// New parent of a Todo
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct TodoMove {
+    /// None to move it to top level
+    #[schema(example = 1)]
+    pub parent_id: Option<i32>,
 }
 
 /// Todo search query
@@ -28,6 +105,69 @@ pub struct TodoQuery {
     pub value: Option<String>,
     /// Search by `done` status.
     pub done: Option<bool>,
+    /// only those not done after their due time, or the others
+    pub overdue: Option<bool>,
+    /// due at or after the time
+    #[param(value_type = Option<String>, example = "2024-07-01 00:00:00")]
+    #[serde(default, with = "utils::mtime::option")]
+    pub due_after: Option<DateTimeTZ>,
+    /// due before the time
+    #[param(value_type = Option<String>, example = "2024-08-01 00:00:00")]
+    #[serde(default, with = "utils::mtime::option")]
+    pub due_before: Option<DateTimeTZ>,
+    #[param(inline)]
+    pub priority: Option<TodoPriority>,
+    /// only direct subtasks of the Todo
+    pub parent_id: Option<i32>,
+    #[param(inline)]
+    pub sort: Option<TodoSort>,
+    /// direction of the sort key, the usual one of the key if not set
+    #[param(inline)]
+    pub order: Option<SortOrder>,
+    /// page
+    #[param(default = 1)]
+    pub page: Option<u64>,
+    /// page size
+    #[param(default = 10)]
+    pub size: Option<u64>,
+}
+
+/// Sort key of listed Todos, ties kept in manual order
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TodoSort {
+    /// manual order
+    #[default]
+    Position,
+    /// soonest due first, those without due time last in either direction
+    DueAt,
+    /// highest first
+    Priority,
+    /// newest first
+    CreatedAt,
+}
+
+impl TodoSort {
+    /// Direction used when the query does not ask for one
+    pub fn default_order(self) -> SortOrder {
+        match self {
+            Self::Position | Self::DueAt => SortOrder::Asc,
+            Self::Priority | Self::CreatedAt => SortOrder::Desc,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct TodoDoneQuery {
+    /// also mark all its subtasks done
+    pub cascade: Option<bool>,
 }
 
 #[derive(Deserialize, IntoParams)]
@@ -0,0 +1,26 @@
+use async_trait::async_trait;
+use serde::Deserialize;
+use utoipa::ToSchema;
+
+use crate::entity::{User, UserRole};
+
+use super::Result;
+
+#[async_trait]
+pub trait UserRepo {
+    /// Create a User along with a new token, returning both.
+    /// An admin needs to be created by an admin, unless it is the first User.
+    async fn create(&self, item: UserNew, by_admin: bool) -> Result<(User, String)>;
+    async fn fetch(&self, id: i32) -> Result<User>;
+    /// Find the User owning the token
+    async fn authenticate(&self, token: &str) -> Result<Option<User>>;
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UserNew {
+    #[schema(example = "alice")]
+    pub name: String,
+    /// user if not set; only admins may create admins, except for the first User
+    #[serde(default)]
+    pub role: UserRole,
+}
@@ -0,0 +1,72 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    entity::{DateTimeTZ, DeliveryStatus, Webhook, WebhookDelivery},
+    event::DomainEvent,
+};
+
+use super::Result;
+
+#[async_trait]
+pub trait WebhookRepo {
+    async fn create(&self, item: WebhookNew) -> Result<Webhook>;
+    async fn list(&self) -> Result<Vec<Webhook>>;
+    async fn delete(&self, id: i32) -> Result<()>;
+    /// Queue deliveries of the event to active webhooks subscribing to it, returning how many.
+    /// Webhooks with a delivery of the event already are skipped
+    async fn enqueue(&self, event: &DomainEvent) -> Result<u64>;
+    /// Take up to `limit` due deliveries with their webhooks,
+    /// keeping others from taking them again for the lease
+    async fn claim_due(
+        &self,
+        limit: u64,
+        lease: Duration,
+    ) -> Result<Vec<(WebhookDelivery, Webhook)>>;
+    /// Record an attempt of the delivery
+    async fn record(&self, id: i32, attempt: DeliveryAttempt) -> Result<()>;
+    async fn deliveries(&self, params: DeliveryQuery) -> Result<(Vec<WebhookDelivery>, u64)>;
+}
+
+/// New webhook subscription
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct WebhookNew {
+    #[schema(example = "https://example.com/hooks/posts")]
+    pub url: String,
+    /// key of the HMAC signature, generated if not given
+    pub secret: Option<String>,
+    /// event kinds to deliver, all if empty
+    #[serde(default)]
+    #[schema(example = json!(["post.created", "post.updated"]))]
+    pub events: Vec<String>,
+}
+
+/// Outcome of one delivery attempt
+#[derive(Debug)]
+pub struct DeliveryAttempt {
+    pub status: DeliveryStatus,
+    pub response_status: Option<i32>,
+    pub error: Option<String>,
+    /// when to try again, if still pending
+    pub next_attempt_at: Option<DateTimeTZ>,
+}
+
+/// Delivery log query
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct DeliveryQuery {
+    pub webhook_id: Option<i32>,
+    #[param(inline)]
+    pub status: Option<DeliveryStatus>,
+    /// event kind
+    #[param(example = "post.created")]
+    pub event: Option<String>,
+    /// page
+    #[param(default = 1)]
+    pub page: Option<u64>,
+    /// page size
+    #[param(default = 10)]
+    pub size: Option<u64>,
+}
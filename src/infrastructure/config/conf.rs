@@ -7,6 +7,9 @@ pub struct ServerConfig {
     #[serde(skip_deserializing)]
     pub run_local: bool,
     pub port: Option<u16>,
+    /// addresses or networks of reverse proxies, whose forwarded client ip is taken
+    #[serde(default)]
+    pub trusted_proxies: Vec<String>,
 }
 
 #[derive(Clone, Default, Serialize, Deserialize)]
@@ -21,6 +24,22 @@ pub struct PgSqlConfig {
     pub log_mode: bool,
     #[serde(default)]
     pub auto_migrate: bool,
+    /// read replicas, taking read-only queries in turn
+    #[serde(default)]
+    pub replica_urls: Vec<String>,
+    pub replica_check_secs: Option<u64>,
+    /// pool options, taking effect on restart
+    pub max_connections: Option<u32>,
+    pub min_connections: Option<u32>,
+    pub acquire_timeout_secs: Option<u64>,
+    pub idle_timeout_secs: Option<u64>,
+    pub max_lifetime_secs: Option<u64>,
+    pub statement_timeout_ms: Option<u64>,
+    /// timeout of each connection attempt and health check,
+    /// also of taking a pool connection unless `acquire_timeout_secs` is set
+    pub connect_timeout_secs: Option<u64>,
+    /// how long to keep retrying the database on startup
+    pub startup_deadline_secs: Option<u64>,
 }
 
 #[derive(Clone, Default, Serialize, Deserialize)]
@@ -30,6 +49,98 @@ pub struct ChildProcConfig {
     pub timeout_secs: u64,
 }
 
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct ViewCounterConfig {
+    /// interval to flush buffered post views to db, 10s if not set
+    pub flush_interval_secs: Option<u64>,
+    /// count a client's views of the same post only once within the window, 0 to disable
+    #[serde(default)]
+    pub dedup_window_secs: u64,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct PostConfig {
+    /// interval to check scheduled posts to publish, 30s if not set
+    pub publish_check_secs: Option<u64>,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct PostCacheConfig {
+    /// max cached posts, and max cached list pages, 1000 if not set, 0 to disable
+    pub size: Option<usize>,
+    /// seconds to keep cached entries, 60 if not set
+    pub ttl_secs: Option<u64>,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct AttachmentConfig {
+    /// directory to store attachment files, `attachments` if not set
+    pub dir: Option<String>,
+    /// max size in bytes of one file, 10MiB if not set
+    pub max_file_size: Option<u64>,
+    /// max total size in bytes of all files of a post, 100MiB if not set
+    pub max_post_size: Option<u64>,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct WebhookConfig {
+    /// attempts of a delivery before giving up, 8 if not set
+    pub max_attempts: Option<u32>,
+    /// delay before the first retry, doubled for each later one, 10s if not set
+    pub retry_base_secs: Option<u64>,
+    /// timeout of one delivery request, 10s if not set
+    pub timeout_secs: Option<u64>,
+    /// interval to look for due retries, 5s if not set
+    pub poll_secs: Option<u64>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TodoBackend {
+    /// lost on restart, and separate per instance
+    #[default]
+    Memory,
+    /// the database of `[db]`
+    Postgres,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct TodoConfig {
+    /// where to keep todos, `memory` if not set, taking effect on restart
+    #[serde(default)]
+    pub backend: TodoBackend,
+    /// file to snapshot memory backend todos to, with a journal of later changes beside it,
+    /// lost on restart if not set
+    pub snapshot_path: Option<String>,
+    /// interval between snapshots, 60s if not set
+    pub snapshot_secs: Option<u64>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutboxSinkKind {
+    #[default]
+    Log,
+    Http,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct OutboxConfig {
+    /// where to relay post events, `log` if not set
+    #[serde(default)]
+    pub sink: OutboxSinkKind,
+    /// url to POST events to, for the http sink
+    pub url: Option<String>,
+    /// interval to look for new events, 1s if not set
+    pub poll_secs: Option<u64>,
+    /// how long an instance keeps relaying after last renewing its lease, 30s if not set
+    pub lease_secs: Option<u64>,
+    /// events read at a time, 100 if not set
+    pub batch_size: Option<u64>,
+    /// how long delivered events are kept, 7 days if not set
+    pub retention_secs: Option<u64>,
+}
+
 #[derive(Clone, Default, Serialize, Deserialize)]
 pub struct Config {
     #[serde(default)]
@@ -39,6 +150,20 @@ pub struct Config {
     pub db: PgSqlConfig,
     #[serde(default)]
     pub py: ChildProcConfig,
+    #[serde(default)]
+    pub views: ViewCounterConfig,
+    #[serde(default)]
+    pub post: PostConfig,
+    #[serde(default)]
+    pub post_cache: PostCacheConfig,
+    #[serde(default)]
+    pub attachment: AttachmentConfig,
+    #[serde(default)]
+    pub webhook: WebhookConfig,
+    #[serde(default)]
+    pub outbox: OutboxConfig,
+    #[serde(default)]
+    pub todo: TodoConfig,
 }
 
 impl Config {
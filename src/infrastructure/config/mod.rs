@@ -20,6 +20,7 @@ use time::{
 };
 
 use conf::Config;
+pub use conf::{OutboxSinkKind, TodoBackend};
 
 use crate::{
     app::{self, log::*},
@@ -45,7 +46,9 @@ pub static JSON_TIME_FORMAT: Lazy<Vec<FormatItem>> = Lazy::new(|| {
     .unwrap()
 });
 
-pub static LOCAL_OFFSET: Lazy<UtcOffset> = Lazy::new(|| UtcOffset::current_local_offset().unwrap());
+// the local offset is only determinable before other threads start, as `init` does; UTC otherwise, e.g. in tests
+pub static LOCAL_OFFSET: Lazy<UtcOffset> =
+    Lazy::new(|| UtcOffset::current_local_offset().unwrap_or(UtcOffset::UTC));
 
 pub fn init() -> app::Result<()> {
     println!("current timezone: {:?}", *LOCAL_OFFSET);
@@ -69,6 +72,13 @@ pub fn init() -> app::Result<()> {
     Ok(())
 }
 
+/// Set the config for tests, without nacos or local file
+#[cfg(test)]
+pub fn init_test(cs: &str) -> app::Result<()> {
+    set_config(Config::parse(cs)?, true)?;
+    Ok(())
+}
+
 type Callback = Box<dyn FnMut(&Config, &Config) + Send + Sync>; // callback type for online config change; may mutate captured vars
 
 static C: Lazy<Mutex<Config>> = Lazy::new(Mutex::default); // global config instance
@@ -100,7 +110,7 @@ pub fn peek_config<'a>() -> Result<MutexGuard<'a, Config>> {
 }
 
 pub fn is_local() -> bool {
-    C.lock().map_or(false, |v| v.server.run_local)
+    C.lock().is_ok_and(|v| v.server.run_local)
 }
 
 pub fn add_callback(f: Callback) -> Result<()> {
@@ -0,0 +1,29 @@
+use tokio::sync::broadcast;
+
+use crate::{app::log::*, event::DomainEvent};
+
+/// events kept for slow subscribers before they start missing some
+const CAPACITY: usize = 1024;
+
+/// In-process broadcast of domain events
+#[derive(Clone)]
+pub struct EventBus(broadcast::Sender<DomainEvent>);
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self(broadcast::channel(CAPACITY).0)
+    }
+}
+
+impl EventBus {
+    pub fn publish(&self, event: DomainEvent) {
+        debug!(?event, "publish domain event");
+        // no subscribers is fine
+        self.0.send(event).ok();
+    }
+
+    /// Receive events published from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<DomainEvent> {
+        self.0.subscribe()
+    }
+}
@@ -1,3 +1,6 @@
 pub mod config;
+pub mod event_bus;
+pub mod outbox;
 pub mod persistence;
 pub mod shell;
+pub mod webhook;
@@ -0,0 +1,217 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use tokio::time::Instant;
+
+use crate::{
+    app::{log::*, utils},
+    event::DomainEvent,
+};
+
+use super::{
+    config::{self, OutboxSinkKind},
+    persistence::OutboxStore,
+    webhook::WebhookQueue,
+};
+
+const LEASE_NAME: &str = "outbox-relay";
+const DEFAULT_LEASE: Duration = Duration::from_secs(30);
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+const DEFAULT_BATCH: u64 = 100;
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_RETENTION: Duration = Duration::from_secs(7 * 24 * 3600);
+/// interval between sweeps of delivered events
+const SWEEP_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Where relayed events go
+#[async_trait]
+pub trait OutboxSink {
+    async fn send(&self, event: &DomainEvent) -> Result<(), String>;
+}
+
+/// Writes events to the log
+pub struct LogSink;
+
+#[async_trait]
+impl OutboxSink for LogSink {
+    async fn send(&self, event: &DomainEvent) -> Result<(), String> {
+        info!(kind = event.kind(), ?event, "outbox event");
+        Ok(())
+    }
+}
+
+/// POSTs events as JSON to a local service
+pub struct HttpSink {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl HttpSink {
+    pub fn new(url: String) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url,
+        }
+    }
+}
+
+#[async_trait]
+impl OutboxSink for HttpSink {
+    async fn send(&self, event: &DomainEvent) -> Result<(), String> {
+        let res = self
+            .client
+            .post(&self.url)
+            .timeout(HTTP_TIMEOUT)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(serde_json::to_string(event).map_err(|e| e.to_string())?)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if !res.status().is_success() {
+            return Err(format!("unexpected response status {}", res.status()));
+        }
+        Ok(())
+    }
+}
+
+/// Relay outbox events to the configured sink and to webhooks in background,
+/// on only the instance holding the lease
+pub fn spawn_relay(store: OutboxStore, webhooks: WebhookQueue) {
+    tokio::spawn(relay(store, webhooks));
+}
+
+async fn relay(store: OutboxStore, webhooks: WebhookQueue) {
+    let holder = utils::get_uuid_str();
+    let mut leading = false;
+    let mut swept: Option<Instant> = None;
+    let mut sink: Option<(
+        OutboxSinkKind,
+        Option<String>,
+        Box<dyn OutboxSink + Send + Sync>,
+    )> = None;
+    loop {
+        let conf = config::peek_config()
+            .map(|c| c.outbox.clone())
+            .unwrap_or_default();
+        let lease = conf.lease_secs.map_or(DEFAULT_LEASE, Duration::from_secs);
+        let batch = conf.batch_size.filter(|&v| v > 0).unwrap_or(DEFAULT_BATCH);
+        // rebuilt when its config changed
+        if !matches!(&sink, Some((kind, url, _)) if *kind == conf.sink && *url == conf.url) {
+            sink = Some((
+                conf.sink,
+                conf.url.clone(),
+                new_sink(conf.sink, conf.url.clone()),
+            ));
+        }
+
+        let mut n = 0;
+        match store.acquire_lease(LEASE_NAME, &holder, lease).await {
+            Ok(true) => {
+                if !leading {
+                    info!(holder, "outbox relay lease taken");
+                    leading = true;
+                }
+                if let Some((_, _, sink)) = &sink {
+                    n = relay_batch(&store, sink.as_ref(), &webhooks, batch, lease).await;
+                }
+                if swept.is_none_or(|t| t.elapsed() >= SWEEP_INTERVAL) {
+                    let retention = conf
+                        .retention_secs
+                        .map_or(DEFAULT_RETENTION, Duration::from_secs);
+                    sweep(&store, retention).await;
+                    swept = Some(Instant::now());
+                }
+            }
+            Ok(false) => {
+                if leading {
+                    warn!(holder, "outbox relay lease lost");
+                    leading = false;
+                }
+            }
+            Err(e) => error!(%e, "take outbox relay lease failed"),
+        }
+
+        if n < batch {
+            let poll = conf
+                .poll_secs
+                .filter(|&v| v > 0)
+                .map_or(DEFAULT_POLL_INTERVAL, Duration::from_secs);
+            tokio::time::sleep(poll).await;
+        }
+    }
+}
+
+fn new_sink(kind: OutboxSinkKind, url: Option<String>) -> Box<dyn OutboxSink + Send + Sync> {
+    match (kind, url) {
+        (OutboxSinkKind::Log, _) => Box::new(LogSink),
+        (OutboxSinkKind::Http, Some(url)) => Box::new(HttpSink::new(url)),
+        (OutboxSinkKind::Http, None) => {
+            error!("no url of the outbox http sink, log events instead");
+            Box::new(LogSink)
+        }
+    }
+}
+
+/// Remove events delivered longer ago than the retention
+async fn sweep(store: &OutboxStore, retention: Duration) {
+    match store
+        .purge_delivered(utils::get_current_time() - retention)
+        .await
+    {
+        Ok(0) => {}
+        Ok(n) => info!(n, "delivered outbox events removed"),
+        Err(e) => error!(%e, "remove delivered outbox events failed"),
+    }
+}
+
+/// Send pending events in order to the sink, then queue their webhook deliveries.
+/// Stops at the first failure so that it is retried first, or before the lease could expire.
+/// Returns how many were handled.
+async fn relay_batch(
+    store: &OutboxStore,
+    sink: &(dyn OutboxSink + Send + Sync),
+    webhooks: &WebhookQueue,
+    batch: u64,
+    lease: Duration,
+) -> u64 {
+    let started = Instant::now();
+    let pending = match store.pending(batch).await {
+        Ok(v) => v,
+        Err(e) => {
+            error!(%e, "read outbox failed");
+            return 0;
+        }
+    };
+    let mut done = Vec::with_capacity(pending.len());
+    for item in pending {
+        if started.elapsed() > lease / 2 {
+            break;
+        }
+        match serde_json::from_str::<DomainEvent>(&item.payload) {
+            Ok(event) => {
+                if let Err(e) = sink.send(&event).await {
+                    warn!(
+                        e,
+                        id = item.id,
+                        kind = item.kind,
+                        "relay outbox event failed"
+                    );
+                    break;
+                }
+                // same event id as sent to the sink, queued only once if relayed again
+                if let Err(e) = webhooks.push(&event).await {
+                    error!(%e, id = item.id, "queue webhook deliveries failed");
+                    break;
+                }
+            }
+            // would block all later events if kept
+            Err(e) => error!(%e, id = item.id, "invalid outbox event, skipped"),
+        }
+        done.push(item.id);
+    }
+    let n = done.len() as u64;
+    if let Err(e) = store.mark_delivered(done).await {
+        error!(%e, "mark outbox events delivered failed");
+    }
+    n
+}
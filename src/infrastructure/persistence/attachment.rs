@@ -0,0 +1,145 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use sea_orm::sea_query::Alias;
+use sea_orm::*;
+
+use crate::{
+    app::log::*,
+    entity::{
+        attachment::{ActiveModel, Column, Entity},
+        post, Attachment,
+    },
+    repository::{AttachmentNew, AttachmentRepo, Error, Result},
+};
+
+use super::conn::DbConns;
+
+pub type AttachmentStore = Arc<dyn AttachmentRepo + Send + Sync>;
+
+pub(super) fn get_attachment_store(conns: &DbConns) -> AttachmentStore {
+    Arc::new(AttachmentRepoImp {
+        db: conns.primary().clone(),
+        reads: conns.clone(),
+    })
+}
+
+/// Database Attachment store
+struct AttachmentRepoImp {
+    db: DbConn,
+    reads: DbConns,
+}
+
+#[async_trait]
+impl AttachmentRepo for AttachmentRepoImp {
+    async fn create(&self, item: AttachmentNew) -> Result<Attachment> {
+        info!(?item, "create attachment");
+        if post::Entity::find_by_id(item.post_id)
+            .one(&self.db)
+            .await?
+            .is_none()
+        {
+            return Err(Error::IdNotFound { id: item.post_id });
+        }
+        let res = ActiveModel {
+            post_id: Set(item.post_id),
+            name: Set(item.name),
+            content_type: Set(item.content_type),
+            size: Set(item.size as i64),
+            sha256: Set(item.sha256),
+            path: Set(item.path),
+            ..Default::default()
+        }
+        .insert(&self.db)
+        .await?;
+        Ok(res)
+    }
+
+    async fn fetch(&self, post_id: i32, id: i32) -> Result<Attachment> {
+        info!(?post_id, ?id, "fetch attachment");
+        find_attachment(&self.db, post_id, id).await
+    }
+
+    async fn list(&self, post_id: i32) -> Result<Vec<Attachment>> {
+        info!(?post_id, "list attachments");
+        self.reads
+            .read(|db| {
+                Box::pin(async move {
+                    Ok(Entity::find()
+                        .filter(Column::PostId.eq(post_id))
+                        .order_by_asc(Column::Id)
+                        .all(db)
+                        .await?)
+                })
+            })
+            .await
+    }
+
+    async fn delete(&self, post_id: i32, id: i32) -> Result<Option<String>> {
+        info!(?post_id, ?id, "delete attachment");
+        let txn = self.db.begin().await?;
+        let item = find_attachment(&txn, post_id, id).await?;
+        Entity::delete_by_id(id).exec(&txn).await?;
+        let shared = Entity::find()
+            .filter(Column::Path.eq(&item.path))
+            .count(&txn)
+            .await?;
+        txn.commit().await?;
+        Ok((shared == 0).then_some(item.path))
+    }
+
+    async fn find_path(&self, sha256: &str) -> Result<Option<String>> {
+        Ok(Entity::find()
+            .select_only()
+            .column(Column::Path)
+            .filter(Column::Sha256.eq(sha256))
+            .into_tuple()
+            .one(&self.db)
+            .await?)
+    }
+
+    async fn paths(&self, post_ids: Vec<i32>) -> Result<Vec<String>> {
+        Ok(Entity::find()
+            .select_only()
+            .column(Column::Path)
+            .distinct()
+            .filter(Column::PostId.is_in(post_ids))
+            .into_tuple()
+            .all(&self.db)
+            .await?)
+    }
+
+    async fn unused(&self, mut paths: Vec<String>) -> Result<Vec<String>> {
+        let used: Vec<String> = Entity::find()
+            .select_only()
+            .column(Column::Path)
+            .distinct()
+            .filter(Column::Path.is_in(paths.clone()))
+            .into_tuple()
+            .all(&self.db)
+            .await?;
+        paths.retain(|x| !used.contains(x));
+        Ok(paths)
+    }
+
+    async fn total_size(&self, post_id: i32) -> Result<u64> {
+        let size: Option<i64> = Entity::find()
+            .select_only()
+            // sum of bigint is numeric in Postgres
+            .column_as(Column::Size.sum().cast_as(Alias::new("bigint")), "size")
+            .filter(Column::PostId.eq(post_id))
+            .into_tuple()
+            .one(&self.db)
+            .await?
+            .flatten();
+        Ok(size.unwrap_or_default() as u64)
+    }
+}
+
+async fn find_attachment<C: ConnectionTrait>(db: &C, post_id: i32, id: i32) -> Result<Attachment> {
+    Entity::find_by_id(id)
+        .filter(Column::PostId.eq(post_id))
+        .one(db)
+        .await?
+        .ok_or(Error::IdNotFound { id })
+}
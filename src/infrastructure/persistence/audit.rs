@@ -0,0 +1,103 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use sea_orm::sea_query::Expr;
+use sea_orm::*;
+
+use crate::{
+    app::log::*,
+    entity::{audit_log, AuditLog},
+    repository::{AuditNew, AuditQuery, AuditRepo, Result},
+};
+
+use super::conn::DbConns;
+
+pub type AuditStore = Arc<dyn AuditRepo + Send + Sync>;
+
+pub(super) fn get_audit_store(conns: &DbConns) -> AuditStore {
+    Arc::new(AuditRepoImp {
+        db: conns.primary().clone(),
+        reads: conns.clone(),
+    })
+}
+
+/// Database audit log store
+struct AuditRepoImp {
+    db: DbConn,
+    reads: DbConns,
+}
+
+/// Write the audit log entry, in the transaction of the change if it is to go with it
+pub(super) async fn append<C: ConnectionTrait>(db: &C, item: AuditNew) -> Result<()> {
+    debug!(?item, "record audit log");
+    audit_log::ActiveModel {
+        actor_id: Set(item.actor_id),
+        action: Set(item.action),
+        resource: Set(item.resource.to_string()),
+        resource_ids: Set(item
+            .resource_ids
+            .iter()
+            .map(i32::to_string)
+            .collect::<Vec<_>>()
+            .join(",")),
+        request_id: Set(item.request_id),
+        client_ip: Set(item.client_ip),
+        before: Set(item.before),
+        after: Set(item.after),
+        ..Default::default()
+    }
+    .insert(db)
+    .await?;
+    Ok(())
+}
+
+#[async_trait]
+impl AuditRepo for AuditRepoImp {
+    async fn record(&self, item: AuditNew) -> Result<()> {
+        append(&self.db, item).await
+    }
+
+    async fn query(&self, params: AuditQuery) -> Result<(Vec<AuditLog>, u64)> {
+        info!(?params, "query audit log");
+        let page = params.page.unwrap_or(1).max(1);
+        let size = params.size.unwrap_or(10);
+
+        let mut cur = audit_log::Entity::find();
+        if let Some(v) = params.actor_id {
+            cur = cur.filter(audit_log::Column::ActorId.eq(v));
+        }
+        if let Some(v) = params.action {
+            cur = cur.filter(audit_log::Column::Action.eq(v));
+        }
+        if let Some(v) = params.resource {
+            cur = cur.filter(audit_log::Column::Resource.eq(v.to_string()));
+        }
+        if let Some(v) = params.resource_id {
+            // ids are comma separated, so look for the id between commas
+            cur = cur.filter(
+                Expr::expr(Expr::cust("(',' || resource_ids || ',')")).like(format!("%,{v},%")),
+            );
+        }
+        if let Some(v) = params.request_id {
+            cur = cur.filter(audit_log::Column::RequestId.eq(v));
+        }
+        if let Some(v) = params.since {
+            cur = cur.filter(audit_log::Column::CreatedAt.gte(v));
+        }
+        if let Some(v) = params.until {
+            cur = cur.filter(audit_log::Column::CreatedAt.lt(v));
+        }
+        let cur = cur.order_by_desc(audit_log::Column::Id);
+        self.reads
+            .read(|db| {
+                let cur = cur.clone();
+                Box::pin(async move {
+                    let paginator = cur.paginate(db, size);
+                    let total = paginator.num_items().await?;
+                    let res = paginator.fetch_page(page - 1).await?;
+                    Ok((res, total))
+                })
+            })
+            .await
+    }
+}
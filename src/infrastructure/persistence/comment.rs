@@ -0,0 +1,167 @@
+use std::{collections::HashMap, sync::Arc};
+
+use async_trait::async_trait;
+use sea_orm::*;
+
+use crate::{
+    app::log::*,
+    entity::{
+        comment::{ActiveModel, Column, Entity},
+        post, Comment, Post,
+    },
+    repository::{CommentNew, CommentQuery, CommentRepo, CommentUpdate, Error, Result},
+};
+
+use super::conn::DbConns;
+
+pub type CommentStore = Arc<dyn CommentRepo + Send + Sync>;
+
+pub(super) fn get_comment_store(conns: &DbConns) -> CommentStore {
+    Arc::new(CommentRepoImp {
+        db: conns.primary().clone(),
+        reads: conns.clone(),
+    })
+}
+
+/// Database Comment store
+struct CommentRepoImp {
+    db: DbConn,
+    reads: DbConns,
+}
+
+#[async_trait]
+impl CommentRepo for CommentRepoImp {
+    async fn create(&self, post_id: i32, item: CommentNew) -> Result<i32> {
+        info!(?post_id, ?item, "create comment");
+        if post::Entity::find_by_id(post_id)
+            .one(&self.db)
+            .await?
+            .is_none()
+        {
+            return Err(Error::IdNotFound { id: post_id });
+        }
+        if let Some(parent_id) = item.parent_id {
+            let parent = Entity::find_by_id(parent_id).one(&self.db).await?;
+            if !matches!(parent, Some(p) if p.post_id == post_id) {
+                return Err(Error::BadRequest);
+            }
+        }
+        let res = ActiveModel {
+            post_id: Set(post_id),
+            parent_id: Set(item.parent_id),
+            content: Set(item.content),
+            author_id: Set(item.author_id),
+            ..Default::default()
+        }
+        .insert(&self.db)
+        .await?;
+        Ok(res.id)
+    }
+
+    async fn update(&self, post_id: i32, item: CommentUpdate) -> Result<()> {
+        info!(?post_id, ?item, "update comment");
+        find_comment(&self.db, post_id, item.id).await?;
+        ActiveModel {
+            id: Unchanged(item.id),
+            content: Set(item.content),
+            ..Default::default()
+        }
+        .update(&self.db)
+        .await?;
+        Ok(())
+    }
+
+    async fn delete(&self, post_id: i32, id: i32) -> Result<()> {
+        info!(?post_id, ?id, "delete comment");
+        let res = Entity::delete_many()
+            .filter(Column::Id.eq(id))
+            .filter(Column::PostId.eq(post_id))
+            .exec(&self.db)
+            .await?;
+        if res.rows_affected == 0 {
+            return Err(Error::IdNotFound { id });
+        }
+        Ok(())
+    }
+
+    async fn author(&self, post_id: i32, id: i32) -> Result<Option<i32>> {
+        Ok(find_comment(&self.db, post_id, id).await?.author_id)
+    }
+
+    async fn query(&self, post_id: i32, params: CommentQuery) -> Result<(Vec<Comment>, u64)> {
+        info!(?post_id, ?params, "query comments");
+        let page = params.page.unwrap_or(1).max(1);
+        let size = params.size.unwrap_or(10);
+        let parent_id = params.parent_id;
+        self.reads
+            .read(|db| {
+                Box::pin(async move {
+                    let mut cur = Entity::find().filter(Column::PostId.eq(post_id));
+                    cur = match parent_id {
+                        Some(v) => cur.filter(Column::ParentId.eq(v)),
+                        None => cur.filter(Column::ParentId.is_null()),
+                    };
+
+                    let paginator = cur.order_by_asc(Column::Id).paginate(db, size);
+
+                    let total = paginator.num_items().await?;
+                    let mut res = paginator.fetch_page(page - 1).await?;
+                    load_reply_counts(db, &mut res).await?;
+                    Ok((res, total))
+                })
+            })
+            .await
+    }
+}
+
+async fn find_comment<C: ConnectionTrait>(db: &C, post_id: i32, id: i32) -> Result<Comment> {
+    Entity::find_by_id(id)
+        .filter(Column::PostId.eq(post_id))
+        .one(db)
+        .await?
+        .ok_or(Error::IdNotFound { id })
+}
+
+async fn load_reply_counts<C: ConnectionTrait>(db: &C, comments: &mut [Comment]) -> Result<()> {
+    if comments.is_empty() {
+        return Ok(());
+    }
+    let rows: Vec<(i32, i64)> = Entity::find()
+        .select_only()
+        .column(Column::ParentId)
+        .column_as(Column::Id.count(), "replies")
+        .filter(Column::ParentId.is_in(comments.iter().map(|x| x.id)))
+        .group_by(Column::ParentId)
+        .into_tuple()
+        .all(db)
+        .await?;
+    let counts: HashMap<i32, i64> = rows.into_iter().collect();
+    for c in comments {
+        c.replies = counts.get(&c.id).copied().unwrap_or_default();
+    }
+    Ok(())
+}
+
+/// Fill in comment counts of the Posts
+pub(super) async fn load_comment_counts<C: ConnectionTrait>(
+    db: &C,
+    posts: &mut [Post],
+) -> Result<()> {
+    if posts.is_empty() {
+        return Ok(());
+    }
+    let rows: Vec<(i32, i64)> = Entity::find()
+        .select_only()
+        .column(Column::PostId)
+        .column_as(Column::Id.count(), "comments")
+        .filter(Column::PostId.is_in(posts.iter().map(|x| x.id)))
+        .group_by(Column::PostId)
+        .into_tuple()
+        .all(db)
+        .await?;
+    let counts: HashMap<i32, i64> = rows.into_iter().collect();
+    for p in posts {
+        p.comment_count = counts.get(&p.id).copied().unwrap_or_default();
+    }
+    Ok(())
+}
@@ -0,0 +1,313 @@
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use futures::future::BoxFuture;
+use migration::{Migrator, MigratorTrait};
+use sea_orm::{DbConn, DbErr, RuntimeErr, SqlxPostgresConnector};
+use sqlx::{pool::PoolOptions, postgres::PgConnectOptions, ConnectOptions};
+use tokio::time::Instant;
+use tracing::log::LevelFilter;
+
+use crate::{
+    app::log::*,
+    repository::{Error, Result},
+};
+
+use super::config;
+
+const DEFAULT_REPLICA_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const DEFAULT_STARTUP_DEADLINE: Duration = Duration::from_secs(60);
+const FIRST_RETRY_DELAY: Duration = Duration::from_millis(500);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(10);
+
+tokio::task_local! {
+    /// Set for requests that must read their own writes
+    static FORCE_PRIMARY: bool;
+}
+
+/// Run the future with all reads going to the primary database
+pub async fn with_primary_reads<F: Future>(f: F) -> F::Output {
+    FORCE_PRIMARY.scope(true, f).await
+}
+
+/// Primary database for writes, and read replicas taking reads in turn
+#[derive(Clone)]
+pub(super) struct DbConns {
+    primary: DbConn,
+    replicas: Arc<Vec<Replica>>,
+    next: Arc<AtomicUsize>,
+}
+
+struct Replica {
+    /// for logs, without credentials
+    host: String,
+    conn: DbConn,
+    healthy: AtomicBool,
+}
+
+impl DbConns {
+    /// Set up replicas from config, checking their health in the background
+    pub(super) fn setup(primary: DbConn) -> Self {
+        let urls = config::peek_config()
+            .map(|c| c.db.replica_urls.clone())
+            .unwrap_or_default();
+        let replicas: Vec<Replica> = urls
+            .into_iter()
+            .filter_map(|url| {
+                let host = url.rsplit('@').next().unwrap_or_default().to_owned();
+                match connect(&url) {
+                    Ok(conn) => Some(Replica {
+                        host,
+                        conn,
+                        healthy: AtomicBool::default(),
+                    }),
+                    Err(e) => {
+                        error!(%e, host, "invalid db replica url, skipped");
+                        None
+                    }
+                }
+            })
+            .collect();
+        let myself = Self {
+            primary,
+            replicas: Arc::new(replicas),
+            next: Arc::default(),
+        };
+        if !myself.replicas.is_empty() {
+            tokio::spawn(myself.clone().check_periodically());
+        }
+        myself
+    }
+
+    pub(super) fn primary(&self) -> &DbConn {
+        &self.primary
+    }
+
+    /// Run a read on the next healthy replica, falling back to the primary
+    /// if there is none, the replica fails, or the request asked for primary reads.
+    /// Errors of the read itself, e.g. a missing id, are returned as they are.
+    pub(super) async fn read<T, F>(&self, f: F) -> Result<T>
+    where
+        F: for<'a> Fn(&'a DbConn) -> BoxFuture<'a, Result<T>>,
+    {
+        if let Some((replica, conn)) = self.pick() {
+            match f(&conn).await {
+                Err(e @ Error::DbError(_)) => {
+                    warn!(%e, host = replica.host, "read from replica failed, retry on primary");
+                    if conn.ping().await.is_err() {
+                        replica.healthy.store(false, Ordering::Relaxed);
+                    }
+                }
+                ret => return ret,
+            }
+        }
+        f(&self.primary).await
+    }
+
+    fn pick(&self) -> Option<(&Replica, DbConn)> {
+        if self.replicas.is_empty() || FORCE_PRIMARY.try_with(|v| *v).unwrap_or_default() {
+            return None;
+        }
+        let n = self.replicas.len();
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        (0..n)
+            .map(|i| &self.replicas[(start + i) % n])
+            .filter(|r| r.healthy.load(Ordering::Relaxed))
+            .map(|r| (r, r.conn.clone()))
+            .next()
+    }
+
+    async fn check_periodically(self) {
+        loop {
+            for replica in self.replicas.iter() {
+                replica.check().await;
+            }
+            let inter = config::peek_config()
+                .ok()
+                .and_then(|c| c.db.replica_check_secs)
+                .filter(|&v| v > 0)
+                .map_or(DEFAULT_REPLICA_CHECK_INTERVAL, Duration::from_secs);
+            tokio::time::sleep(inter).await;
+        }
+    }
+}
+
+impl Replica {
+    /// Take the replica in or out of service by ping
+    async fn check(&self) {
+        let ok = ping(&self.conn).await;
+        let was = self.healthy.swap(ok.is_ok(), Ordering::Relaxed);
+        match ok {
+            Ok(()) if !was => info!(host = self.host, "db replica in service"),
+            Err(e) if was => error!(%e, host = self.host, "db replica out of service"),
+            Err(e) => debug!(%e, host = self.host, "db replica still down"),
+            _ => {}
+        }
+    }
+}
+
+/// Whether the primary database is reachable and migrated
+#[derive(Clone)]
+pub struct DbHealth {
+    conn: DbConn,
+    ready: Arc<AtomicBool>,
+}
+
+impl DbHealth {
+    /// Ready once startup finished, and while the primary answers pings
+    pub async fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::Relaxed) && ping(&self.conn).await.is_ok()
+    }
+}
+
+/// Connect to the primary in background, retrying with backoff until it is ready.
+/// Past the startup deadline it is reported down, and still retried.
+pub(super) fn wait_primary(conn: &DbConn, auto_migrate: bool) -> DbHealth {
+    let health = DbHealth {
+        conn: conn.clone(),
+        ready: Arc::default(),
+    };
+    let ready = health.ready.clone();
+    let conn = conn.clone();
+    tokio::spawn(async move {
+        let deadline = Instant::now()
+            + config::peek_config()
+                .ok()
+                .and_then(|c| c.db.startup_deadline_secs)
+                .map_or(DEFAULT_STARTUP_DEADLINE, Duration::from_secs);
+        let mut delay = FIRST_RETRY_DELAY;
+        let mut overdue = false;
+        loop {
+            match startup(&conn, auto_migrate).await {
+                Ok(()) => {
+                    info!("database ready");
+                    ready.store(true, Ordering::Relaxed);
+                    return;
+                }
+                Err(e) if overdue => {
+                    debug!(%e, ?delay, "database still not ready, retry later");
+                }
+                Err(e) if Instant::now() + delay < deadline => {
+                    warn!(%e, ?delay, "database not ready, retry later");
+                }
+                Err(e) => {
+                    error!(%e, ?delay, "database not ready before startup deadline, keep retrying");
+                    overdue = true;
+                }
+            }
+            tokio::time::sleep(delay).await;
+            delay = (delay * 2).min(MAX_RETRY_DELAY);
+        }
+    });
+    health
+}
+
+async fn startup(conn: &DbConn, auto_migrate: bool) -> std::result::Result<(), String> {
+    ping(conn).await?;
+    if auto_migrate {
+        Migrator::up(conn, None).await.map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Ping within the connect timeout
+async fn ping(conn: &DbConn) -> std::result::Result<(), String> {
+    let timeout = config::peek_config()
+        .ok()
+        .and_then(|c| c.db.connect_timeout_secs)
+        .map_or(DEFAULT_CONNECT_TIMEOUT, Duration::from_secs);
+    match tokio::time::timeout(timeout, conn.ping()).await {
+        Ok(ret) => ret.map_err(|e| e.to_string()),
+        Err(_) => Err(format!("no answer in {timeout:?}")),
+    }
+}
+
+/// Create a connection pool with the configured options, of the backend by url scheme.
+/// Connections are opened on demand, so this fails only on invalid urls.
+pub(super) fn connect(url: &str) -> std::result::Result<DbConn, DbErr> {
+    #[cfg(any(test, feature = "sqlite"))]
+    if url.starts_with("sqlite:") {
+        return connect_sqlite(url);
+    }
+    let (log_mode, statement_timeout_ms) = config::peek_config()
+        .map(|c| (c.db.log_mode, c.db.statement_timeout_ms))
+        .unwrap_or_default();
+    let mut opt: PgConnectOptions = url.parse().map_err(sqlx_err)?;
+    opt = opt.log_statements(log_level(log_mode));
+    if let Some(ms) = statement_timeout_ms {
+        opt = opt.options([("statement_timeout", ms.to_string())]);
+    }
+    Ok(SqlxPostgresConnector::from_sqlx_postgres_pool(
+        pool_options().connect_lazy_with(opt),
+    ))
+}
+
+/// SQLite has no statement timeout, and in-memory databases live as long as their only connection
+#[cfg(any(test, feature = "sqlite"))]
+fn connect_sqlite(url: &str) -> std::result::Result<DbConn, DbErr> {
+    use sea_orm::SqlxSqliteConnector;
+    use sqlx::sqlite::SqliteConnectOptions;
+
+    let log_mode = config::peek_config()
+        .map(|c| c.db.log_mode)
+        .unwrap_or_default();
+    let opt: SqliteConnectOptions = url.parse().map_err(sqlx_err)?;
+    let opt = opt.log_statements(log_level(log_mode));
+    let mut pool = pool_options();
+    if url.contains(":memory:") || url.contains("mode=memory") {
+        pool = pool
+            .max_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None);
+    }
+    Ok(SqlxSqliteConnector::from_sqlx_sqlite_pool(
+        pool.connect_lazy_with(opt),
+    ))
+}
+
+/// Pool options from config, sqlx defaults for what is not configured
+fn pool_options<DB: sqlx::Database>() -> PoolOptions<DB> {
+    let conf = config::peek_config()
+        .map(|c| c.db.clone())
+        .unwrap_or_default();
+    let mut pool = PoolOptions::new();
+    // connections are opened on acquire, so their attempts are bound by its timeout
+    if let Some(secs) = conf.connect_timeout_secs {
+        pool = pool.acquire_timeout(Duration::from_secs(secs));
+    }
+    if let Some(secs) = conf.acquire_timeout_secs {
+        pool = pool.acquire_timeout(Duration::from_secs(secs));
+    }
+    if let Some(secs) = conf.idle_timeout_secs {
+        pool = pool.idle_timeout(Duration::from_secs(secs));
+    }
+    if let Some(secs) = conf.max_lifetime_secs {
+        pool = pool.max_lifetime(Duration::from_secs(secs));
+    }
+    if let Some(n) = conf.max_connections {
+        pool = pool.max_connections(n);
+    }
+    if let Some(n) = conf.min_connections {
+        pool = pool.min_connections(n);
+    }
+    pool
+}
+
+fn log_level(log_mode: bool) -> LevelFilter {
+    if log_mode {
+        LevelFilter::Info
+    } else {
+        LevelFilter::Trace
+    }
+}
+
+fn sqlx_err(e: sqlx::Error) -> DbErr {
+    DbErr::Conn(RuntimeErr::SqlxError(e))
+}
@@ -0,0 +1,132 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use crate::{
+    entity::Todo,
+    event::{Action, DomainEvent, Resource},
+    infrastructure::event_bus::EventBus,
+    repository::{AuditContext, Result, TodoChanges, TodoQuery, TodoRepo, TodoUpdate},
+};
+
+use super::TodoStore;
+
+/// Wrap the store to publish events of its changes
+pub(super) fn with_todo_events(inner: TodoStore, bus: &EventBus) -> TodoStore {
+    Arc::new(EventedTodoRepo {
+        inner,
+        bus: bus.clone(),
+    })
+}
+
+struct EventedTodoRepo {
+    inner: TodoStore,
+    bus: EventBus,
+}
+
+impl EventedTodoRepo {
+    fn publish(&self, action: Action, ids: Vec<i32>, changed: &[&str]) {
+        if !ids.is_empty() {
+            self.bus
+                .publish(DomainEvent::new(Resource::Todo, action, ids, changed));
+        }
+    }
+
+    /// Todos completed as a side effect of changing the one
+    fn publish_completed(&self, changes: &TodoChanges, id: Option<i32>) {
+        let ids = changes
+            .completed
+            .iter()
+            .copied()
+            .filter(|&x| Some(x) != id)
+            .collect();
+        self.publish(Action::Updated, ids, &["done"]);
+    }
+}
+
+#[async_trait]
+impl TodoRepo for EventedTodoRepo {
+    async fn create(&self, item: Todo, audit: Option<AuditContext>) -> Result<(i32, TodoChanges)> {
+        let (id, changes) = self.inner.create(item, audit).await?;
+        self.publish(
+            Action::Created,
+            vec![id],
+            &[
+                "value",
+                "done",
+                "due_at",
+                "priority",
+                "parent_id",
+                "auto_complete",
+            ],
+        );
+        self.publish_completed(&changes, Some(id));
+        Ok((id, changes))
+    }
+
+    async fn update(&self, item: TodoUpdate, audit: Option<AuditContext>) -> Result<TodoChanges> {
+        let id = item.id;
+        let changed: Vec<&str> = [
+            ("value", item.value.is_some()),
+            ("done", item.done.is_some()),
+            ("due_at", item.due_at.is_some()),
+            ("priority", item.priority.is_some()),
+            ("auto_complete", item.auto_complete.is_some()),
+        ]
+        .into_iter()
+        .filter_map(|(k, set)| set.then_some(k))
+        .collect();
+        let changes = self.inner.update(item, audit).await?;
+        if !changed.is_empty() {
+            self.publish(Action::Updated, vec![id], &changed);
+        }
+        self.publish_completed(&changes, Some(id));
+        Ok(changes)
+    }
+
+    async fn delete(&self, ids: Vec<i32>, audit: Option<AuditContext>) -> Result<TodoChanges> {
+        let changes = self.inner.delete(ids, audit).await?;
+        self.publish(Action::Deleted, changes.deleted.clone(), &[]);
+        self.publish_completed(&changes, None);
+        Ok(changes)
+    }
+
+    async fn fetch(&self, id: i32) -> Result<Todo> {
+        self.inner.fetch(id).await
+    }
+
+    async fn query(&self, params: TodoQuery) -> Result<(Vec<Todo>, u64)> {
+        self.inner.query(params).await
+    }
+
+    async fn reorder(
+        &self,
+        id: i32,
+        position: i32,
+        audit: Option<AuditContext>,
+    ) -> Result<TodoChanges> {
+        let changes = self.inner.reorder(id, position, audit).await?;
+        self.publish(Action::Updated, changes.moved.clone(), &["position"]);
+        Ok(changes)
+    }
+
+    async fn subtree(&self, id: i32) -> Result<Vec<Todo>> {
+        self.inner.subtree(id).await
+    }
+
+    async fn reparent(
+        &self,
+        id: i32,
+        parent_id: Option<i32>,
+        audit: Option<AuditContext>,
+    ) -> Result<TodoChanges> {
+        let changes = self.inner.reparent(id, parent_id, audit).await?;
+        self.publish(Action::Updated, changes.reparented.clone(), &["parent_id"]);
+        self.publish_completed(&changes, Some(id));
+        Ok(changes)
+    }
+
+    async fn flush(&self) -> Result<()> {
+        self.inner.flush().await
+    }
+}
@@ -1,40 +1,92 @@
 mod todo;
 pub use todo::TodoStore;
 
+mod todo_journal;
+
+mod attachment;
+pub use attachment::AttachmentStore;
+
+mod audit;
+pub use audit::AuditStore;
+
+mod comment;
+pub use comment::CommentStore;
+
+mod conn;
+pub use conn::{with_primary_reads, DbHealth};
+
+mod evented;
+
+mod outbox;
+pub use outbox::OutboxStore;
+
 mod post;
 pub use post::PostStore;
 
-use migration::{Migrator, MigratorTrait};
-use sea_orm::{ConnectOptions, Database};
-use tracing::log::LevelFilter;
+mod post_cache;
 
-use crate::app;
+mod scheduler;
+
+mod tag;
+pub use tag::TagStore;
+
+mod user;
+pub use user::UserStore;
+
+mod views;
+pub use views::ViewCounterHandle;
+
+mod webhook;
+pub use webhook::WebhookStore;
+
+use axum::extract::FromRef;
+
+use crate::{app, infrastructure::event_bus::EventBus};
 
 use super::config;
 
+#[derive(Clone, FromRef)]
 pub struct Db {
     pub todo: TodoStore,
     pub post: PostStore,
+    pub comment: CommentStore,
+    pub attachment: AttachmentStore,
+    pub tag: TagStore,
+    pub user: UserStore,
+    pub webhook: WebhookStore,
+    pub outbox: OutboxStore,
+    pub audit: AuditStore,
+    pub views: ViewCounterHandle,
+    pub events: EventBus,
+    pub health: DbHealth,
 }
 
 impl Db {
     pub async fn setup() -> app::Result<Self> {
         let db_conf = config::peek_config()?.db.clone();
-        let mut opt = ConnectOptions::new(db_conf.url.clone());
-        if !db_conf.log_mode {
-            opt.sqlx_logging_level(LevelFilter::Trace);
-        }
-
-        let conn = Database::connect(opt)
-            .await
-            .expect("Database connection failed");
-        if db_conf.auto_migrate {
-            Migrator::up(&conn, None).await?;
-        }
+        let conn = conn::connect(&db_conf.url)?;
+        let health = conn::wait_primary(&conn, db_conf.auto_migrate);
+
+        let conns = conn::DbConns::setup(conn.clone());
+        let events = EventBus::default();
+        let views = views::ViewCounter::setup(&conn);
+        let post = post_cache::with_cache(post::get_post_store(&conns, &views), &views)?;
+        scheduler::spawn_publisher(post.clone());
+        let comment = post_cache::with_post_eviction(comment::get_comment_store(&conns), &post);
 
         Ok(Self {
-            todo: todo::get_todo_store(),
-            post: post::get_post_store(&conn),
+            todo: evented::with_todo_events(todo::get_todo_store(&conns)?, &events),
+            post,
+            comment,
+            attachment: attachment::get_attachment_store(&conns),
+            tag: tag::get_tag_store(&conns),
+            user: user::get_user_store(&conn),
+            webhook: webhook::get_webhook_store(&conn),
+            outbox: outbox::get_outbox_store(&conn),
+            audit: audit::get_audit_store(&conns),
+            views,
+            events,
+            health,
         })
     }
 }
@@ -0,0 +1,101 @@
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use sea_orm::sea_query::{Expr, OnConflict};
+use sea_orm::*;
+
+use crate::{
+    app::{log::*, utils},
+    entity::{lease, outbox_event, DateTimeTZ, OutboxEvent},
+    event::DomainEvent,
+    repository::{OutboxRepo, Result},
+};
+
+pub type OutboxStore = Arc<dyn OutboxRepo + Send + Sync>;
+
+pub(super) fn get_outbox_store(db: &DbConn) -> OutboxStore {
+    Arc::new(OutboxRepoImp { db: db.clone() })
+}
+
+/// Write the event to the outbox, in the transaction of the change it describes
+pub(super) async fn append<C: ConnectionTrait>(db: &C, event: DomainEvent) -> Result<()> {
+    outbox_event::ActiveModel {
+        event_id: Set(event.id.clone()),
+        kind: Set(event.kind()),
+        payload: Set(serde_json::to_string(&event)?),
+        ..Default::default()
+    }
+    .insert(db)
+    .await?;
+    Ok(())
+}
+
+/// Database outbox store
+struct OutboxRepoImp {
+    db: DbConn,
+}
+
+#[async_trait]
+impl OutboxRepo for OutboxRepoImp {
+    async fn acquire_lease(&self, name: &str, holder: &str, ttl: Duration) -> Result<bool> {
+        let now = utils::get_current_time();
+        // the first taker creates the lease as already expired
+        lease::Entity::insert(lease::ActiveModel {
+            name: Set(name.to_owned()),
+            holder: Set(holder.to_owned()),
+            expires_at: Set(now),
+        })
+        .on_conflict(
+            OnConflict::column(lease::Column::Name)
+                .do_nothing()
+                .to_owned(),
+        )
+        .exec_without_returning(&self.db)
+        .await?;
+        let res = lease::Entity::update_many()
+            .col_expr(lease::Column::Holder, Expr::value(holder))
+            .col_expr(lease::Column::ExpiresAt, Expr::value(now + ttl))
+            .filter(lease::Column::Name.eq(name))
+            .filter(
+                Condition::any()
+                    .add(lease::Column::Holder.eq(holder))
+                    .add(lease::Column::ExpiresAt.lte(now)),
+            )
+            .exec(&self.db)
+            .await?;
+        Ok(res.rows_affected == 1)
+    }
+
+    async fn pending(&self, limit: u64) -> Result<Vec<OutboxEvent>> {
+        Ok(outbox_event::Entity::find()
+            .filter(outbox_event::Column::DeliveredAt.is_null())
+            .order_by_asc(outbox_event::Column::Id)
+            .limit(limit)
+            .all(&self.db)
+            .await?)
+    }
+
+    async fn mark_delivered(&self, ids: Vec<i32>) -> Result<()> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+        debug!(?ids, "outbox events delivered");
+        outbox_event::Entity::update_many()
+            .col_expr(
+                outbox_event::Column::DeliveredAt,
+                Expr::value(Some(utils::get_current_time())),
+            )
+            .filter(outbox_event::Column::Id.is_in(ids))
+            .exec(&self.db)
+            .await?;
+        Ok(())
+    }
+
+    async fn purge_delivered(&self, before: DateTimeTZ) -> Result<u64> {
+        let res = outbox_event::Entity::delete_many()
+            .filter(outbox_event::Column::DeliveredAt.lt(before))
+            .exec(&self.db)
+            .await?;
+        Ok(res.rows_affected)
+    }
+}
@@ -1,108 +1,696 @@
 use std::sync::Arc;
 
 use async_trait::async_trait;
+use futures::future::BoxFuture;
 use sea_orm::sea_query::Expr;
 use sea_orm::*;
 
 use crate::{
-    app::log::*,
+    app::{log::*, utils},
     entity::{
-        post::{ActiveModel, Column, Entity},
-        Post,
+        post::{self, ActiveModel, Column, Entity},
+        post_revision, post_slug, DateTimeTZ, Post, PostRevision, PostStatus,
+    },
+    event::{Action, DomainEvent, Resource},
+    repository::{
+        AuditContext, BulkItem, BulkMode, DeleteReport, Error, PostNew, PostQuery, PostRepo,
+        PostTransition, PostUpdate, Result, RevisionQuery,
     },
-    repository::{Error, PostNew, PostQuery, PostRepo, PostUpdate, Result},
 };
 
+use super::{audit, comment, conn::DbConns, outbox, tag, user, ViewCounterHandle};
+
 pub type PostStore = Arc<dyn PostRepo + Send + Sync>;
 
-pub(super) fn get_post_store(db: &DbConn) -> PostStore {
-    Arc::new(PostRepoImp { db: db.clone() })
+/// Fields changed by reverting to a revision
+const CONTENT_FIELDS: [&str; 2] = ["title", "content"];
+/// Fields changed by a status transition
+const STATUS_FIELDS: [&str; 2] = ["status", "publish_at"];
+
+pub(super) fn get_post_store(conns: &DbConns, views: &ViewCounterHandle) -> PostStore {
+    Arc::new(PostRepoImp {
+        db: conns.primary().clone(),
+        reads: conns.clone(),
+        views: views.clone(),
+    })
 }
 
 /// Database Post store
 struct PostRepoImp {
     db: DbConn,
+    reads: DbConns,
+    views: ViewCounterHandle,
 }
 
 #[async_trait]
 impl PostRepo for PostRepoImp {
-    async fn create(&self, item: PostNew) -> Result<i32> {
+    async fn create(&self, item: PostNew, audit: Option<AuditContext>) -> Result<i32> {
         info!(?item, "create post");
-        let res = ActiveModel {
-            title: Set(item.title.to_owned()),
-            content: Set(item.content.to_owned()),
-            ..Default::default()
-        }
-        .save(&self.db)
-        .await?;
-        Ok(match res.id {
-            ActiveValue::Set(v) | ActiveValue::Unchanged(v) => v,
-            NotSet => 0,
-        })
+        let txn = self.db.begin().await?;
+        let id = create_post_with_event(&txn, item).await?;
+        audit_after(&txn, audit, vec![id], &[]).await?;
+        txn.commit().await?;
+        Ok(id)
     }
 
-    async fn update(&self, item: PostUpdate) -> Result<()> {
+    async fn update(&self, item: PostUpdate, audit: Option<AuditContext>) -> Result<()> {
         info!(?item, "update post");
-        ActiveModel {
-            id: Unchanged(item.id),
-            title: item.title.map_or(NotSet, Set),
-            content: item.content.map_or(NotSet, Set),
-            ..Default::default()
-        }
-        .update(&self.db)
-        .await?;
+        let id = item.id;
+        let txn = self.db.begin().await?;
+        let before = audit_before(&txn, audit.as_ref(), vec![id]).await?;
+        update_post_with_event(&txn, item).await?;
+        audit_after(&txn, audit, vec![id], &before).await?;
+        txn.commit().await?;
         Ok(())
     }
 
-    async fn delete(&self, ids: Vec<i32>) -> Result<()> {
+    async fn delete(&self, ids: Vec<i32>, audit: Option<AuditContext>) -> Result<DeleteReport> {
         info!(?ids, "delete posts");
+        let txn = self.db.begin().await?;
+        let deleted: Vec<i32> = Entity::find()
+            .select_only()
+            .column(Column::Id)
+            .filter(Column::Id.is_in(ids.clone()))
+            .order_by_asc(Column::Id)
+            .into_tuple()
+            .all(&txn)
+            .await?;
+        let before = audit_before(&txn, audit.as_ref(), deleted.clone()).await?;
         Entity::delete_many()
-            .filter(Column::Id.is_in(ids))
-            .exec(&self.db)
+            .filter(Column::Id.is_in(deleted.clone()))
+            .exec(&txn)
             .await?;
-        Ok(())
+        if !deleted.is_empty() {
+            let event = DomainEvent::new(Resource::Post, Action::Deleted, deleted.clone(), &[]);
+            outbox::append(&txn, event).await?;
+        }
+        audit_after(&txn, audit, deleted.clone(), &before).await?;
+        txn.commit().await?;
+        let mut not_found: Vec<i32> = ids.into_iter().filter(|x| !deleted.contains(x)).collect();
+        not_found.sort_unstable();
+        not_found.dedup();
+        Ok(DeleteReport { deleted, not_found })
+    }
+
+    async fn create_many(
+        &self,
+        items: Vec<PostNew>,
+        mode: BulkMode,
+        audit: Option<AuditContext>,
+    ) -> Result<Vec<BulkItem>> {
+        info!(n = items.len(), ?mode, "create posts in bulk");
+        let txn = self.db.begin().await?;
+        let mut ret = Vec::with_capacity(items.len());
+        for (index, item) in items.into_iter().enumerate() {
+            ret.push(
+                bulk_item(&txn, mode, index, |db| {
+                    Box::pin(create_post_with_event(db, item))
+                })
+                .await?,
+            );
+        }
+        audit_after(&txn, audit, succeeded(&ret), &[]).await?;
+        txn.commit().await?;
+        Ok(ret)
     }
 
-    async fn fetch(&self, id: i32) -> Result<Post> {
-        info!(?id, "fetch post");
-        let res = Entity::find_by_id(id).one(&self.db).await?;
+    async fn update_many(
+        &self,
+        items: Vec<PostUpdate>,
+        mode: BulkMode,
+        audit: Option<AuditContext>,
+    ) -> Result<Vec<BulkItem>> {
+        info!(n = items.len(), ?mode, "update posts in bulk");
+        let txn = self.db.begin().await?;
+        let ids = items.iter().map(|x| x.id).collect();
+        let before = audit_before(&txn, audit.as_ref(), ids).await?;
+        let mut ret = Vec::with_capacity(items.len());
+        for (index, item) in items.into_iter().enumerate() {
+            ret.push(
+                bulk_item(&txn, mode, index, |db| {
+                    Box::pin(async move {
+                        let id = item.id;
+                        update_post_with_event(db, item).await?;
+                        Ok(id)
+                    })
+                })
+                .await?,
+            );
+        }
+        audit_after(&txn, audit, succeeded(&ret), &before).await?;
+        txn.commit().await?;
+        Ok(ret)
+    }
+
+    async fn fetch(&self, id: i32, viewer: Option<String>) -> Result<Post> {
+        info!(?id, ?viewer, "fetch post");
+        let res = Entity::find_by_id(id)
+            .filter(Column::Status.eq(PostStatus::Published))
+            .one(&self.db)
+            .await?;
         match res {
-            Some(v) => {
-                Entity::update_many()
-                    .col_expr(Column::Views, Expr::col(Column::Views).add(1))
-                    .filter(Column::Id.eq(id))
-                    .exec(&self.db)
-                    .await?;
-                // self.db
-                //     .execute(Statement::from_sql_and_values(
-                //         DbBackend::Postgres,
-                //         "UPDATE posts SET views = views + 1 WHERE id = $1",
-                //         [id.into()],
-                //     ))
-                //     .await?;
+            Some(mut v) => {
+                v.views += self.views.record(id, viewer.as_deref())?;
+                let posts = std::slice::from_mut(&mut v);
+                tag::load_tags(&self.db, posts).await?;
+                comment::load_comment_counts(&self.db, posts).await?;
+                user::load_authors(&self.db, posts).await?;
                 Ok(v)
             }
             None => Err(Error::IdNotFound { id }),
         }
     }
 
+    async fn fetch_unpublished(&self, id: i32) -> Result<Post> {
+        info!(?id, "fetch unpublished post");
+        let mut v = Entity::find_by_id(id)
+            .filter(Column::Status.ne(PostStatus::Published))
+            .one(&self.db)
+            .await?
+            .ok_or(Error::IdNotFound { id })?;
+        let posts = std::slice::from_mut(&mut v);
+        tag::load_tags(&self.db, posts).await?;
+        comment::load_comment_counts(&self.db, posts).await?;
+        user::load_authors(&self.db, posts).await?;
+        Ok(v)
+    }
+
+    async fn snapshot(&self, ids: Vec<i32>) -> Result<Vec<Post>> {
+        load_snapshot(&self.db, ids).await
+    }
+
+    async fn find_slug(&self, slug: String) -> Result<(i32, String)> {
+        info!(?slug, "find post slug");
+        self.reads
+            .read(|db| Box::pin(find_slug(db, slug.clone())))
+            .await
+    }
+
     async fn query(&self, params: PostQuery) -> Result<(Vec<Post>, u64)> {
         info!(?params, "query posts");
-        let page = params.page.unwrap_or(1);
+        self.reads
+            .read(|db| Box::pin(query_posts(db, params.clone())))
+            .await
+    }
+
+    async fn scan(&self, params: PostQuery, after: i32, limit: u64) -> Result<Vec<Post>> {
+        debug!(?params, ?after, ?limit, "scan posts");
+        self.reads
+            .read(|db| {
+                let params = params.clone();
+                Box::pin(async move {
+                    let mut res = filtered(params)
+                        .filter(Column::Id.gt(after))
+                        .order_by_asc(Column::Id)
+                        .limit(limit)
+                        .all(db)
+                        .await?;
+                    tag::load_tags(db, &mut res).await?;
+                    Ok(res)
+                })
+            })
+            .await
+    }
+
+    async fn revisions(&self, id: i32, params: RevisionQuery) -> Result<(Vec<PostRevision>, u64)> {
+        info!(?id, ?params, "query post revisions");
+        let page = params.page.unwrap_or(1).max(1);
         let size = params.size.unwrap_or(10);
+        self.reads
+            .read(|db| {
+                Box::pin(async move {
+                    let paginator = post_revision::Entity::find()
+                        .filter(post_revision::Column::PostId.eq(id))
+                        .order_by_desc(post_revision::Column::Version)
+                        .paginate(db, size);
+
+                    let total = paginator.num_items().await?;
+                    let res = paginator.fetch_page(page - 1).await?;
+                    Ok((res, total))
+                })
+            })
+            .await
+    }
+
+    async fn revision(&self, id: i32, version: i32) -> Result<PostRevision> {
+        info!(?id, ?version, "fetch post revision");
+        self.reads
+            .read(|db| Box::pin(find_revision(db, id, version)))
+            .await
+    }
+
+    async fn revert(&self, id: i32, version: i32, audit: Option<AuditContext>) -> Result<i32> {
+        info!(?id, ?version, "revert post");
+        let txn = self.db.begin().await?;
+        let before = audit_before(&txn, audit.as_ref(), vec![id]).await?;
+        let rev = find_revision(&txn, id, version).await?;
+        let new_version = update_post(
+            &txn,
+            PostUpdate {
+                id,
+                title: Some(rev.title),
+                content: Some(rev.content),
+                slug: None,
+                tags: None,
+            },
+        )
+        .await?;
+        let event = DomainEvent::new(Resource::Post, Action::Updated, vec![id], &CONTENT_FIELDS);
+        outbox::append(&txn, event).await?;
+        audit_after(&txn, audit, vec![id], &before).await?;
+        txn.commit().await?;
+        Ok(new_version)
+    }
+
+    async fn transition(
+        &self,
+        id: i32,
+        to: PostTransition,
+        audit: Option<AuditContext>,
+    ) -> Result<()> {
+        info!(?id, ?to, "transit post status");
+        let txn = self.db.begin().await?;
+        let post = Entity::find_by_id(id)
+            .one(&txn)
+            .await?
+            .ok_or(Error::IdNotFound { id })?;
+        let before = audit_before(&txn, audit.as_ref(), vec![id]).await?;
+        let (status, publish_at) = plan_transition(post.status, post.publish_at, to)?;
+        let res = Entity::update_many()
+            .col_expr(Column::Status, Expr::value(status))
+            .col_expr(Column::PublishAt, Expr::value(publish_at))
+            .filter(Column::Id.eq(id))
+            .filter(Column::Status.eq(post.status))
+            .exec(&txn)
+            .await?;
+        if res.rows_affected == 0 {
+            // status changed by someone else in the meantime
+            return Err(Error::InvalidTransition {
+                from: post.status,
+                to: status,
+            });
+        }
+        let event = DomainEvent::new(Resource::Post, Action::Updated, vec![id], &STATUS_FIELDS);
+        outbox::append(&txn, event).await?;
+        audit_after(&txn, audit, vec![id], &before).await?;
+        txn.commit().await?;
+        Ok(())
+    }
+
+    async fn publish_due(&self) -> Result<Vec<i32>> {
+        let txn = self.db.begin().await?;
+        let res = Entity::update_many()
+            .col_expr(Column::Status, Expr::value(PostStatus::Published))
+            .filter(Column::Status.eq(PostStatus::Scheduled))
+            .filter(Column::PublishAt.lte(utils::get_current_time()))
+            .exec_with_returning(&txn)
+            .await?;
+        let ids: Vec<i32> = res.into_iter().map(|x| x.id).collect();
+        if !ids.is_empty() {
+            let event = DomainEvent::new(Resource::Post, Action::Updated, ids.clone(), &["status"]);
+            outbox::append(&txn, event).await?;
+        }
+        txn.commit().await?;
+        Ok(ids)
+    }
+
+    async fn authors(&self, ids: Vec<i32>) -> Result<Vec<(i32, Option<i32>)>> {
+        Ok(Entity::find()
+            .select_only()
+            .column(Column::Id)
+            .column(Column::AuthorId)
+            .filter(Column::Id.is_in(ids))
+            .into_tuple()
+            .all(&self.db)
+            .await?)
+    }
+}
+
+/// Check the status change, and work out when the Post is published
+fn plan_transition(
+    from: PostStatus,
+    published_at: Option<DateTimeTZ>,
+    to: PostTransition,
+) -> Result<(PostStatus, Option<DateTimeTZ>)> {
+    if !from.can_transition_to(to.status) {
+        return Err(Error::InvalidTransition {
+            from,
+            to: to.status,
+        });
+    }
+    let now = utils::get_current_time();
+    let publish_at = match to.status {
+        PostStatus::Draft => None,
+        PostStatus::Scheduled => match to.publish_at {
+            Some(v) if v > now => Some(v),
+            _ => return Err(Error::InvalidPublishAt),
+        },
+        PostStatus::Published => Some(now),
+        PostStatus::Archived => published_at,
+    };
+    Ok((to.status, publish_at))
+}
+
+/// Check the status of an imported Post, keeping when it was published
+fn imported_status(
+    status: PostStatus,
+    publish_at: Option<DateTimeTZ>,
+) -> Result<(PostStatus, Option<DateTimeTZ>)> {
+    let publish_at = match status {
+        PostStatus::Draft => None,
+        // published right away by the scheduler if the time has passed
+        PostStatus::Scheduled => Some(publish_at.ok_or(Error::InvalidPublishAt)?),
+        PostStatus::Published => Some(publish_at.unwrap_or_else(utils::get_current_time)),
+        PostStatus::Archived => publish_at,
+    };
+    Ok((status, publish_at))
+}
+
+async fn find_slug<C: ConnectionTrait>(db: &C, slug: String) -> Result<(i32, String)> {
+    if let Some(post) = Entity::find()
+        .filter(Column::Slug.eq(&slug))
+        .one(db)
+        .await?
+    {
+        return Ok((post.id, post.slug));
+    }
+    let post = post_slug::Entity::find_by_id(slug.clone())
+        .find_also_related(Entity)
+        .one(db)
+        .await?
+        .and_then(|(_, post)| post)
+        .ok_or(Error::SlugNotFound { slug })?;
+    Ok((post.id, post.slug))
+}
 
-        let mut cur = Entity::find();
-        if let Some(v) = params.title {
-            cur = cur.filter(Column::Title.contains(v));
+async fn query_posts<C: ConnectionTrait>(db: &C, params: PostQuery) -> Result<(Vec<Post>, u64)> {
+    let page = params.page.unwrap_or(1);
+    let size = params.size.unwrap_or(10);
+
+    let paginator = filtered(params)
+        .order_by_desc(Column::Id)
+        .paginate(db, size);
+
+    let total = paginator.num_items().await?;
+    let mut res = paginator.fetch_page(page - 1).await?;
+    tag::load_tags(db, &mut res).await?;
+    comment::load_comment_counts(db, &mut res).await?;
+    user::load_authors(db, &mut res).await?;
+    Ok((res, total))
+}
+
+/// Select Posts matching the query, ignoring pagination
+fn filtered(params: PostQuery) -> Select<Entity> {
+    let mut cur = Entity::find();
+    if let Some(v) = params.title {
+        cur = cur.filter(Column::Title.contains(v));
+    }
+    if let Some(v) = params.content {
+        cur = cur.filter(Column::Content.contains(v));
+    }
+    if let Some(v) = params.author_id {
+        cur = cur.filter(Column::AuthorId.eq(v));
+    }
+    if let Some(v) = params.status {
+        cur = cur.filter(Column::Status.eq(v));
+    }
+    if let Some(v) = params.tags {
+        let tags = tag::normalize(v.split(',').map(str::to_owned).collect());
+        if !tags.is_empty() {
+            let mode = params.tag_match.unwrap_or_default();
+            cur = cur.filter(Column::Id.in_subquery(tag::tagged_post_ids(tags, mode)));
+        }
+    }
+    cur
+}
+
+/// Posts by ids as stored, with their tags
+async fn load_snapshot<C: ConnectionTrait>(db: &C, ids: Vec<i32>) -> Result<Vec<Post>> {
+    let mut res = Entity::find()
+        .filter(Column::Id.is_in(ids))
+        .order_by_asc(Column::Id)
+        .all(db)
+        .await?;
+    tag::load_tags(db, &mut res).await?;
+    Ok(res)
+}
+
+/// Posts as they are before a change, if it is audited
+async fn audit_before<C: ConnectionTrait>(
+    db: &C,
+    audit: Option<&AuditContext>,
+    ids: Vec<i32>,
+) -> Result<Vec<Post>> {
+    match audit {
+        Some(_) => load_snapshot(db, ids).await,
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Record the change of the Posts in the audit log, in its transaction as the outbox event,
+/// with those of them found before and after it
+async fn audit_after<C: ConnectionTrait>(
+    db: &C,
+    audit: Option<AuditContext>,
+    ids: Vec<i32>,
+    before: &[Post],
+) -> Result<()> {
+    let Some(audit) = audit.filter(|_| !ids.is_empty()) else {
+        return Ok(());
+    };
+    let before: Vec<Post> = before
+        .iter()
+        .filter(|x| ids.contains(&x.id))
+        .cloned()
+        .collect();
+    let after = load_snapshot(db, ids.clone()).await?;
+    audit::append(db, audit.change(Resource::Post, ids, &before, &after)).await
+}
+
+/// Run one item of a bulk operation, in a savepoint if failed items are to be kept apart
+async fn bulk_item<F>(
+    txn: &DatabaseTransaction,
+    mode: BulkMode,
+    index: usize,
+    f: F,
+) -> Result<BulkItem>
+where
+    F: for<'a> FnOnce(&'a DatabaseTransaction) -> BoxFuture<'a, Result<i32>>,
+{
+    match mode {
+        BulkMode::AllOrNothing => match f(txn).await {
+            Ok(id) => Ok(BulkItem {
+                index,
+                id: Some(id),
+                error: None,
+            }),
+            Err(e) => Err(Error::BulkItemFailed {
+                index,
+                error: e.to_string(),
+            }),
+        },
+        BulkMode::BestEffort => {
+            let savepoint = txn.begin().await?;
+            match f(&savepoint).await {
+                Ok(id) => {
+                    savepoint.commit().await?;
+                    Ok(BulkItem {
+                        index,
+                        id: Some(id),
+                        error: None,
+                    })
+                }
+                Err(e) => {
+                    savepoint.rollback().await?;
+                    Ok(BulkItem {
+                        index,
+                        id: None,
+                        error: Some(e.to_string()),
+                    })
+                }
+            }
         }
-        if let Some(v) = params.content {
-            cur = cur.filter(Column::Content.contains(v));
+    }
+}
+
+/// Create the Post, with its event in the outbox
+async fn create_post_with_event<C: ConnectionTrait>(db: &C, item: PostNew) -> Result<i32> {
+    let changed = new_fields(&item);
+    let id = create_post(db, item).await?;
+    let event = DomainEvent::new(Resource::Post, Action::Created, vec![id], &changed);
+    outbox::append(db, event).await?;
+    Ok(id)
+}
+
+/// Update the Post, with its event in the outbox if anything changed
+async fn update_post_with_event<C: ConnectionTrait>(db: &C, item: PostUpdate) -> Result<i32> {
+    let (id, changed) = (item.id, update_fields(&item));
+    let version = update_post_with_tags(db, item).await?;
+    if !changed.is_empty() {
+        let event = DomainEvent::new(Resource::Post, Action::Updated, vec![id], &changed);
+        outbox::append(db, event).await?;
+    }
+    Ok(version)
+}
+
+/// Fields given for a new Post
+fn new_fields(item: &PostNew) -> Vec<&'static str> {
+    let mut ret = vec!["title", "content"];
+    if item.slug.is_some() {
+        ret.push("slug");
+    }
+    if !item.tags.is_empty() {
+        ret.push("tags");
+    }
+    if item.status.is_some() {
+        ret.push("status");
+    }
+    if item.publish_at.is_some() {
+        ret.push("publish_at");
+    }
+    ret
+}
+
+/// Fields changed by an update
+fn update_fields(item: &PostUpdate) -> Vec<&'static str> {
+    [
+        ("title", item.title.is_some()),
+        ("content", item.content.is_some()),
+        ("slug", item.slug.is_some()),
+        ("tags", item.tags.is_some()),
+    ]
+    .into_iter()
+    .filter_map(|(k, set)| set.then_some(k))
+    .collect()
+}
+
+/// Ids of the items done
+fn succeeded(items: &[BulkItem]) -> Vec<i32> {
+    items
+        .iter()
+        .filter(|x| x.error.is_none())
+        .filter_map(|x| x.id)
+        .collect()
+}
+
+async fn create_post<C: ConnectionTrait>(db: &C, item: PostNew) -> Result<i32> {
+    if item.title.is_empty() {
+        return Err(Error::BadRequest);
+    }
+    let (status, publish_at) = match item.status {
+        Some(status) if item.imported => imported_status(status, item.publish_at)?,
+        Some(status) if status != PostStatus::Draft => plan_transition(
+            PostStatus::Draft,
+            None,
+            PostTransition {
+                status,
+                publish_at: item.publish_at,
+            },
+        )?,
+        _ => (PostStatus::Draft, None),
+    };
+    let slug = match item.slug {
+        Some(v) => Set(checked_slug(db, &v, None).await?),
+        None => NotSet,
+    };
+    let post = ActiveModel {
+        title: Set(item.title),
+        content: Set(item.content),
+        slug,
+        status: Set(status),
+        publish_at: Set(publish_at),
+        author_id: Set(item.author_id),
+        ..Default::default()
+    }
+    .insert(db)
+    .await?;
+    add_revision(db, &post).await?;
+    tag::set_tags(db, post.id, item.tags).await?;
+    Ok(post.id)
+}
+
+async fn update_post_with_tags<C: ConnectionTrait>(db: &C, item: PostUpdate) -> Result<i32> {
+    let (id, tags) = (item.id, item.tags.clone());
+    let version = update_post(db, item).await?;
+    if let Some(tags) = tags {
+        tag::set_tags(db, id, tags).await?;
+    }
+    Ok(version)
+}
+
+/// Apply the update and record the result as a new revision, returning its version
+async fn update_post<C: ConnectionTrait>(db: &C, item: PostUpdate) -> Result<i32> {
+    if item.title.is_none() && item.content.is_none() && item.slug.is_none() {
+        if Entity::find_by_id(item.id).one(db).await?.is_none() {
+            return Err(Error::IdNotFound { id: item.id });
         }
+        return current_version(db, item.id).await;
+    }
+    let slug = match item.slug {
+        Some(v) => Set(checked_slug(db, &v, Some(item.id)).await?),
+        None => NotSet,
+    };
+    let post = ActiveModel {
+        id: Unchanged(item.id),
+        title: item.title.map_or(NotSet, Set),
+        content: item.content.map_or(NotSet, Set),
+        slug,
+        ..Default::default()
+    }
+    .update(db)
+    .await
+    .map_err(|e| match e {
+        DbErr::RecordNotUpdated => Error::IdNotFound { id: item.id },
+        e => e.into(),
+    })?;
+    add_revision(db, &post).await
+}
 
-        let paginator = cur.order_by_desc(Column::Id).paginate(&self.db, size);
+/// Normalize a slug given by the user, which must not be used by another Post
+async fn checked_slug<C: ConnectionTrait>(db: &C, slug: &str, id: Option<i32>) -> Result<String> {
+    let slug = utils::slugify(slug);
+    if slug.is_empty() {
+        return Err(Error::BadRequest);
+    }
+    if post::slug_taken(db, &slug, id).await? {
+        return Err(Error::SlugTaken { slug });
+    }
+    Ok(slug)
+}
 
-        let total = paginator.num_items().await?;
-        let res = paginator.fetch_page(page - 1).await?;
-        Ok((res, total))
+/// The row lock taken by the preceding write keeps concurrent versions apart
+async fn add_revision<C: ConnectionTrait>(db: &C, post: &Post) -> Result<i32> {
+    let version = current_version(db, post.id).await? + 1;
+    post_revision::ActiveModel {
+        post_id: Set(post.id),
+        version: Set(version),
+        title: Set(post.title.clone()),
+        content: Set(post.content.clone()),
+        created_at: Set(post.updated_at),
+        ..Default::default()
     }
+    .insert(db)
+    .await?;
+    Ok(version)
+}
+
+async fn current_version<C: ConnectionTrait>(db: &C, id: i32) -> Result<i32> {
+    let version: Option<i32> = post_revision::Entity::find()
+        .select_only()
+        .column_as(post_revision::Column::Version.max(), "version")
+        .filter(post_revision::Column::PostId.eq(id))
+        .into_tuple()
+        .one(db)
+        .await?
+        .flatten();
+    Ok(version.unwrap_or_default())
+}
+
+async fn find_revision<C: ConnectionTrait>(db: &C, id: i32, version: i32) -> Result<PostRevision> {
+    post_revision::Entity::find()
+        .filter(post_revision::Column::PostId.eq(id))
+        .filter(post_revision::Column::Version.eq(version))
+        .one(db)
+        .await?
+        .ok_or(Error::RevisionNotFound { id, version })
 }
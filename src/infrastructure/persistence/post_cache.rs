@@ -0,0 +1,710 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, Mutex, MutexGuard,
+};
+
+use async_trait::async_trait;
+use cached::{Cached, TimedSizedCache};
+
+use crate::{
+    app::log::*,
+    entity::{Comment, Post, PostRevision, PostStatus},
+    repository::{
+        AuditContext, BulkItem, BulkMode, CacheStats, CommentNew, CommentQuery, CommentRepo,
+        CommentUpdate, DeleteReport, PostNew, PostQuery, PostRepo, PostTransition, PostUpdate,
+        Result, RevisionQuery, TagMatch,
+    },
+};
+
+use super::{config, tag, CommentStore, PostStore, ViewCounterHandle};
+
+const DEFAULT_SIZE: usize = 1000;
+const DEFAULT_TTL_SECS: u64 = 60;
+
+/// Filters and page of a list query without text search
+type QueryKey = (
+    Option<i32>,
+    Option<PostStatus>,
+    Option<String>,
+    Option<TagMatch>,
+    u64,
+    u64,
+);
+
+/// Wrap the store with a read-through cache of fetched Posts and common list pages
+pub(super) fn with_cache(inner: PostStore, views: &ViewCounterHandle) -> Result<PostStore> {
+    Ok(Arc::new(CachedPostRepo::new(inner, views, configured)?))
+}
+
+/// Wrap the Comment store to drop the commented Post from the cache, as its comment count changes
+pub(super) fn with_post_eviction(inner: CommentStore, posts: &PostStore) -> CommentStore {
+    Arc::new(EvictingCommentRepo {
+        inner,
+        posts: posts.clone(),
+    })
+}
+
+/// Cache size and ttl in the config
+fn configured() -> Result<(usize, u64)> {
+    let conf = config::peek_config()?.post_cache.clone();
+    Ok((
+        conf.size.unwrap_or(DEFAULT_SIZE),
+        conf.ttl_secs.unwrap_or(DEFAULT_TTL_SECS),
+    ))
+}
+
+struct PostCache {
+    size: usize,
+    ttl_secs: u64,
+    /// published Posts, with views written to db only
+    posts: TimedSizedCache<i32, Post>,
+    lists: TimedSizedCache<QueryKey, (Vec<Post>, u64)>,
+}
+
+impl PostCache {
+    fn new(size: usize, ttl_secs: u64) -> Self {
+        Self {
+            size,
+            ttl_secs,
+            posts: TimedSizedCache::with_size_and_lifespan(size, ttl_secs),
+            lists: TimedSizedCache::with_size_and_lifespan(size, ttl_secs),
+        }
+    }
+
+    /// Drop list pages matching the predicate
+    fn drop_lists<F: Fn(&QueryKey, &(Vec<Post>, u64)) -> bool>(&mut self, f: F) {
+        let keys: Vec<QueryKey> = self
+            .lists
+            .key_order()
+            .zip(self.lists.value_order())
+            .filter(|(k, (_, v))| f(k, v))
+            .map(|(k, _)| k.clone())
+            .collect();
+        for k in keys {
+            self.lists.cache_remove(&k);
+        }
+    }
+
+    /// A changed Post leaves the cache, as do list pages showing it
+    fn drop_post(&mut self, id: i32) {
+        self.posts.cache_remove(&id);
+        self.drop_lists(|_, (posts, _)| posts.iter().any(|p| p.id == id));
+    }
+}
+
+/// Post store with cache in front
+struct CachedPostRepo {
+    inner: PostStore,
+    views: ViewCounterHandle,
+    /// cache size and ttl, read on every use to follow config changes
+    settings: fn() -> Result<(usize, u64)>,
+    cache: Arc<Mutex<Option<PostCache>>>,
+    /// bumped on every change, so that results read before it are not cached after it
+    generation: AtomicU64,
+    fetch_hits: AtomicU64,
+    fetch_misses: AtomicU64,
+    query_hits: AtomicU64,
+    query_misses: AtomicU64,
+}
+
+impl CachedPostRepo {
+    fn new(
+        inner: PostStore,
+        views: &ViewCounterHandle,
+        settings: fn() -> Result<(usize, u64)>,
+    ) -> Result<Self> {
+        let cache: Arc<Mutex<Option<PostCache>>> = Arc::default();
+        // views written to db are no longer pending, move them into the cached Posts
+        let flushed = cache.clone();
+        views.on_flush(Box::new(move |pending| {
+            if let Ok(mut cache) = flushed.lock() {
+                if let Some(cache) = cache.as_mut() {
+                    for (id, n) in pending {
+                        if let Some(post) = cache.posts.cache_get_mut(id) {
+                            post.views += n;
+                        }
+                    }
+                }
+            }
+        }))?;
+        Ok(Self {
+            inner,
+            views: views.clone(),
+            settings,
+            cache,
+            generation: AtomicU64::default(),
+            fetch_hits: AtomicU64::default(),
+            fetch_misses: AtomicU64::default(),
+            query_hits: AtomicU64::default(),
+            query_misses: AtomicU64::default(),
+        })
+    }
+
+    /// The cache, rebuilt if its config changed, or None if disabled
+    fn cache(&self) -> Result<MutexGuard<'_, Option<PostCache>>> {
+        let (size, ttl_secs) = (self.settings)()?;
+        let mut cache = self.cache.lock()?;
+        match cache.as_ref() {
+            Some(c) if c.size == size && c.ttl_secs == ttl_secs => {}
+            None if size == 0 => {}
+            _ => {
+                info!(?size, ?ttl_secs, "post cache reset");
+                *cache = (size > 0).then(|| PostCache::new(size, ttl_secs));
+            }
+        }
+        Ok(cache)
+    }
+
+    fn invalidate(&self, f: impl FnOnce(&mut PostCache)) -> Result<()> {
+        let mut cache = self.cache()?;
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        if let Some(cache) = cache.as_mut() {
+            f(cache);
+        }
+        Ok(())
+    }
+
+    /// Cache a result read at the generation, unless changes happened since
+    fn fill(&self, generation: u64, f: impl FnOnce(&mut PostCache)) -> Result<()> {
+        let mut cache = self.cache()?;
+        if let (Some(cache), true) = (
+            cache.as_mut(),
+            self.generation.load(Ordering::SeqCst) == generation,
+        ) {
+            f(cache);
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl PostRepo for CachedPostRepo {
+    async fn create(&self, item: PostNew, audit: Option<AuditContext>) -> Result<i32> {
+        let id = self.inner.create(item, audit).await?;
+        self.invalidate(|c| c.lists.cache_clear())?;
+        Ok(id)
+    }
+
+    async fn update(&self, item: PostUpdate, audit: Option<AuditContext>) -> Result<()> {
+        let (id, retagged) = (item.id, item.tags.is_some());
+        let ret = self.inner.update(item, audit).await;
+        self.invalidate(|c| {
+            c.drop_post(id);
+            if retagged {
+                c.drop_lists(|k, _| k.2.is_some());
+            }
+        })?;
+        ret
+    }
+
+    async fn delete(&self, ids: Vec<i32>, audit: Option<AuditContext>) -> Result<DeleteReport> {
+        let report = self.inner.delete(ids, audit).await?;
+        self.invalidate(|c| {
+            for id in &report.deleted {
+                c.posts.cache_remove(id);
+            }
+            if !report.deleted.is_empty() {
+                // later pages shift and totals change
+                c.lists.cache_clear();
+            }
+        })?;
+        Ok(report)
+    }
+
+    async fn create_many(
+        &self,
+        items: Vec<PostNew>,
+        mode: BulkMode,
+        audit: Option<AuditContext>,
+    ) -> Result<Vec<BulkItem>> {
+        let ret = self.inner.create_many(items, mode, audit).await?;
+        self.invalidate(|c| c.lists.cache_clear())?;
+        Ok(ret)
+    }
+
+    async fn update_many(
+        &self,
+        items: Vec<PostUpdate>,
+        mode: BulkMode,
+        audit: Option<AuditContext>,
+    ) -> Result<Vec<BulkItem>> {
+        let changed: Vec<(i32, bool)> = items.iter().map(|x| (x.id, x.tags.is_some())).collect();
+        let ret = self.inner.update_many(items, mode, audit).await;
+        self.invalidate(|c| {
+            for &(id, _) in &changed {
+                c.drop_post(id);
+            }
+            if changed.iter().any(|&(_, retagged)| retagged) {
+                c.drop_lists(|k, _| k.2.is_some());
+            }
+        })?;
+        ret
+    }
+
+    async fn fetch(&self, id: i32, viewer: Option<String>) -> Result<Post> {
+        let cached = self
+            .cache()?
+            .as_mut()
+            .and_then(|c| c.posts.cache_get(&id).cloned());
+        if let Some(mut post) = cached {
+            self.fetch_hits.fetch_add(1, Ordering::Relaxed);
+            post.views += self.views.record(id, viewer.as_deref())?;
+            return Ok(post);
+        }
+        self.fetch_misses.fetch_add(1, Ordering::Relaxed);
+        let generation = self.generation.load(Ordering::SeqCst);
+        let post = self.inner.fetch(id, viewer).await?;
+        let mut flushed = post.clone();
+        flushed.views -= self.views.pending(id)?;
+        self.fill(generation, |c| {
+            c.posts.cache_set(id, flushed);
+        })?;
+        Ok(post)
+    }
+
+    async fn fetch_unpublished(&self, id: i32) -> Result<Post> {
+        // only authors look at these, not worth caching
+        self.inner.fetch_unpublished(id).await
+    }
+
+    async fn snapshot(&self, ids: Vec<i32>) -> Result<Vec<Post>> {
+        self.inner.snapshot(ids).await
+    }
+
+    async fn find_slug(&self, slug: String) -> Result<(i32, String)> {
+        self.inner.find_slug(slug).await
+    }
+
+    async fn query(&self, params: PostQuery) -> Result<(Vec<Post>, u64)> {
+        // text searches are too diverse to be worth caching
+        if params.title.is_some() || params.content.is_some() {
+            return self.inner.query(params).await;
+        }
+        let key: QueryKey = (
+            params.author_id,
+            params.status,
+            params
+                .tags
+                .as_deref()
+                .map(|v| tag::normalize(v.split(',').map(str::to_owned).collect()).join(",")),
+            params.tag_match,
+            params.page.unwrap_or(1),
+            params.size.unwrap_or(10),
+        );
+        let cached = self
+            .cache()?
+            .as_mut()
+            .and_then(|c| c.lists.cache_get(&key).cloned());
+        if let Some(ret) = cached {
+            self.query_hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(ret);
+        }
+        self.query_misses.fetch_add(1, Ordering::Relaxed);
+        let generation = self.generation.load(Ordering::SeqCst);
+        let ret = self.inner.query(params).await?;
+        self.fill(generation, |c| {
+            c.lists.cache_set(key, ret.clone());
+        })?;
+        Ok(ret)
+    }
+
+    async fn scan(&self, params: PostQuery, after: i32, limit: u64) -> Result<Vec<Post>> {
+        self.inner.scan(params, after, limit).await
+    }
+
+    async fn revisions(&self, id: i32, params: RevisionQuery) -> Result<(Vec<PostRevision>, u64)> {
+        self.inner.revisions(id, params).await
+    }
+
+    async fn revision(&self, id: i32, version: i32) -> Result<PostRevision> {
+        self.inner.revision(id, version).await
+    }
+
+    async fn revert(&self, id: i32, version: i32, audit: Option<AuditContext>) -> Result<i32> {
+        let ret = self.inner.revert(id, version, audit).await;
+        self.invalidate(|c| c.drop_post(id))?;
+        ret
+    }
+
+    async fn transition(
+        &self,
+        id: i32,
+        to: PostTransition,
+        audit: Option<AuditContext>,
+    ) -> Result<()> {
+        let ret = self.inner.transition(id, to, audit).await;
+        self.invalidate(|c| {
+            c.posts.cache_remove(&id);
+            c.lists.cache_clear();
+        })?;
+        ret
+    }
+
+    async fn publish_due(&self) -> Result<Vec<i32>> {
+        let ids = self.inner.publish_due().await?;
+        if !ids.is_empty() {
+            self.invalidate(|c| {
+                for id in &ids {
+                    c.posts.cache_remove(id);
+                }
+                c.lists.cache_clear();
+            })?;
+        }
+        Ok(ids)
+    }
+
+    async fn authors(&self, ids: Vec<i32>) -> Result<Vec<(i32, Option<i32>)>> {
+        self.inner.authors(ids).await
+    }
+
+    fn evict(&self, id: i32) -> Result<()> {
+        self.invalidate(|c| c.drop_post(id))
+    }
+
+    fn cache_stats(&self) -> Option<CacheStats> {
+        let cache = self.cache().ok()?;
+        let (capacity, ttl_secs, entries) = cache.as_ref().map_or((0, 0, 0), |c| {
+            (
+                c.size,
+                c.ttl_secs,
+                c.posts.cache_size() + c.lists.cache_size(),
+            )
+        });
+        Some(CacheStats {
+            capacity,
+            ttl_secs,
+            entries,
+            fetch_hits: self.fetch_hits.load(Ordering::Relaxed),
+            fetch_misses: self.fetch_misses.load(Ordering::Relaxed),
+            query_hits: self.query_hits.load(Ordering::Relaxed),
+            query_misses: self.query_misses.load(Ordering::Relaxed),
+        })
+    }
+}
+
+/// Comment store dropping the commented Post from the cache on changes to the comment count
+struct EvictingCommentRepo {
+    inner: CommentStore,
+    posts: PostStore,
+}
+
+#[async_trait]
+impl CommentRepo for EvictingCommentRepo {
+    async fn create(&self, post_id: i32, item: CommentNew) -> Result<i32> {
+        let id = self.inner.create(post_id, item).await?;
+        self.posts.evict(post_id)?;
+        Ok(id)
+    }
+
+    async fn update(&self, post_id: i32, item: CommentUpdate) -> Result<()> {
+        self.inner.update(post_id, item).await
+    }
+
+    async fn delete(&self, post_id: i32, id: i32) -> Result<()> {
+        self.inner.delete(post_id, id).await?;
+        self.posts.evict(post_id)
+    }
+
+    async fn author(&self, post_id: i32, id: i32) -> Result<Option<i32>> {
+        self.inner.author(post_id, id).await
+    }
+
+    async fn query(&self, post_id: i32, params: CommentQuery) -> Result<(Vec<Comment>, u64)> {
+        self.inner.query(post_id, params).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use sea_orm::DbConn;
+    use serde_json::json;
+    use tokio::sync::Barrier;
+
+    use super::*;
+    use crate::{
+        entity::PostRevision,
+        infrastructure::persistence::views::ViewCounter,
+        repository::{Error, PostTransition, RevisionQuery},
+    };
+
+    /// Posts in memory, whose fetches can be held between reading and returning
+    #[derive(Default)]
+    struct FakePosts {
+        posts: Mutex<HashMap<i32, Post>>,
+        /// waited on twice by a fetch, once read and before returning, if set
+        hold: Mutex<Option<Arc<Barrier>>>,
+    }
+
+    impl FakePosts {
+        fn with(posts: &[(i32, PostStatus)]) -> Self {
+            let posts = posts
+                .iter()
+                .map(|&(id, status)| {
+                    let post = serde_json::from_value(json!({
+                        "id": id,
+                        "title": format!("post {id}"),
+                        "slug": format!("post-{id}"),
+                        "content": "c",
+                        "views": 0,
+                        "status": status,
+                        "created_at": "2024-07-01 08:00:00",
+                        "updated_at": "2024-07-01 08:00:00",
+                    }))
+                    .unwrap();
+                    (id, post)
+                })
+                .collect();
+            Self {
+                posts: Mutex::new(posts),
+                hold: Mutex::default(),
+            }
+        }
+
+        fn set_status(&self, id: i32, status: PostStatus) {
+            if let Some(p) = self.posts.lock().unwrap().get_mut(&id) {
+                p.status = status;
+            }
+        }
+    }
+
+    #[async_trait]
+    impl PostRepo for FakePosts {
+        async fn create(&self, _: PostNew, _: Option<AuditContext>) -> Result<i32> {
+            unimplemented!()
+        }
+
+        async fn update(&self, item: PostUpdate, _: Option<AuditContext>) -> Result<()> {
+            if let Some(p) = self.posts.lock().unwrap().get_mut(&item.id) {
+                p.title = item.title.unwrap_or_default();
+            }
+            Ok(())
+        }
+
+        async fn delete(&self, ids: Vec<i32>, _: Option<AuditContext>) -> Result<DeleteReport> {
+            let mut posts = self.posts.lock().unwrap();
+            let (deleted, not_found) = ids.into_iter().partition(|x| posts.remove(x).is_some());
+            Ok(DeleteReport { deleted, not_found })
+        }
+
+        async fn create_many(
+            &self,
+            _: Vec<PostNew>,
+            _: BulkMode,
+            _: Option<AuditContext>,
+        ) -> Result<Vec<BulkItem>> {
+            unimplemented!()
+        }
+
+        async fn update_many(
+            &self,
+            _: Vec<PostUpdate>,
+            _: BulkMode,
+            _: Option<AuditContext>,
+        ) -> Result<Vec<BulkItem>> {
+            unimplemented!()
+        }
+
+        async fn fetch(&self, id: i32, _: Option<String>) -> Result<Post> {
+            let post = self
+                .posts
+                .lock()
+                .unwrap()
+                .get(&id)
+                .filter(|p| p.status == PostStatus::Published)
+                .cloned()
+                .ok_or(Error::IdNotFound { id })?;
+            let hold = self.hold.lock().unwrap().clone();
+            if let Some(hold) = hold {
+                hold.wait().await;
+                hold.wait().await;
+            }
+            Ok(post)
+        }
+
+        async fn fetch_unpublished(&self, _: i32) -> Result<Post> {
+            unimplemented!()
+        }
+
+        async fn snapshot(&self, _: Vec<i32>) -> Result<Vec<Post>> {
+            unimplemented!()
+        }
+
+        async fn find_slug(&self, _: String) -> Result<(i32, String)> {
+            unimplemented!()
+        }
+
+        async fn query(&self, _: PostQuery) -> Result<(Vec<Post>, u64)> {
+            let mut list: Vec<Post> = self
+                .posts
+                .lock()
+                .unwrap()
+                .values()
+                .filter(|p| p.status == PostStatus::Published)
+                .cloned()
+                .collect();
+            list.sort_by_key(|p| p.id);
+            let total = list.len() as u64;
+            Ok((list, total))
+        }
+
+        async fn scan(&self, _: PostQuery, _: i32, _: u64) -> Result<Vec<Post>> {
+            unimplemented!()
+        }
+
+        async fn revisions(&self, _: i32, _: RevisionQuery) -> Result<(Vec<PostRevision>, u64)> {
+            unimplemented!()
+        }
+
+        async fn revision(&self, _: i32, _: i32) -> Result<PostRevision> {
+            unimplemented!()
+        }
+
+        async fn revert(&self, _: i32, _: i32, _: Option<AuditContext>) -> Result<i32> {
+            unimplemented!()
+        }
+
+        async fn transition(
+            &self,
+            id: i32,
+            to: PostTransition,
+            _: Option<AuditContext>,
+        ) -> Result<()> {
+            self.set_status(id, to.status);
+            Ok(())
+        }
+
+        async fn publish_due(&self) -> Result<Vec<i32>> {
+            let mut due: Vec<i32> = self
+                .posts
+                .lock()
+                .unwrap()
+                .values()
+                .filter(|p| p.status == PostStatus::Scheduled)
+                .map(|p| p.id)
+                .collect();
+            due.sort_unstable();
+            for &id in &due {
+                self.set_status(id, PostStatus::Published);
+            }
+            Ok(due)
+        }
+
+        async fn authors(&self, _: Vec<i32>) -> Result<Vec<(i32, Option<i32>)>> {
+            unimplemented!()
+        }
+    }
+
+    fn small_cache() -> Result<(usize, u64)> {
+        Ok((10, 60))
+    }
+
+    fn cached_store(inner: &Arc<FakePosts>) -> Arc<CachedPostRepo> {
+        let views = ViewCounter::setup(&DbConn::Disconnected);
+        Arc::new(CachedPostRepo::new(inner.clone(), &views, small_cache).unwrap())
+    }
+
+    fn any_query() -> PostQuery {
+        serde_json::from_value(json!({})).unwrap()
+    }
+
+    /// Whether the Post is cached, and how many list pages are
+    fn cached(store: &CachedPostRepo, id: i32) -> (bool, usize) {
+        let mut cache = store.cache().unwrap();
+        let cache = cache.as_mut().unwrap();
+        (
+            cache.posts.cache_get(&id).is_some(),
+            cache.lists.cache_size(),
+        )
+    }
+
+    /// Fetch the Posts and list them, so that all are cached
+    async fn fill(store: &CachedPostRepo, ids: &[i32]) {
+        for &id in ids {
+            store.fetch(id, None).await.unwrap();
+        }
+        store.query(any_query()).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn changes_evict_cached_posts() {
+        use PostStatus::*;
+
+        let inner = Arc::new(FakePosts::with(&[
+            (1, Published),
+            (2, Published),
+            (3, Scheduled),
+        ]));
+        let store = cached_store(&inner);
+        fill(&store, &[1, 2]).await;
+        assert_eq!(cached(&store, 1), (true, 1));
+
+        let update = PostUpdate {
+            id: 1,
+            title: Some("new".to_owned()),
+            content: None,
+            slug: None,
+            tags: None,
+        };
+        store.update(update, None).await.unwrap();
+        assert_eq!(cached(&store, 1), (false, 0));
+        assert!(cached(&store, 2).0);
+        assert_eq!(store.fetch(1, None).await.unwrap().title, "new");
+
+        // comments changed under the Post
+        fill(&store, &[1, 2]).await;
+        store.evict(2).unwrap();
+        assert_eq!(cached(&store, 2), (false, 0));
+        assert!(cached(&store, 1).0);
+
+        fill(&store, &[1, 2]).await;
+        let to = PostTransition {
+            status: Draft,
+            publish_at: None,
+        };
+        store.transition(2, to, None).await.unwrap();
+        assert_eq!(cached(&store, 2), (false, 0));
+        assert!(store.fetch(2, None).await.is_err());
+
+        fill(&store, &[1]).await;
+        assert_eq!(store.publish_due().await.unwrap(), [3]);
+        assert_eq!(cached(&store, 1), (true, 0));
+        let (list, _) = store.query(any_query()).await.unwrap();
+        assert_eq!(list.iter().map(|p| p.id).collect::<Vec<_>>(), [1, 3]);
+
+        store.delete(vec![1], None).await.unwrap();
+        assert_eq!(cached(&store, 1), (false, 0));
+        assert!(store.fetch(1, None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn read_before_change_not_cached() {
+        let inner = Arc::new(FakePosts::with(&[(1, PostStatus::Published)]));
+        let store = cached_store(&inner);
+        let hold = Arc::new(Barrier::new(2));
+        *inner.hold.lock().unwrap() = Some(hold.clone());
+
+        let reading = tokio::spawn({
+            let store = store.clone();
+            async move { store.fetch(1, None).await }
+        });
+        // read the old title, now change it before the read fills the cache
+        hold.wait().await;
+        let update = PostUpdate {
+            id: 1,
+            title: Some("new".to_owned()),
+            content: None,
+            slug: None,
+            tags: None,
+        };
+        store.update(update, None).await.unwrap();
+        hold.wait().await;
+        assert_eq!(reading.await.unwrap().unwrap().title, "post 1");
+        assert!(!cached(&store, 1).0);
+
+        *inner.hold.lock().unwrap() = None;
+        assert_eq!(store.fetch(1, None).await.unwrap().title, "new");
+        assert!(cached(&store, 1).0);
+    }
+}
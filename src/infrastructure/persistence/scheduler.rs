@@ -0,0 +1,40 @@
+use tokio::time::{Duration, MissedTickBehavior};
+
+use crate::app::log::*;
+
+use super::{config, PostStore};
+
+const DEFAULT_PUBLISH_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Publish scheduled Posts in background
+pub(super) fn spawn_publisher(store: PostStore) {
+    tokio::spawn(async move {
+        let mut inter = publish_check_interval();
+        let mut ticker = tokio::time::interval(inter);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            match store.publish_due().await {
+                Ok(ids) if ids.is_empty() => {}
+                Ok(ids) => info!(?ids, "scheduled posts published"),
+                Err(e) => error!(%e, "publish scheduled posts failed"),
+            }
+            // pick up config changes
+            let cur = publish_check_interval();
+            if cur != inter {
+                inter = cur;
+                ticker = tokio::time::interval(inter);
+                ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+                ticker.tick().await;
+            }
+        }
+    });
+}
+
+fn publish_check_interval() -> Duration {
+    config::peek_config()
+        .ok()
+        .and_then(|c| c.post.publish_check_secs)
+        .filter(|&v| v > 0)
+        .map_or(DEFAULT_PUBLISH_CHECK_INTERVAL, Duration::from_secs)
+}
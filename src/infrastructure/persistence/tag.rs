@@ -0,0 +1,156 @@
+use std::{collections::HashMap, sync::Arc};
+
+use async_trait::async_trait;
+use sea_orm::sea_query::{Alias, Expr, OnConflict, Query, SelectStatement};
+use sea_orm::*;
+
+use crate::{
+    app::log::*,
+    entity::{post_tag, tag, Post},
+    repository::{Result, TagCount, TagMatch, TagRepo},
+};
+
+use super::conn::DbConns;
+
+pub type TagStore = Arc<dyn TagRepo + Send + Sync>;
+
+pub(super) fn get_tag_store(conns: &DbConns) -> TagStore {
+    Arc::new(TagRepoImp {
+        reads: conns.clone(),
+    })
+}
+
+/// Database Tag store
+struct TagRepoImp {
+    reads: DbConns,
+}
+
+#[async_trait]
+impl TagRepo for TagRepoImp {
+    async fn list(&self) -> Result<Vec<TagCount>> {
+        info!("list tags");
+        self.reads.read(|db| Box::pin(list_tags(db))).await
+    }
+}
+
+async fn list_tags<C: ConnectionTrait>(db: &C) -> Result<Vec<TagCount>> {
+    let res: Vec<(String, i64)> = tag::Entity::find()
+        .select_only()
+        .column(tag::Column::Name)
+        .column_as(post_tag::Column::PostId.count(), "posts")
+        .left_join(post_tag::Entity)
+        .group_by(tag::Column::Id)
+        .group_by(tag::Column::Name)
+        .order_by_desc(Expr::col(Alias::new("posts")))
+        .order_by_asc(tag::Column::Name)
+        .into_tuple()
+        .all(db)
+        .await?;
+    Ok(res
+        .into_iter()
+        .map(|(name, posts)| TagCount { name, posts })
+        .collect())
+}
+
+/// trim, drop empty and duplicated tag names
+pub(super) fn normalize(tags: Vec<String>) -> Vec<String> {
+    let mut ret: Vec<String> = Vec::with_capacity(tags.len());
+    for t in tags {
+        let t = t.trim();
+        if !t.is_empty() && !ret.iter().any(|x| x == t) {
+            ret.push(t.to_owned());
+        }
+    }
+    ret
+}
+
+/// Replace all tags of the Post, creating missing tags on the way
+pub(super) async fn set_tags<C: ConnectionTrait>(
+    db: &C,
+    post_id: i32,
+    tags: Vec<String>,
+) -> Result<()> {
+    post_tag::Entity::delete_many()
+        .filter(post_tag::Column::PostId.eq(post_id))
+        .exec(db)
+        .await?;
+    let tags = normalize(tags);
+    if tags.is_empty() {
+        return Ok(());
+    }
+    tag::Entity::insert_many(tags.iter().map(|name| tag::ActiveModel {
+        name: Set(name.clone()),
+        ..Default::default()
+    }))
+    .on_conflict(
+        OnConflict::column(tag::Column::Name)
+            .do_nothing()
+            .to_owned(),
+    )
+    .exec_without_returning(db)
+    .await?;
+    let tag_ids: Vec<i32> = tag::Entity::find()
+        .select_only()
+        .column(tag::Column::Id)
+        .filter(tag::Column::Name.is_in(tags))
+        .into_tuple()
+        .all(db)
+        .await?;
+    post_tag::Entity::insert_many(tag_ids.into_iter().map(|tag_id| post_tag::ActiveModel {
+        post_id: Set(post_id),
+        tag_id: Set(tag_id),
+    }))
+    .exec_without_returning(db)
+    .await?;
+    Ok(())
+}
+
+/// Fill in tags of the Posts
+pub(super) async fn load_tags<C: ConnectionTrait>(db: &C, posts: &mut [Post]) -> Result<()> {
+    if posts.is_empty() {
+        return Ok(());
+    }
+    let rows: Vec<(i32, String)> = post_tag::Entity::find()
+        .select_only()
+        .column(post_tag::Column::PostId)
+        .column(tag::Column::Name)
+        .inner_join(tag::Entity)
+        .filter(post_tag::Column::PostId.is_in(posts.iter().map(|x| x.id)))
+        .order_by_asc(tag::Column::Name)
+        .into_tuple()
+        .all(db)
+        .await?;
+    let mut tags: HashMap<i32, Vec<String>> = HashMap::new();
+    for (post_id, name) in rows {
+        tags.entry(post_id).or_default().push(name);
+    }
+    for post in posts {
+        post.tags = tags.remove(&post.id).unwrap_or_default();
+    }
+    Ok(())
+}
+
+/// Sub query of ids of Posts tagged by any or all of the tags
+pub(super) fn tagged_post_ids(tags: Vec<String>, mode: TagMatch) -> SelectStatement {
+    let n = tags.len() as i64;
+    let mut query = Query::select();
+    query
+        .column((post_tag::Entity, post_tag::Column::PostId))
+        .from(post_tag::Entity)
+        .inner_join(
+            tag::Entity,
+            Expr::col((tag::Entity, tag::Column::Id))
+                .equals((post_tag::Entity, post_tag::Column::TagId)),
+        )
+        .and_where(Expr::col((tag::Entity, tag::Column::Name)).is_in(tags));
+    if mode == TagMatch::All {
+        query
+            .group_by_col((post_tag::Entity, post_tag::Column::PostId))
+            .and_having(
+                Expr::col((post_tag::Entity, post_tag::Column::TagId))
+                    .count_distinct()
+                    .eq(n),
+            );
+    }
+    query
+}
@@ -1,110 +1,1325 @@
-use std::sync::{
-    atomic::{AtomicI32, Ordering},
-    Arc, Mutex,
+use std::{
+    cmp::Ordering as CmpOrdering,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    sync::{
+        atomic::{AtomicI32, Ordering},
+        Arc,
+    },
 };
 
+use async_trait::async_trait;
+use sea_orm::sea_query::{Expr, Func};
+use sea_orm::*;
+use tokio::{sync::RwLock, time::Duration};
+
 use crate::{
-    app::log::*,
-    entity::Todo,
-    repository::{Error, Result, TodoQuery, TodoRepo, TodoUpdate},
+    app::{log::*, utils},
+    entity::{
+        todo::{ActiveModel, Column, Entity},
+        DateTimeTZ, Todo,
+    },
+    event::Resource,
+    repository::{
+        AuditContext, AuditNew, Error, Result, SortOrder, TodoChanges, TodoQuery, TodoRepo,
+        TodoSort, TodoUpdate,
+    },
+};
+
+use super::{
+    audit,
+    config::{self, TodoBackend},
+    conn::DbConns,
+    todo_journal::TodoJournal,
 };
 
+const DEFAULT_SNAPSHOT_INTERVAL: Duration = Duration::from_secs(60);
+
 pub type TodoStore = Arc<dyn TodoRepo + Send + Sync>;
 
-pub(super) fn get_todo_store() -> TodoStore {
-    Arc::new(TodoRepoImp {
-        data: Mutex::default(),
+/// Todo store of the configured backend
+pub(super) fn get_todo_store(conns: &DbConns) -> Result<TodoStore> {
+    let conf = config::peek_config()
+        .map(|c| c.todo.clone())
+        .unwrap_or_default();
+    info!(backend = ?conf.backend, "todo store");
+    Ok(match conf.backend {
+        TodoBackend::Memory => {
+            let Some(path) = conf.snapshot_path else {
+                return Ok(Arc::new(TodoRepoImp {
+                    data: RwLock::default(),
+                    seq: AtomicI32::new(1),
+                    journal: None,
+                    db: conns.primary().clone(),
+                }));
+            };
+            let (journal, restored) = TodoJournal::open(&path)?;
+            let store = Arc::new(TodoRepoImp {
+                data: RwLock::new(TodoTable::new(restored.todos)),
+                seq: AtomicI32::new(restored.seq),
+                journal: Some(journal),
+                db: conns.primary().clone(),
+            });
+            tokio::spawn(store.clone().snapshot_periodically());
+            store
+        }
+        TodoBackend::Postgres => Arc::new(TodoDbRepoImp {
+            db: conns.primary().clone(),
+            reads: conns.clone(),
+        }),
     })
 }
 
 /// In-memory Todo store
 struct TodoRepoImp {
-    data: Mutex<Vec<Todo>>,
+    data: RwLock<TodoTable>,
+    seq: AtomicI32,
+    /// where changes are kept across restarts, if configured
+    journal: Option<TodoJournal>,
+    /// where changes are audited
+    db: DbConn,
+}
+
+/// Todos of the in-memory store, indexed by id, by manual order and by parent
+#[derive(Default)]
+struct TodoTable {
+    items: BTreeMap<i32, TodoEntry>,
+    /// (position, id) of every Todo
+    order: BTreeSet<(i32, i32)>,
+    /// ids of the direct subtasks of each parent
+    children: HashMap<i32, BTreeSet<i32>>,
+}
+
+struct TodoEntry {
+    todo: Todo,
+    /// lowercased value, for search
+    lower: String,
+}
+
+impl TodoTable {
+    fn new(todos: Vec<Todo>) -> Self {
+        let mut table = Self::default();
+        for x in todos {
+            table.put(x);
+        }
+        table
+    }
+
+    fn get(&self, id: i32) -> Option<&Todo> {
+        self.items.get(&id).map(|x| &x.todo)
+    }
+
+    /// Insert the Todo, or replace the one of the same id
+    fn put(&mut self, todo: Todo) {
+        let lower = match self.remove(todo.id) {
+            Some(old) if old.todo.value == todo.value => old.lower,
+            _ => todo.value.to_lowercase(),
+        };
+        self.order.insert((todo.position, todo.id));
+        if let Some(parent) = todo.parent_id {
+            self.children.entry(parent).or_default().insert(todo.id);
+        }
+        self.items.insert(todo.id, TodoEntry { todo, lower });
+    }
+
+    fn remove(&mut self, id: i32) -> Option<TodoEntry> {
+        let old = self.items.remove(&id)?;
+        self.order.remove(&(old.todo.position, id));
+        if let Some(parent) = old.todo.parent_id {
+            if let Some(ids) = self.children.get_mut(&parent) {
+                ids.remove(&id);
+                if ids.is_empty() {
+                    self.children.remove(&parent);
+                }
+            }
+        }
+        Some(old)
+    }
+
+    fn last_position(&self) -> i32 {
+        self.order.last().map_or(0, |x| x.0)
+    }
+
+    /// All Todos, by id
+    fn iter(&self) -> impl Iterator<Item = &TodoEntry> {
+        self.items.values()
+    }
+
+    fn children(&self, id: i32) -> impl Iterator<Item = &Todo> {
+        self.children
+            .get(&id)
+            .into_iter()
+            .flatten()
+            .filter_map(|&x| self.get(x))
+    }
+
+    /// Ids of the Todo and its subtasks at any depth, parents first
+    fn subtree(&self, id: i32) -> Vec<i32> {
+        let mut ids = Vec::new();
+        if self.get(id).is_some() {
+            ids.push(id);
+        }
+        let mut i = 0;
+        while i < ids.len() {
+            let children = self.children.get(&ids[i]).into_iter().flatten();
+            ids.extend(children);
+            i += 1;
+        }
+        ids
+    }
 }
 
-static TODO_SEQ: AtomicI32 = AtomicI32::new(1);
+/// Changes to the table, undone if they cannot be journaled
+struct TableChange<'a> {
+    table: &'a mut TodoTable,
+    /// Todos before the first change of each
+    before: BTreeMap<i32, Option<Todo>>,
+}
+
+impl<'a> TableChange<'a> {
+    fn new(table: &'a mut TodoTable) -> Self {
+        Self {
+            table,
+            before: BTreeMap::new(),
+        }
+    }
+
+    fn put(&mut self, todo: Todo) {
+        let table = &mut *self.table;
+        self.before
+            .entry(todo.id)
+            .or_insert_with(|| table.get(todo.id).cloned());
+        table.put(todo);
+    }
+
+    fn remove(&mut self, id: i32) {
+        let table = &mut *self.table;
+        self.before
+            .entry(id)
+            .or_insert_with(|| table.get(id).cloned());
+        table.remove(id);
+    }
+
+    /// Complete the Todo and then its ancestors, each if set to auto-complete
+    /// and all its subtasks are done
+    fn auto_complete(&mut self, mut id: Option<i32>) {
+        let now = utils::get_current_time();
+        while let Some(x) = id.and_then(|id| self.table.get(id)) {
+            if x.done || !x.auto_complete {
+                break;
+            }
+            let all_done = {
+                let mut children = self.table.children(x.id).peekable();
+                children.peek().is_some() && children.all(|c| c.done)
+            };
+            if !all_done {
+                break;
+            }
+            let mut x = x.clone();
+            x.done = true;
+            x.completed_at = Some(now);
+            id = x.parent_id;
+            self.put(x);
+        }
+    }
+
+    /// Journal and audit the changes, or undo them, telling which Todos they reached
+    async fn commit(self, store: &TodoRepoImp, audit: Option<AuditContext>) -> Result<TodoChanges> {
+        let mut changes = TodoChanges::default();
+        let mut put = Vec::new();
+        for (&id, old) in &self.before {
+            let Some(x) = self.table.get(id) else {
+                changes.deleted.push(id);
+                continue;
+            };
+            put.push(x);
+            if let Some(old) = old {
+                if !old.done && x.done {
+                    changes.completed.push(id);
+                }
+                if old.position != x.position {
+                    changes.moved.push(id);
+                }
+                if old.parent_id != x.parent_id {
+                    changes.reparented.push(id);
+                }
+            }
+        }
+        let audit = audit.filter(|_| !self.before.is_empty()).map(|x| {
+            let ids = self.before.keys().copied().collect();
+            let before: Vec<&Todo> = self.before.values().flatten().collect();
+            x.change(Resource::Todo, ids, &before, &put)
+        });
+        if let Err(e) = store.save(&put, &changes.deleted, audit).await {
+            for (id, old) in self.before {
+                match old {
+                    Some(x) => self.table.put(x),
+                    None => {
+                        self.table.remove(id);
+                    }
+                }
+            }
+            return Err(e);
+        }
+        Ok(changes)
+    }
+}
+
+impl TodoRepoImp {
+    /// Journal the change, with its audit entry committed only if that succeeds
+    async fn save(&self, put: &[&Todo], deleted: &[i32], audit: Option<AuditNew>) -> Result<()> {
+        let txn = match audit {
+            Some(item) => {
+                let txn = self.db.begin().await?;
+                audit::append(&txn, item).await?;
+                Some(txn)
+            }
+            None => None,
+        };
+        if let Some(journal) = &self.journal {
+            journal.record(put, deleted).await?;
+        }
+        if let Some(txn) = txn {
+            txn.commit().await?;
+        }
+        Ok(())
+    }
+
+    async fn snapshot_periodically(self: Arc<Self>) {
+        loop {
+            tokio::time::sleep(snapshot_interval()).await;
+            if let Err(e) = self.flush().await {
+                error!(%e, "snapshot todos failed");
+            }
+        }
+    }
+}
+
+fn snapshot_interval() -> Duration {
+    config::peek_config()
+        .ok()
+        .and_then(|c| c.todo.snapshot_secs)
+        .filter(|&v| v > 0)
+        .map_or(DEFAULT_SNAPSHOT_INTERVAL, Duration::from_secs)
+}
 
+#[async_trait]
 impl TodoRepo for TodoRepoImp {
-    fn create(&self, mut item: Todo) -> Result<i32> {
+    async fn create(
+        &self,
+        mut item: Todo,
+        audit: Option<AuditContext>,
+    ) -> Result<(i32, TodoChanges)> {
         info!(?item, "create todo");
-        // let mut list = self.data.lock()?;
-        // let max_id = match list.iter().max_by_key(|x| x.id) {
-        //     Some(x) => x.id + 1,
-        //     None => 1,
-        // };
-        // item.id = max_id;
-        // list.push(item);
-        let id = TODO_SEQ.fetch_add(1, Ordering::SeqCst);
+        let now = utils::get_current_time();
+        item.created_at = now;
+        item.completed_at = item.done.then_some(now);
+        let mut table = self.data.write().await;
+        if let Some(parent) = item.parent_id {
+            if table.get(parent).is_none() {
+                return Err(Error::IdNotFound { id: parent });
+            }
+        }
+        let id = self.seq.fetch_add(1, Ordering::SeqCst);
         item.id = id;
-        self.data.lock()?.push(item);
-        Ok(id)
+        item.position = table.last_position() + 1;
+        let parent = item.parent_id;
+        let mut change = TableChange::new(&mut table);
+        change.put(item);
+        change.auto_complete(parent);
+        let changes = change.commit(self, audit).await?;
+        Ok((id, changes))
     }
 
-    fn update(&self, item: TodoUpdate) -> Result<()> {
+    async fn update(&self, item: TodoUpdate, audit: Option<AuditContext>) -> Result<TodoChanges> {
         info!(?item, "update todo");
         if item.id <= 0 {
             return Err(Error::IdNotFound { id: item.id });
         }
-        if item.value.is_none() && item.done.is_none() {
-            return Ok(());
+        if item.is_empty() {
+            return Ok(TodoChanges::default());
         }
-        self.data
-            .lock()?
-            .iter_mut()
-            .find(|x| x.id == item.id)
-            .map_or(Err(Error::IdNotFound { id: item.id }), |x| {
-                if let Some(v) = item.value {
-                    x.value = v
-                }
-                if let Some(v) = item.done {
-                    x.done = v
-                }
-                Ok(())
-            })
+        let mut table = self.data.write().await;
+        let Some(mut x) = table.get(item.id).cloned() else {
+            return Err(Error::IdNotFound { id: item.id });
+        };
+        let subtasks = match (item.done, item.cascade) {
+            (Some(true), true) => table.subtree(item.id),
+            _ => Vec::new(),
+        };
+        if let Some(v) = item.value {
+            x.value = v
+        }
+        if let Some(v) = item.done {
+            x.completed_at = completed_at(x.done, x.completed_at, v);
+            x.done = v
+        }
+        if let Some(v) = item.due_at {
+            x.due_at = v
+        }
+        if let Some(v) = item.priority {
+            x.priority = v
+        }
+        if let Some(v) = item.auto_complete {
+            x.auto_complete = v
+        }
+        let (id, parent) = (x.id, x.parent_id);
+        let mut change = TableChange::new(&mut table);
+        change.put(x);
+        let now = utils::get_current_time();
+        for sub in subtasks.into_iter().skip(1) {
+            if let Some(x) = change.table.get(sub).filter(|x| !x.done) {
+                let mut x = x.clone();
+                x.done = true;
+                x.completed_at = Some(now);
+                change.put(x);
+            }
+        }
+        change.auto_complete(Some(id));
+        change.auto_complete(parent);
+        change.commit(self, audit).await
     }
 
-    fn delete(&self, ids: Vec<i32>) -> Result<()> {
+    async fn delete(&self, ids: Vec<i32>, audit: Option<AuditContext>) -> Result<TodoChanges> {
         info!(?ids, "delete todos");
-        self.data.lock()?.retain(|x| !ids.contains(&x.id));
-        Ok(())
+        let mut table = self.data.write().await;
+        let mut parents = Vec::new();
+        let mut removed = Vec::new();
+        for id in ids {
+            if let Some(x) = table.get(id) {
+                parents.push(x.parent_id);
+                removed.extend(table.subtree(id));
+            }
+        }
+        let mut change = TableChange::new(&mut table);
+        for id in removed {
+            change.remove(id);
+        }
+        for parent in parents {
+            change.auto_complete(parent);
+        }
+        change.commit(self, audit).await
     }
 
-    fn fetch(&self, id: i32) -> Result<Todo> {
+    async fn fetch(&self, id: i32) -> Result<Todo> {
         info!(?id, "fetch todo");
-        if id <= 0 {
-            return Err(Error::IdNotFound { id });
-        }
-        let list = self.data.lock()?;
-        let target = list.iter().find(|x| x.id == id);
-        match target {
+        match self.data.read().await.get(id) {
             Some(x) => Ok(x.clone()),
             None => Err(Error::IdNotFound { id }),
         }
     }
 
-    fn query(&self, mut req: TodoQuery) -> Result<Vec<Todo>> {
+    async fn query(&self, mut req: TodoQuery) -> Result<(Vec<Todo>, u64)> {
         info!(?req, "query todos");
-        if let Some(v) = req.value.as_mut() {
-            v.make_ascii_lowercase();
-        }
-        Ok(self
-            .data
-            .lock()?
+        req.value = req.value.map(|v| v.to_lowercase());
+        let now = utils::get_current_time();
+        let table = self.data.read().await;
+        let mut matched: Vec<&Todo> = table
             .iter()
-            .filter(|x| {
-                if let Some(ref v) = req.value {
-                    if !x.value.to_lowercase().contains(v) {
-                        return false;
-                    }
-                }
-                if let Some(v) = req.done {
-                    if x.done != v {
-                        return false;
-                    }
-                }
-                true
-            })
+            .filter(|x| matches(x, &req, now))
+            .map(|x| &x.todo)
+            .collect();
+        let sort = req.sort.unwrap_or_default();
+        let order = req.order.unwrap_or(sort.default_order());
+        matched.sort_unstable_by(|a, b| compare(sort, order, a, b));
+        let total = matched.len() as u64;
+        let size = req.size.unwrap_or(10);
+        let skip = req.page.unwrap_or(1).saturating_sub(1).saturating_mul(size);
+        let page = matched
+            .into_iter()
+            .skip(skip as usize)
+            .take(size as usize)
             .cloned()
+            .collect();
+        Ok((page, total))
+    }
+
+    async fn reorder(
+        &self,
+        id: i32,
+        position: i32,
+        audit: Option<AuditContext>,
+    ) -> Result<TodoChanges> {
+        info!(?id, ?position, "reorder todo");
+        let mut table = self.data.write().await;
+        let ids = table.order.iter().map(|&(_, id)| id).collect();
+        let moved: Vec<Todo> = move_to(ids, id, position)?
+            .into_iter()
+            .filter_map(|(id, p)| {
+                table.get(id).map(|x| Todo {
+                    position: p,
+                    ..x.clone()
+                })
+            })
+            .collect();
+        let mut change = TableChange::new(&mut table);
+        for x in moved {
+            change.put(x);
+        }
+        change.commit(self, audit).await
+    }
+
+    async fn subtree(&self, id: i32) -> Result<Vec<Todo>> {
+        info!(?id, "fetch todo subtree");
+        let table = self.data.read().await;
+        let ids = table.subtree(id);
+        if ids.is_empty() {
+            return Err(Error::IdNotFound { id });
+        }
+        Ok(ids
+            .into_iter()
+            .filter_map(|x| table.get(x).cloned())
             .collect())
     }
+
+    async fn reparent(
+        &self,
+        id: i32,
+        parent_id: Option<i32>,
+        audit: Option<AuditContext>,
+    ) -> Result<TodoChanges> {
+        info!(?id, ?parent_id, "move todo subtree");
+        let mut table = self.data.write().await;
+        let Some(mut x) = table.get(id).cloned() else {
+            return Err(Error::IdNotFound { id });
+        };
+        if let Some(parent) = parent_id {
+            if table.get(parent).is_none() {
+                return Err(Error::IdNotFound { id: parent });
+            }
+            if table.subtree(id).contains(&parent) {
+                return Err(Error::TodoCycle {
+                    id,
+                    parent_id: parent,
+                });
+            }
+        }
+        let old = std::mem::replace(&mut x.parent_id, parent_id);
+        if old == parent_id {
+            return Ok(TodoChanges::default());
+        }
+        let mut change = TableChange::new(&mut table);
+        change.put(x);
+        change.auto_complete(old);
+        change.auto_complete(parent_id);
+        change.commit(self, audit).await
+    }
+
+    async fn flush(&self) -> Result<()> {
+        let Some(journal) = &self.journal else {
+            return Ok(());
+        };
+        if !journal.is_dirty() {
+            return Ok(());
+        }
+        // no changes until the journal is emptied
+        let table = self.data.read().await;
+        let todos: Vec<&Todo> = table.iter().map(|x| &x.todo).collect();
+        journal.save(&todos, self.seq.load(Ordering::SeqCst)).await
+    }
+}
+
+/// Whether the Todo passes the filters of the query, with `value` already lowercased
+fn matches(x: &TodoEntry, req: &TodoQuery, now: DateTimeTZ) -> bool {
+    if let Some(ref v) = req.value {
+        if !x.lower.contains(v) {
+            return false;
+        }
+    }
+    let x = &x.todo;
+    if let Some(v) = req.done {
+        if x.done != v {
+            return false;
+        }
+    }
+    if let Some(v) = req.overdue {
+        if x.is_overdue(now) != v {
+            return false;
+        }
+    }
+    if let Some(v) = req.due_after {
+        if x.due_at.is_none_or(|d| d < v) {
+            return false;
+        }
+    }
+    if let Some(v) = req.due_before {
+        if x.due_at.is_none_or(|d| d >= v) {
+            return false;
+        }
+    }
+    if let Some(v) = req.priority {
+        if x.priority != v {
+            return false;
+        }
+    }
+    if req.parent_id.is_some() && x.parent_id != req.parent_id {
+        return false;
+    }
+    true
+}
+
+/// Order of the sort, ties in manual order
+fn compare(sort: TodoSort, order: SortOrder, a: &Todo, b: &Todo) -> CmpOrdering {
+    let directed = |x: CmpOrdering| match order {
+        SortOrder::Asc => x,
+        SortOrder::Desc => x.reverse(),
+    };
+    let first = match sort {
+        TodoSort::Position => directed(a.position.cmp(&b.position)),
+        TodoSort::DueAt => match (a.due_at, b.due_at) {
+            (Some(x), Some(y)) => directed(x.cmp(&y)),
+            (Some(_), None) => CmpOrdering::Less,
+            (None, Some(_)) => CmpOrdering::Greater,
+            (None, None) => CmpOrdering::Equal,
+        },
+        TodoSort::Priority => directed(a.priority.cmp(&b.priority)),
+        TodoSort::CreatedAt => directed(a.created_at.cmp(&b.created_at)),
+    };
+    first
+        .then(a.position.cmp(&b.position))
+        .then(a.id.cmp(&b.id))
+}
+
+/// Completion time after setting `done`, kept if it was done already
+fn completed_at(was_done: bool, at: Option<DateTimeTZ>, done: bool) -> Option<DateTimeTZ> {
+    match (was_done, done) {
+        (_, false) => None,
+        (true, true) => at.or_else(|| Some(utils::get_current_time())),
+        (false, true) => Some(utils::get_current_time()),
+    }
+}
+
+/// New positions of the Todos in manual order after moving one to the position,
+/// for only those changed
+fn move_to(mut ids: Vec<i32>, id: i32, position: i32) -> Result<HashMap<i32, i32>> {
+    let from = ids
+        .iter()
+        .position(|&x| x == id)
+        .ok_or(Error::IdNotFound { id })?;
+    if position < 1 {
+        return Err(Error::BadRequest);
+    }
+    ids.remove(from);
+    let to = (position as usize - 1).min(ids.len());
+    ids.insert(to, id);
+    let (lo, hi) = (from.min(to), from.max(to));
+    Ok((lo..=hi).map(|i| (ids[i], i as i32 + 1)).collect())
+}
+
+/// Database Todo store
+struct TodoDbRepoImp {
+    db: DbConn,
+    reads: DbConns,
+}
+
+#[async_trait]
+impl TodoRepo for TodoDbRepoImp {
+    async fn create(&self, item: Todo, audit: Option<AuditContext>) -> Result<(i32, TodoChanges)> {
+        info!(?item, "create todo");
+        let now = utils::get_current_time();
+        let txn = self.db.begin().await?;
+        // creates one at a time, or two could take the same last position
+        lock_all(&txn).await?;
+        if let Some(parent) = item.parent_id {
+            if Entity::find_by_id(parent).one(&txn).await?.is_none() {
+                return Err(Error::IdNotFound { id: parent });
+            }
+        }
+        let last: Option<i32> = Entity::find()
+            .select_only()
+            .column_as(Column::Position.max(), "position")
+            .into_tuple()
+            .one(&txn)
+            .await?
+            .flatten();
+        let res = ActiveModel {
+            value: Set(item.value),
+            done: Set(item.done),
+            due_at: Set(item.due_at),
+            priority: Set(item.priority),
+            position: Set(last.unwrap_or(0) + 1),
+            created_at: Set(now),
+            completed_at: Set(item.done.then_some(now)),
+            parent_id: Set(item.parent_id),
+            auto_complete: Set(item.auto_complete),
+            ..Default::default()
+        }
+        .insert(&txn)
+        .await?;
+        let completed = auto_complete(&txn, res.parent_id).await?;
+        let changes = TodoChanges {
+            completed: completed.iter().map(|x| x.id).collect(),
+            ..Default::default()
+        };
+        audit_change(&txn, audit, completed, Some(res.id)).await?;
+        txn.commit().await?;
+        Ok((res.id, changes))
+    }
+
+    async fn update(&self, item: TodoUpdate, audit: Option<AuditContext>) -> Result<TodoChanges> {
+        info!(?item, "update todo");
+        if item.id <= 0 {
+            return Err(Error::IdNotFound { id: item.id });
+        }
+        if item.is_empty() {
+            return Ok(TodoChanges::default());
+        }
+        let id = item.id;
+        let txn = self.db.begin().await?;
+        let current = Entity::find_by_id(id)
+            .one(&txn)
+            .await?
+            .ok_or(Error::IdNotFound { id })?;
+        let mut completed = Vec::new();
+        if !current.done && item.done == Some(true) {
+            completed.push(id);
+        }
+        let mut before = vec![current.clone()];
+        ActiveModel {
+            id: Unchanged(id),
+            value: item.value.map_or(NotSet, Set),
+            done: item.done.map_or(NotSet, Set),
+            due_at: item.due_at.map_or(NotSet, Set),
+            priority: item.priority.map_or(NotSet, Set),
+            auto_complete: item.auto_complete.map_or(NotSet, Set),
+            completed_at: item.done.map_or(NotSet, |v| {
+                Set(completed_at(current.done, current.completed_at, v))
+            }),
+            ..Default::default()
+        }
+        .update(&txn)
+        .await?;
+        if let (Some(true), true) = (item.done, item.cascade) {
+            let subtasks: Vec<Todo> = load_subtree(&txn, id)
+                .await?
+                .into_iter()
+                .skip(1)
+                .filter(|x| !x.done)
+                .collect();
+            let ids: Vec<i32> = subtasks.iter().map(|x| x.id).collect();
+            Entity::update_many()
+                .col_expr(Column::Done, Expr::value(true))
+                .col_expr(Column::CompletedAt, Expr::value(utils::get_current_time()))
+                .filter(Column::Id.is_in(ids.clone()))
+                .exec(&txn)
+                .await?;
+            completed.extend(ids);
+            before.extend(subtasks);
+        }
+        let mut done = auto_complete(&txn, Some(id)).await?;
+        done.extend(auto_complete(&txn, current.parent_id).await?);
+        completed.extend(done.iter().map(|x| x.id));
+        before.extend(done);
+        audit_change(&txn, audit, before, None).await?;
+        txn.commit().await?;
+        Ok(TodoChanges {
+            completed,
+            ..Default::default()
+        })
+    }
+
+    async fn delete(&self, ids: Vec<i32>, audit: Option<AuditContext>) -> Result<TodoChanges> {
+        info!(?ids, "delete todos");
+        let txn = self.db.begin().await?;
+        let mut parents = Vec::new();
+        let mut before = Vec::new();
+        for id in ids {
+            let subtree = load_subtree(&txn, id).await?;
+            if let Some(x) = subtree.first() {
+                parents.push(x.parent_id);
+            }
+            before.extend(subtree);
+        }
+        let mut removed: Vec<i32> = before.iter().map(|x| x.id).collect();
+        removed.sort_unstable();
+        removed.dedup();
+        Entity::delete_many()
+            .filter(Column::Id.is_in(removed.clone()))
+            .exec(&txn)
+            .await?;
+        let mut completed = Vec::new();
+        for parent in parents {
+            let done = auto_complete(&txn, parent).await?;
+            completed.extend(done.iter().map(|x| x.id));
+            before.extend(done);
+        }
+        audit_change(&txn, audit, before, None).await?;
+        txn.commit().await?;
+        Ok(TodoChanges {
+            deleted: removed,
+            completed,
+            ..Default::default()
+        })
+    }
+
+    async fn fetch(&self, id: i32) -> Result<Todo> {
+        info!(?id, "fetch todo");
+        if id <= 0 {
+            return Err(Error::IdNotFound { id });
+        }
+        Entity::find_by_id(id)
+            .one(&self.db)
+            .await?
+            .ok_or(Error::IdNotFound { id })
+    }
+
+    async fn query(&self, req: TodoQuery) -> Result<(Vec<Todo>, u64)> {
+        info!(?req, "query todos");
+        let now = utils::get_current_time();
+        let mut cur = Entity::find();
+        if let Some(v) = req.value {
+            cur = cur.filter(
+                Expr::expr(Func::lower(Expr::col(Column::Value)))
+                    .like(format!("%{}%", v.to_lowercase())),
+            );
+        }
+        if let Some(v) = req.done {
+            cur = cur.filter(Column::Done.eq(v));
+        }
+        if let Some(v) = req.overdue {
+            // no NULL comparisons, so that negation keeps those without due time
+            let overdue = Condition::all()
+                .add(Column::Done.eq(false))
+                .add(Column::DueAt.is_not_null())
+                .add(Column::DueAt.lt(now));
+            cur = cur.filter(if v { overdue } else { overdue.not() });
+        }
+        if let Some(v) = req.due_after {
+            cur = cur.filter(Column::DueAt.gte(v));
+        }
+        if let Some(v) = req.due_before {
+            cur = cur.filter(Column::DueAt.lt(v));
+        }
+        if let Some(v) = req.priority {
+            cur = cur.filter(Column::Priority.eq(v));
+        }
+        if let Some(v) = req.parent_id {
+            cur = cur.filter(Column::ParentId.eq(v));
+        }
+        let sort = req.sort.unwrap_or_default();
+        let order = match req.order.unwrap_or(sort.default_order()) {
+            SortOrder::Asc => Order::Asc,
+            SortOrder::Desc => Order::Desc,
+        };
+        cur = match sort {
+            TodoSort::Position => cur.order_by(Column::Position, order),
+            TodoSort::DueAt => cur
+                .order_by_asc(Expr::col(Column::DueAt).is_null())
+                .order_by(Column::DueAt, order),
+            TodoSort::Priority => cur.order_by(Column::Priority, order),
+            TodoSort::CreatedAt => cur.order_by(Column::CreatedAt, order),
+        };
+        let cur = cur.order_by_asc(Column::Position).order_by_asc(Column::Id);
+        let page = req.page.unwrap_or(1).max(1);
+        let size = req.size.unwrap_or(10);
+        self.reads
+            .read(|db| {
+                let cur = cur.clone();
+                Box::pin(async move {
+                    let paginator = cur.paginate(db, size);
+                    let total = paginator.num_items().await?;
+                    Ok((paginator.fetch_page(page - 1).await?, total))
+                })
+            })
+            .await
+    }
+
+    async fn reorder(
+        &self,
+        id: i32,
+        position: i32,
+        audit: Option<AuditContext>,
+    ) -> Result<TodoChanges> {
+        info!(?id, ?position, "reorder todo");
+        let txn = self.db.begin().await?;
+        let mut cur = Entity::find()
+            .select_only()
+            .column(Column::Id)
+            .order_by_asc(Column::Position)
+            .order_by_asc(Column::Id);
+        // others wait until this move is done, SQLite takes the whole database anyway
+        if txn.get_database_backend() == DbBackend::Postgres {
+            cur = cur.lock_exclusive();
+        }
+        let ids: Vec<i32> = cur.into_tuple().all(&txn).await?;
+        let positions = move_to(ids, id, position)?;
+        let before = match audit {
+            Some(_) => {
+                Entity::find()
+                    .filter(Column::Id.is_in(positions.keys().copied()))
+                    .all(&txn)
+                    .await?
+            }
+            None => Vec::new(),
+        };
+        let mut moved = Vec::new();
+        for (id, position) in positions {
+            Entity::update_many()
+                .col_expr(Column::Position, Expr::value(position))
+                .filter(Column::Id.eq(id))
+                .exec(&txn)
+                .await?;
+            moved.push(id);
+        }
+        audit_change(&txn, audit, before, None).await?;
+        txn.commit().await?;
+        moved.sort_unstable();
+        Ok(TodoChanges {
+            moved,
+            ..Default::default()
+        })
+    }
+
+    async fn subtree(&self, id: i32) -> Result<Vec<Todo>> {
+        info!(?id, "fetch todo subtree");
+        let res = self
+            .reads
+            .read(|db| Box::pin(async move { load_subtree(db, id).await }))
+            .await?;
+        if res.is_empty() {
+            return Err(Error::IdNotFound { id });
+        }
+        Ok(res)
+    }
+
+    async fn reparent(
+        &self,
+        id: i32,
+        parent_id: Option<i32>,
+        audit: Option<AuditContext>,
+    ) -> Result<TodoChanges> {
+        info!(?id, ?parent_id, "move todo subtree");
+        let txn = self.db.begin().await?;
+        // moves one at a time, or two crossing ones could both pass the cycle check
+        lock_all(&txn).await?;
+        let subtree = load_subtree(&txn, id).await?;
+        let Some(current) = subtree.first() else {
+            return Err(Error::IdNotFound { id });
+        };
+        if let Some(parent) = parent_id {
+            if Entity::find_by_id(parent).one(&txn).await?.is_none() {
+                return Err(Error::IdNotFound { id: parent });
+            }
+            if subtree.iter().any(|x| x.id == parent) {
+                return Err(Error::TodoCycle {
+                    id,
+                    parent_id: parent,
+                });
+            }
+        }
+        if current.parent_id == parent_id {
+            return Ok(TodoChanges::default());
+        }
+        ActiveModel {
+            id: Unchanged(id),
+            parent_id: Set(parent_id),
+            ..Default::default()
+        }
+        .update(&txn)
+        .await?;
+        let mut before = vec![current.clone()];
+        before.extend(auto_complete(&txn, current.parent_id).await?);
+        before.extend(auto_complete(&txn, parent_id).await?);
+        let completed = before[1..].iter().map(|x| x.id).collect();
+        audit_change(&txn, audit, before, None).await?;
+        txn.commit().await?;
+        Ok(TodoChanges {
+            completed,
+            reparented: vec![id],
+            ..Default::default()
+        })
+    }
+}
+
+/// Lock all Todos until the transaction ends, SQLite takes the whole database anyway
+async fn lock_all(txn: &DatabaseTransaction) -> Result<()> {
+    if txn.get_database_backend() == DbBackend::Postgres {
+        Entity::find()
+            .select_only()
+            .column(Column::Id)
+            .order_by_asc(Column::Id)
+            .lock_exclusive()
+            .into_tuple::<i32>()
+            .all(txn)
+            .await?;
+    }
+    Ok(())
+}
+
+/// The Todo and its subtasks at any depth, parents first, or none if it is missing
+async fn load_subtree<C: ConnectionTrait>(db: &C, id: i32) -> Result<Vec<Todo>> {
+    let mut res: Vec<Todo> = Entity::find_by_id(id).one(db).await?.into_iter().collect();
+    // a cycle, if ever, must not keep it loading
+    let mut seen: HashSet<i32> = res.iter().map(|x| x.id).collect();
+    let mut level = res.len();
+    while level > 0 {
+        let parents: Vec<i32> = res[res.len() - level..].iter().map(|x| x.id).collect();
+        let children: Vec<Todo> = Entity::find()
+            .filter(Column::ParentId.is_in(parents))
+            .order_by_asc(Column::Position)
+            .all(db)
+            .await?
+            .into_iter()
+            .filter(|x| seen.insert(x.id))
+            .collect();
+        level = children.len();
+        res.extend(children);
+    }
+    Ok(res)
+}
+
+/// Complete the Todo and then its ancestors, each if set to auto-complete
+/// and all its subtasks are done, returning those completed as they were before
+async fn auto_complete<C: ConnectionTrait>(db: &C, mut id: Option<i32>) -> Result<Vec<Todo>> {
+    let now = utils::get_current_time();
+    let mut completed = Vec::new();
+    while let Some(x) = id {
+        let Some(x) = Entity::find_by_id(x).one(db).await? else {
+            break;
+        };
+        if x.done || !x.auto_complete {
+            break;
+        }
+        let children = Entity::find().filter(Column::ParentId.eq(x.id));
+        let total = children.clone().count(db).await?;
+        let undone = children.filter(Column::Done.eq(false)).count(db).await?;
+        if total == 0 || undone > 0 {
+            break;
+        }
+        ActiveModel {
+            id: Unchanged(x.id),
+            done: Set(true),
+            completed_at: Set(Some(now)),
+            ..Default::default()
+        }
+        .update(db)
+        .await?;
+        id = x.parent_id;
+        completed.push(x);
+    }
+    Ok(completed)
+}
+
+/// Audit the change of the Todos, given as they were before it, and of the one created if any
+async fn audit_change<C: ConnectionTrait>(
+    db: &C,
+    audit: Option<AuditContext>,
+    mut before: Vec<Todo>,
+    created: Option<i32>,
+) -> Result<()> {
+    let Some(audit) = audit else {
+        return Ok(());
+    };
+    // the first snapshot of each is the one before the change
+    before.sort_by_key(|x| x.id);
+    before.dedup_by_key(|x| x.id);
+    let mut ids: Vec<i32> = before.iter().map(|x| x.id).chain(created).collect();
+    if ids.is_empty() {
+        return Ok(());
+    }
+    ids.sort_unstable();
+    let after = Entity::find()
+        .filter(Column::Id.is_in(ids.clone()))
+        .order_by_asc(Column::Id)
+        .all(db)
+        .await?;
+    audit::append(db, audit.change(Resource::Todo, ids, &before, &after)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn move_todo() {
+        let moved = move_to(vec![1, 2, 3, 4, 5], 4, 2).unwrap();
+        assert_eq!(moved, [(4, 2), (2, 3), (3, 4)].into_iter().collect());
+
+        let moved = move_to(vec![1, 2, 3, 4, 5], 1, 100).unwrap();
+        assert_eq!(
+            moved,
+            [(2, 1), (3, 2), (4, 3), (5, 4), (1, 5)]
+                .into_iter()
+                .collect()
+        );
+
+        assert!(move_to(vec![1, 2], 1, 0).is_err());
+        assert!(move_to(vec![1, 2], 3, 1).is_err());
+    }
+
+    /// Database store on a fresh in-memory SQLite
+    async fn db_store() -> TodoDbRepoImp {
+        use migration::{Migrator, MigratorTrait};
+
+        use crate::infrastructure::persistence::conn::connect;
+
+        let db = connect("sqlite::memory:").unwrap();
+        Migrator::up(&db, None).await.unwrap();
+        TodoDbRepoImp {
+            db: db.clone(),
+            reads: DbConns::setup(db),
+        }
+    }
+
+    async fn create(store: &TodoDbRepoImp, item: serde_json::Value) -> i32 {
+        let item = serde_json::from_value(item).unwrap();
+        store.create(item, None).await.unwrap().0
+    }
+
+    async fn values(store: &TodoDbRepoImp, query: serde_json::Value) -> (Vec<String>, u64) {
+        let (list, total) = store
+            .query(serde_json::from_value(query).unwrap())
+            .await
+            .unwrap();
+        (list.into_iter().map(|x| x.value).collect(), total)
+    }
+
+    #[tokio::test]
+    async fn db_store_pages_by_sort_key() {
+        let store = db_store().await;
+        for (value, priority, due_at) in [
+            ("a", "low", Some("2024-07-03 00:00:00")),
+            ("b", "urgent", None),
+            ("c", "high", Some("2024-07-01 00:00:00")),
+        ] {
+            let item =
+                json!({"value": value, "done": false, "priority": priority, "due_at": due_at});
+            create(&store, item).await;
+        }
+
+        let query = json!({"sort": "priority", "size": 2});
+        assert_eq!(
+            values(&store, query).await,
+            (vec!["b".into(), "c".into()], 3)
+        );
+        let query = json!({"sort": "priority", "size": 2, "page": 2});
+        assert_eq!(values(&store, query).await, (vec!["a".into()], 3));
+        // those without due time last either way
+        let (list, _) = values(&store, json!({"sort": "due_at"})).await;
+        assert_eq!(list, ["c", "a", "b"]);
+        let (list, _) = values(&store, json!({"sort": "due_at", "order": "desc"})).await;
+        assert_eq!(list, ["a", "c", "b"]);
+    }
+
+    #[tokio::test]
+    async fn db_store_reorders() {
+        let store = db_store().await;
+        for value in ["a", "b", "c", "d"] {
+            create(&store, json!({"value": value, "done": false})).await;
+        }
+        let changes = store.reorder(4, 2, None).await.unwrap();
+        assert_eq!(changes.moved, [2, 3, 4]);
+        let (list, _) = values(&store, json!({})).await;
+        assert_eq!(list, ["a", "d", "b", "c"]);
+        assert!(store.reorder(5, 1, None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn db_store_cascades_and_auto_completes() {
+        let store = db_store().await;
+        let trip = create(
+            &store,
+            json!({"value": "trip", "done": false, "auto_complete": true}),
+        )
+        .await;
+        let pack = create(
+            &store,
+            json!({"value": "pack", "done": false, "parent_id": trip}),
+        )
+        .await;
+        let socks = create(
+            &store,
+            json!({"value": "socks", "done": false, "parent_id": pack}),
+        )
+        .await;
+        let book = create(
+            &store,
+            json!({"value": "book", "done": false, "parent_id": trip}),
+        )
+        .await;
+
+        let done = |id: i32, cascade: bool| {
+            serde_json::from_value(json!({"id": id, "done": true, "cascade": cascade})).unwrap()
+        };
+        let changes = store.update(done(pack, true), None).await.unwrap();
+        assert_eq!(changes.completed, [pack, socks]);
+        assert!(store.fetch(socks).await.unwrap().completed_at.is_some());
+        assert!(!store.fetch(trip).await.unwrap().done);
+
+        let changes = store.update(done(book, false), None).await.unwrap();
+        assert_eq!(changes.completed, [book, trip]);
+        assert!(store.fetch(trip).await.unwrap().done);
+
+        let changes = store.delete(vec![pack, socks], None).await.unwrap();
+        assert_eq!(changes.deleted, [pack, socks]);
+        assert_eq!(store.subtree(trip).await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn stores_keep_or_clear_due_at() {
+        let (memory, _) = filled_store(0).await;
+        for store in [&memory as &dyn TodoRepo, &db_store().await] {
+            let item = json!({"value": "a", "done": false, "due_at": "2024-07-10 18:00:00"});
+            let (id, _) = store
+                .create(serde_json::from_value(item).unwrap(), None)
+                .await
+                .unwrap();
+            let update = |v| serde_json::from_value(v).unwrap();
+            store
+                .update(update(json!({"id": id, "value": "b"})), None)
+                .await
+                .unwrap();
+            assert!(store.fetch(id).await.unwrap().due_at.is_some());
+            store
+                .update(update(json!({"id": id, "due_at": null})), None)
+                .await
+                .unwrap();
+            assert_eq!(store.fetch(id).await.unwrap().due_at, None);
+        }
+    }
+
+    #[tokio::test]
+    async fn db_store_rejects_cycles() {
+        let store = db_store().await;
+        let a = create(&store, json!({"value": "a", "done": false})).await;
+        let b = create(&store, json!({"value": "b", "done": false, "parent_id": a})).await;
+        let c = create(&store, json!({"value": "c", "done": false, "parent_id": b})).await;
+
+        for parent in [a, c] {
+            let res = store.reparent(a, Some(parent), None).await;
+            assert!(matches!(res, Err(Error::TodoCycle { .. })));
+        }
+        assert!(matches!(
+            store.reparent(a, Some(100), None).await,
+            Err(Error::IdNotFound { id: 100 })
+        ));
+        let changes = store.reparent(c, None, None).await.unwrap();
+        assert_eq!(changes.reparented, [c]);
+        let changes = store.reparent(c, None, None).await.unwrap();
+        assert_eq!(changes, TodoChanges::default());
+        store.reparent(a, Some(c), None).await.unwrap();
+        let ids: Vec<i32> = store
+            .subtree(c)
+            .await
+            .unwrap()
+            .iter()
+            .map(|x| x.id)
+            .collect();
+        assert_eq!(ids, [c, a, b]);
+    }
+
+    /// Memory store of `n` Todos, with a Vec of the same as it used to be
+    async fn filled_store(n: i32) -> (TodoRepoImp, Vec<Todo>) {
+        use crate::infrastructure::persistence::conn::connect;
+
+        let store = TodoRepoImp {
+            data: RwLock::default(),
+            seq: AtomicI32::new(1),
+            journal: None,
+            db: connect("sqlite::memory:").unwrap(),
+        };
+        // filled alike, for similar memory layout
+        let mut list: Vec<Todo> = Vec::new();
+        for i in 0..n {
+            let item: Todo = serde_json::from_value(serde_json::json!({
+                "value": format!("Review the Quarterly Report, section {i}"),
+                "done": i % 2 == 0,
+            }))
+            .unwrap();
+            let (id, _) = store.create(item.clone(), None).await.unwrap();
+            list.push(store.fetch(id).await.unwrap());
+        }
+        (store, list)
+    }
+
+    #[tokio::test]
+    async fn memory_store_indexes_agree_with_scan() {
+        const N: i32 = 2_000;
+        let (store, mut list) = filled_store(N).await;
+        let ids: Vec<i32> = (1..=N).step_by(100).collect();
+        for &id in &ids {
+            let x = list.iter().find(|x| x.id == id).unwrap();
+            assert_eq!(store.fetch(id).await.unwrap().value, x.value);
+        }
+
+        // found by the lowercased value and done, in manual order
+        let ids_of = |v: Vec<Todo>| v.into_iter().map(|x| x.id).collect::<Vec<_>>();
+        let query = || {
+            serde_json::from_value(json!({"value": "SECTION 1", "done": true, "size": N})).unwrap()
+        };
+        let scan = |list: &[Todo]| {
+            let mut v: Vec<Todo> = list
+                .iter()
+                .filter(|x| x.done && x.value.contains("section 1"))
+                .cloned()
+                .collect();
+            v.sort_by_key(|x| (x.position, x.id));
+            v
+        };
+        let (found, total) = store.query(query()).await.unwrap();
+        assert_eq!(total as usize, found.len());
+        assert_eq!(ids_of(found), ids_of(scan(&list)));
+
+        store.reorder(11, 1, None).await.unwrap();
+        store.delete(ids.clone(), None).await.unwrap();
+        for &id in &ids {
+            assert!(store.fetch(id).await.is_err());
+        }
+        list.retain(|x| !ids.contains(&x.id));
+        for x in &mut list {
+            x.position = store.fetch(x.id).await.unwrap().position;
+        }
+        let (found, _) = store.query(query()).await.unwrap();
+        assert_eq!(ids_of(found), ids_of(scan(&list)));
+        assert_eq!(ids_of(scan(&list))[0], 11);
+    }
+
+    /// Indexed memory store against linear scans over a Vec, as it used to be:
+    /// `cargo test --release bench_memory_store -- --ignored --nocapture`
+    #[tokio::test]
+    #[ignore]
+    async fn bench_memory_store() {
+        use std::time::Instant;
+
+        const N: i32 = 100_000;
+        let (store, mut list) = filled_store(N).await;
+        let ids: Vec<i32> = (1..=N).step_by(100).collect();
+
+        let t = Instant::now();
+        for &id in &ids {
+            store.fetch(id).await.unwrap();
+        }
+        let indexed = t.elapsed();
+        let t = Instant::now();
+        for &id in &ids {
+            list.iter().find(|x| x.id == id).unwrap();
+        }
+        println!("fetch {}: {indexed:?}, scan {:?}", ids.len(), t.elapsed());
+
+        let query = || serde_json::from_value(serde_json::json!({"value": "SECTION 99"})).unwrap();
+        let t = Instant::now();
+        for _ in 0..10 {
+            store.query(query()).await.unwrap();
+        }
+        let indexed = t.elapsed();
+        let t = Instant::now();
+        for _ in 0..10 {
+            let v = "SECTION 99".to_lowercase();
+            let mut matched: Vec<_> = list
+                .iter()
+                .filter(|x| x.value.to_lowercase().contains(&v))
+                .collect();
+            matched.sort_by_key(|x| (x.position, x.id));
+        }
+        println!("query 10 times: {indexed:?}, scan {:?}", t.elapsed());
+
+        // in place, not to time a copy of the list
+        let t = Instant::now();
+        list.retain(|x| !ids.contains(&x.id));
+        let scan = t.elapsed();
+        let t = Instant::now();
+        store.delete(ids.clone(), None).await.unwrap();
+        println!("delete {}: {:?}, scan {scan:?}", ids.len(), t.elapsed());
+    }
 }
@@ -0,0 +1,365 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::{fs::File, io::AsyncWriteExt, sync::Mutex};
+
+use crate::{
+    app::{log::*, utils},
+    entity::{DateTimeTZ, Todo, TodoPriority},
+    repository::Result,
+};
+
+/// Snapshot file of the in-memory Todo store, with a journal of the changes made since
+pub(super) struct TodoJournal {
+    snapshot: PathBuf,
+    journal: Mutex<JournalFile>,
+    dirty: AtomicBool,
+}
+
+/// Journal file opened for appending, with the length of its intact entries
+struct JournalFile {
+    file: File,
+    len: u64,
+}
+
+/// Todos restored on startup
+pub(super) struct Restored {
+    pub todos: Vec<Todo>,
+    /// next Todo id
+    pub seq: i32,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct Snapshot {
+    seq: i32,
+    todos: Vec<TodoRecord>,
+}
+
+/// Change of the store, idempotent so that replaying it over a later snapshot is harmless
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+enum Entry {
+    /// Todos as they are after being created or changed
+    Put {
+        todos: Vec<TodoRecord>,
+    },
+    Delete {
+        ids: Vec<i32>,
+    },
+}
+
+/// All fields of a Todo, including those clients do not set
+#[derive(Serialize, Deserialize)]
+struct TodoRecord {
+    id: i32,
+    value: String,
+    done: bool,
+    #[serde(default, with = "utils::mtime::option")]
+    due_at: Option<DateTimeTZ>,
+    priority: TodoPriority,
+    position: i32,
+    #[serde(with = "utils::mtime")]
+    created_at: DateTimeTZ,
+    #[serde(default, with = "utils::mtime::option")]
+    completed_at: Option<DateTimeTZ>,
+    #[serde(default)]
+    parent_id: Option<i32>,
+    #[serde(default)]
+    auto_complete: bool,
+}
+
+impl From<&Todo> for TodoRecord {
+    fn from(x: &Todo) -> Self {
+        Self {
+            id: x.id,
+            value: x.value.clone(),
+            done: x.done,
+            due_at: x.due_at,
+            priority: x.priority,
+            position: x.position,
+            created_at: x.created_at,
+            completed_at: x.completed_at,
+            parent_id: x.parent_id,
+            auto_complete: x.auto_complete,
+        }
+    }
+}
+
+impl From<TodoRecord> for Todo {
+    fn from(x: TodoRecord) -> Self {
+        Self {
+            id: x.id,
+            value: x.value,
+            done: x.done,
+            due_at: x.due_at,
+            priority: x.priority,
+            position: x.position,
+            created_at: x.created_at,
+            completed_at: x.completed_at,
+            parent_id: x.parent_id,
+            auto_complete: x.auto_complete,
+        }
+    }
+}
+
+impl TodoJournal {
+    /// Restore the Todos from the snapshot and the journal, then keep journaling to it
+    pub(super) fn open(snapshot: &str) -> Result<(Self, Restored)> {
+        let snapshot = PathBuf::from(snapshot);
+        let journal = journal_path(&snapshot);
+        let saved = match fs::read(&snapshot) {
+            Ok(data) => serde_json::from_slice(&data)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Snapshot::default(),
+            Err(e) => return Err(e.into()),
+        };
+        let changes = match fs::read_to_string(&journal) {
+            Ok(data) => data,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let (restored, len) = replay(saved, &changes)?;
+        info!(path = ?snapshot, n = restored.todos.len(), seq = restored.seq, "todos restored");
+
+        if let Some(dir) = snapshot.parent().filter(|x| !x.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&journal)?;
+        if len < changes.len() {
+            // drop the torn entry, or the next one would be appended to it
+            file.set_len(len as u64)?;
+        }
+        Ok((
+            Self {
+                snapshot,
+                journal: Mutex::new(JournalFile {
+                    file: File::from_std(file),
+                    len: len as u64,
+                }),
+                dirty: AtomicBool::new(len > 0),
+            },
+            restored,
+        ))
+    }
+
+    /// Journal the Todos as they are to be and the removed ones, in one write
+    pub(super) async fn record(&self, put: &[&Todo], deleted: &[i32]) -> Result<()> {
+        let mut entries = Vec::with_capacity(2);
+        if !put.is_empty() {
+            let todos = put.iter().map(|&x| x.into()).collect();
+            entries.push(Entry::Put { todos });
+        }
+        if !deleted.is_empty() {
+            entries.push(Entry::Delete {
+                ids: deleted.to_vec(),
+            });
+        }
+        self.append(&entries).await
+    }
+
+    /// Whether there are changes since the last snapshot
+    pub(super) fn is_dirty(&self) -> bool {
+        self.dirty.load(Ordering::Relaxed)
+    }
+
+    /// Replace the snapshot atomically and empty the journal, with no changes made meanwhile
+    pub(super) async fn save(&self, todos: &[&Todo], seq: i32) -> Result<()> {
+        let mut journal = self.journal.lock().await;
+        let saved = Snapshot {
+            seq,
+            todos: todos.iter().map(|&x| x.into()).collect(),
+        };
+        let data = serde_json::to_vec(&saved)?;
+        let tmp = self.snapshot.with_extension("tmp");
+        let mut file = File::create(&tmp).await?;
+        file.write_all(&data).await?;
+        file.sync_all().await?;
+        tokio::fs::rename(&tmp, &self.snapshot).await?;
+        // a crash before this replays the journal over the new snapshot, to the same result
+        journal.file.set_len(0).await?;
+        journal.len = 0;
+        self.dirty.store(false, Ordering::Relaxed);
+        info!(path = ?self.snapshot, n = todos.len(), seq, "todos snapshot saved");
+        Ok(())
+    }
+
+    async fn append(&self, entries: &[Entry]) -> Result<()> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+        let mut lines = Vec::new();
+        for entry in entries {
+            serde_json::to_writer(&mut lines, entry)?;
+            lines.push(b'\n');
+        }
+        let mut journal = self.journal.lock().await;
+        let written = async {
+            journal.file.write_all(&lines).await?;
+            journal.file.flush().await
+        }
+        .await;
+        if let Err(e) = written {
+            // not to leave a part of the entries for the next ones to follow
+            let len = journal.len;
+            if let Err(e) = journal.file.set_len(len).await {
+                error!(%e, len, "failed to truncate todo journal");
+            }
+            return Err(e.into());
+        }
+        journal.len += lines.len() as u64;
+        self.dirty.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+fn journal_path(snapshot: &Path) -> PathBuf {
+    let mut name = snapshot.as_os_str().to_owned();
+    name.push(".journal");
+    name.into()
+}
+
+/// Todos of the snapshot after the journaled changes, in creation order,
+/// with the length of the journal up to the torn entry if any
+fn replay(saved: Snapshot, changes: &str) -> Result<(Restored, usize)> {
+    let mut todos: Vec<Todo> = saved.todos.into_iter().map(Todo::from).collect();
+    // also past ids deleted since, never to be given again
+    let mut seq = todos
+        .iter()
+        .map(|x| x.id + 1)
+        .fold(saved.seq.max(1), i32::max);
+    let lines: Vec<&str> = changes.split_inclusive('\n').collect();
+    let mut len = 0;
+    for (i, line) in lines.iter().enumerate() {
+        let entry = match serde_json::from_str(line) {
+            // entries are written with the newline, one without it is not complete
+            Ok(_) if !line.ends_with('\n') => {
+                warn!("unterminated last todo journal entry dropped");
+                break;
+            }
+            Ok(v) => v,
+            Err(_) if line.trim().is_empty() => {
+                len += line.len();
+                continue;
+            }
+            // torn by a crash while being written
+            Err(e) if i + 1 == lines.len() => {
+                warn!(%e, "last todo journal entry dropped");
+                break;
+            }
+            Err(e) => return Err(e.into()),
+        };
+        len += line.len();
+        match entry {
+            Entry::Put { todos: changed } => {
+                for x in changed {
+                    seq = seq.max(x.id + 1);
+                    match todos.iter_mut().find(|t| t.id == x.id) {
+                        Some(t) => *t = x.into(),
+                        None => todos.push(x.into()),
+                    }
+                }
+            }
+            Entry::Delete { ids } => todos.retain(|x| !ids.contains(&x.id)),
+        }
+    }
+    todos.sort_unstable_by_key(|x| x.id);
+    Ok((Restored { todos, seq }, len))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    fn record(id: i32, value: &str) -> TodoRecord {
+        TodoRecord {
+            id,
+            value: value.to_owned(),
+            done: false,
+            due_at: None,
+            priority: TodoPriority::Medium,
+            position: id,
+            created_at: utils::get_current_time(),
+            completed_at: None,
+            parent_id: None,
+            auto_complete: false,
+        }
+    }
+
+    #[test]
+    fn replay_journal_over_snapshot() {
+        let line = |entry: Entry| serde_json::to_string(&entry).unwrap();
+        let saved = Snapshot {
+            seq: 3,
+            todos: vec![record(1, "a"), record(2, "b")],
+        };
+        let changes = [
+            line(Entry::Put {
+                todos: vec![record(4, "d")],
+            }),
+            line(Entry::Put {
+                todos: vec![record(1, "a2")],
+            }),
+            line(Entry::Delete { ids: vec![2] }),
+            line(Entry::Put {
+                todos: vec![record(6, "f")],
+            }),
+            line(Entry::Delete { ids: vec![6] }),
+            r#"{"op":"put","todos":[{"id":5"#.to_owned(),
+        ]
+        .join("\n");
+
+        let (restored, len) = replay(saved, &changes).unwrap();
+        let values: Vec<_> = restored.todos.iter().map(|x| x.value.as_str()).collect();
+        assert_eq!(values, ["a2", "d"]);
+        assert_eq!(restored.seq, 7);
+        assert_eq!(len, changes.rfind('\n').unwrap() + 1);
+
+        let torn_inside = format!("{{\n{changes}");
+        assert!(replay(Snapshot::default(), &torn_inside).is_err());
+    }
+
+    #[tokio::test]
+    async fn write_again_after_torn_tail() {
+        let dir = PathBuf::from("target/test-todo-journal").join(utils::get_uuid_str());
+        let snapshot = dir.join("todos.json");
+        let path = snapshot.to_str().unwrap();
+        let values = |restored: Restored| -> Vec<String> {
+            restored.todos.into_iter().map(|x| x.value).collect()
+        };
+
+        let (journal, _) = TodoJournal::open(path).unwrap();
+        journal
+            .record(&[&record(1, "a").into()], &[])
+            .await
+            .unwrap();
+        drop(journal);
+        // crash while writing the next entry
+        fs::OpenOptions::new()
+            .append(true)
+            .open(journal_path(&snapshot))
+            .unwrap()
+            .write_all(br#"{"op":"put","todos":[{"id":2"#)
+            .unwrap();
+
+        let (journal, restored) = TodoJournal::open(path).unwrap();
+        assert_eq!(values(restored), ["a"]);
+        journal
+            .record(&[&record(2, "b").into()], &[])
+            .await
+            .unwrap();
+        drop(journal);
+
+        let (_, restored) = TodoJournal::open(path).unwrap();
+        assert_eq!(restored.seq, 3);
+        assert_eq!(values(restored), ["a", "b"]);
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,93 @@
+use std::{collections::HashMap, sync::Arc};
+
+use async_trait::async_trait;
+use sea_orm::*;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    app::{log::*, utils},
+    entity::{
+        user::{ActiveModel, Column, Entity},
+        Post, User, UserRole, UserSummary,
+    },
+    repository::{Error, Result, UserNew, UserRepo},
+};
+
+pub type UserStore = Arc<dyn UserRepo + Send + Sync>;
+
+pub(super) fn get_user_store(db: &DbConn) -> UserStore {
+    Arc::new(UserRepoImp { db: db.clone() })
+}
+
+/// Database User store
+struct UserRepoImp {
+    db: DbConn,
+}
+
+#[async_trait]
+impl UserRepo for UserRepoImp {
+    async fn create(&self, item: UserNew, by_admin: bool) -> Result<(User, String)> {
+        info!(?item, by_admin, "create user");
+        let txn = self.db.begin().await?;
+        if item.role == UserRole::Admin && !by_admin {
+            // one at a time, or two could both find no Users, SQLite takes the whole database anyway
+            if txn.get_database_backend() == DbBackend::Postgres {
+                txn.execute_unprepared("LOCK TABLE users IN EXCLUSIVE MODE")
+                    .await?;
+            }
+            if Entity::find().one(&txn).await?.is_some() {
+                return Err(Error::Forbidden);
+            }
+        }
+        let token = utils::get_uuid_str();
+        let res = ActiveModel {
+            name: Set(item.name),
+            role: Set(item.role),
+            token_hash: Set(hash_token(&token)),
+            ..Default::default()
+        }
+        .insert(&txn)
+        .await?;
+        txn.commit().await?;
+        Ok((res, token))
+    }
+
+    async fn fetch(&self, id: i32) -> Result<User> {
+        info!(?id, "fetch user");
+        Entity::find_by_id(id)
+            .one(&self.db)
+            .await?
+            .ok_or(Error::IdNotFound { id })
+    }
+
+    async fn authenticate(&self, token: &str) -> Result<Option<User>> {
+        Ok(Entity::find()
+            .filter(Column::TokenHash.eq(hash_token(token)))
+            .one(&self.db)
+            .await?)
+    }
+}
+
+/// Tokens are kept hashed, not to be usable if the database leaks
+fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+/// Fill in author summaries of the Posts
+pub(super) async fn load_authors<C: ConnectionTrait>(db: &C, posts: &mut [Post]) -> Result<()> {
+    let ids: Vec<i32> = posts.iter().filter_map(|x| x.author_id).collect();
+    if ids.is_empty() {
+        return Ok(());
+    }
+    let users: HashMap<i32, UserSummary> = Entity::find()
+        .filter(Column::Id.is_in(ids))
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|x| (x.id, x.into()))
+        .collect();
+    for p in posts {
+        p.author = p.author_id.and_then(|id| users.get(&id).cloned());
+    }
+    Ok(())
+}
@@ -0,0 +1,236 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    mem,
+    sync::{Arc, Mutex},
+};
+
+use sea_orm::sea_query::Expr;
+use sea_orm::*;
+use tokio::time::{Duration, Instant, MissedTickBehavior};
+
+use crate::{
+    app::log::*,
+    entity::post::{Column, Entity},
+    repository::Result,
+};
+
+use super::config;
+
+const DEFAULT_FLUSH_INTERVAL: Duration = Duration::from_secs(10);
+
+pub type ViewCounterHandle = Arc<ViewCounter>;
+
+/// Called with the views written to db, by Post id
+pub type FlushHook = Box<dyn Fn(&HashMap<i32, i32>) + Send + Sync>;
+
+/// Buffered Post view counter, flushing aggregated increments to db in batches
+pub struct ViewCounter {
+    db: DbConn,
+    pending: Mutex<HashMap<i32, i32>>,
+    seen: Mutex<HashMap<(i32, String), Instant>>,
+    hooks: Mutex<Vec<FlushHook>>,
+}
+
+impl ViewCounter {
+    pub(super) fn setup(db: &DbConn) -> ViewCounterHandle {
+        let myself = Arc::new(Self {
+            db: db.clone(),
+            pending: Mutex::default(),
+            seen: Mutex::default(),
+            hooks: Mutex::default(),
+        });
+        tokio::spawn(myself.clone().flush_periodically());
+        myself
+    }
+
+    /// Count a view of the Post, returning the number of views not flushed yet
+    pub fn record(&self, id: i32, client: Option<&str>) -> Result<i32> {
+        self.record_within(id, client, dedup_window())
+    }
+
+    fn record_within(&self, id: i32, client: Option<&str>, window: Duration) -> Result<i32> {
+        if let (Some(client), false) = (client, window.is_zero()) {
+            let now = Instant::now();
+            let mut seen = self.seen.lock()?;
+            match seen.get_mut(&(id, client.to_owned())) {
+                Some(ts) if now - *ts < window => {
+                    drop(seen);
+                    return self.pending(id);
+                }
+                Some(ts) => *ts = now,
+                None => {
+                    seen.insert((id, client.to_owned()), now);
+                }
+            }
+        }
+        let mut pending = self.pending.lock()?;
+        let n = pending.entry(id).or_default();
+        *n += 1;
+        Ok(*n)
+    }
+
+    pub fn pending(&self, id: i32) -> Result<i32> {
+        Ok(self.pending.lock()?.get(&id).copied().unwrap_or_default())
+    }
+
+    /// Run the hook after every successful flush
+    pub fn on_flush(&self, hook: FlushHook) -> Result<()> {
+        self.hooks.lock()?.push(hook);
+        Ok(())
+    }
+
+    /// Write all buffered views to db, keeping them buffered on failure
+    pub async fn flush(&self) -> Result<()> {
+        let pending = mem::take(&mut *self.pending.lock()?);
+        if pending.is_empty() {
+            return Ok(());
+        }
+        // posts with the same increment share one statement
+        let mut batches: BTreeMap<i32, Vec<i32>> = BTreeMap::new();
+        for (&id, &n) in &pending {
+            batches.entry(n).or_default().push(id);
+        }
+        let ret = self.write_batches(batches).await;
+        match ret {
+            Ok(()) => {
+                debug!(posts = pending.len(), "post views flushed");
+                for hook in self.hooks.lock()?.iter() {
+                    hook(&pending);
+                }
+            }
+            Err(_) => {
+                let mut cur = self.pending.lock()?;
+                for (id, n) in pending {
+                    *cur.entry(id).or_default() += n;
+                }
+            }
+        }
+        ret
+    }
+
+    async fn write_batches(&self, batches: BTreeMap<i32, Vec<i32>>) -> Result<()> {
+        let txn = self.db.begin().await?;
+        for (n, ids) in batches {
+            Entity::update_many()
+                .col_expr(Column::Views, Expr::col(Column::Views).add(n))
+                .filter(Column::Id.is_in(ids))
+                .exec(&txn)
+                .await?;
+        }
+        txn.commit().await?;
+        Ok(())
+    }
+
+    fn prune_seen(&self) -> Result<()> {
+        let window = dedup_window();
+        let now = Instant::now();
+        self.seen.lock()?.retain(|_, ts| now - *ts < window);
+        Ok(())
+    }
+
+    async fn flush_periodically(self: Arc<Self>) {
+        let mut inter = flush_interval();
+        let mut ticker = tokio::time::interval_at(Instant::now() + inter, inter);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = self.flush().await {
+                error!(%e, "flush post views failed");
+            }
+            self.prune_seen().ok();
+            // pick up config changes
+            let cur = flush_interval();
+            if cur != inter {
+                inter = cur;
+                ticker = tokio::time::interval_at(Instant::now() + inter, inter);
+                ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            }
+        }
+    }
+}
+
+fn flush_interval() -> Duration {
+    config::peek_config()
+        .ok()
+        .and_then(|c| c.views.flush_interval_secs)
+        .filter(|&v| v > 0)
+        .map_or(DEFAULT_FLUSH_INTERVAL, Duration::from_secs)
+}
+
+fn dedup_window() -> Duration {
+    config::peek_config().map_or(Duration::ZERO, |c| {
+        Duration::from_secs(c.views.dedup_window_secs)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicI32, Ordering};
+
+    use migration::{Migrator, MigratorTrait};
+
+    use super::*;
+    use crate::{entity::post::ActiveModel, infrastructure::persistence::conn::connect};
+
+    fn counter(db: DbConn) -> ViewCounter {
+        ViewCounter {
+            db,
+            pending: Mutex::default(),
+            seen: Mutex::default(),
+            hooks: Mutex::default(),
+        }
+    }
+
+    #[test]
+    fn count_client_once_within_window() {
+        let views = counter(DbConn::Disconnected);
+        let window = Duration::from_secs(60);
+        assert_eq!(views.record_within(1, Some("10.0.0.1"), window).unwrap(), 1);
+        assert_eq!(views.record_within(1, Some("10.0.0.1"), window).unwrap(), 1);
+        assert_eq!(views.record_within(1, Some("10.0.0.2"), window).unwrap(), 2);
+        assert_eq!(views.record_within(1, None, window).unwrap(), 3);
+        assert_eq!(views.record_within(2, Some("10.0.0.1"), window).unwrap(), 1);
+        // no window, every view counts
+        assert_eq!(
+            views
+                .record_within(1, Some("10.0.0.1"), Duration::ZERO)
+                .unwrap(),
+            4
+        );
+    }
+
+    #[tokio::test]
+    async fn flush_or_keep_buffered() {
+        let db = connect("sqlite::memory:").unwrap();
+        let views = counter(db.clone());
+        let flushed = Arc::new(AtomicI32::new(0));
+        let seen = flushed.clone();
+        views
+            .on_flush(Box::new(move |x| {
+                seen.fetch_add(x.values().sum(), Ordering::SeqCst);
+            }))
+            .unwrap();
+        for _ in 0..3 {
+            views.record_within(1, None, Duration::ZERO).unwrap();
+        }
+        // no table yet
+        assert!(views.flush().await.is_err());
+        assert_eq!(views.pending(1).unwrap(), 3);
+        assert_eq!(flushed.load(Ordering::SeqCst), 0);
+
+        Migrator::up(&db, None).await.unwrap();
+        let post = ActiveModel {
+            title: Set("t".to_owned()),
+            content: Set("c".to_owned()),
+            ..Default::default()
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+        views.flush().await.unwrap();
+        assert_eq!(views.pending(post.id).unwrap(), 0);
+        assert_eq!(flushed.load(Ordering::SeqCst), 3);
+        let post = Entity::find_by_id(post.id).one(&db).await.unwrap().unwrap();
+        assert_eq!(post.views, 3);
+    }
+}
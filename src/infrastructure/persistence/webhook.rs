@@ -0,0 +1,202 @@
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use sea_orm::sea_query::{Expr, OnConflict};
+use sea_orm::*;
+
+use crate::{
+    app::{log::*, utils},
+    entity::{webhook, webhook_delivery, DeliveryStatus, Webhook, WebhookDelivery},
+    event::DomainEvent,
+    repository::{DeliveryAttempt, DeliveryQuery, Error, Result, WebhookNew, WebhookRepo},
+};
+
+pub type WebhookStore = Arc<dyn WebhookRepo + Send + Sync>;
+
+pub(super) fn get_webhook_store(db: &DbConn) -> WebhookStore {
+    Arc::new(WebhookRepoImp { db: db.clone() })
+}
+
+/// Database Webhook store
+struct WebhookRepoImp {
+    db: DbConn,
+}
+
+#[async_trait]
+impl WebhookRepo for WebhookRepoImp {
+    async fn create(&self, item: WebhookNew) -> Result<Webhook> {
+        info!(url = item.url, events = ?item.events, "create webhook");
+        let res = webhook::ActiveModel {
+            url: Set(item.url),
+            secret: Set(item
+                .secret
+                .unwrap_or_else(|| utils::get_uuid_str().replace('-', ""))),
+            events: Set(item.events.join(",")),
+            active: Set(true),
+            ..Default::default()
+        }
+        .insert(&self.db)
+        .await?;
+        Ok(res)
+    }
+
+    async fn list(&self) -> Result<Vec<Webhook>> {
+        info!("list webhooks");
+        Ok(webhook::Entity::find()
+            .order_by_asc(webhook::Column::Id)
+            .all(&self.db)
+            .await?)
+    }
+
+    async fn delete(&self, id: i32) -> Result<()> {
+        info!(?id, "delete webhook");
+        let res = webhook::Entity::delete_by_id(id).exec(&self.db).await?;
+        if res.rows_affected == 0 {
+            return Err(Error::IdNotFound { id });
+        }
+        Ok(())
+    }
+
+    async fn enqueue(&self, event: &DomainEvent) -> Result<u64> {
+        let kind = event.kind();
+        let hooks: Vec<Webhook> = webhook::Entity::find()
+            .filter(webhook::Column::Active.eq(true))
+            .all(&self.db)
+            .await?
+            .into_iter()
+            .filter(|h| h.subscribes(&kind))
+            .collect();
+        if hooks.is_empty() {
+            return Ok(0);
+        }
+        debug!(
+            event_id = event.id,
+            kind,
+            n = hooks.len(),
+            "queue webhook deliveries"
+        );
+        let payload = serde_json::to_string(event)?;
+        let now = utils::get_current_time();
+        let mut n = 0;
+        let txn = self.db.begin().await?;
+        for h in hooks {
+            let delivery = webhook_delivery::ActiveModel {
+                webhook_id: Set(h.id),
+                event_id: Set(event.id.clone()),
+                event: Set(kind.clone()),
+                payload: Set(payload.clone()),
+                status: Set(DeliveryStatus::Pending),
+                attempts: Set(0),
+                next_attempt_at: Set(Some(now)),
+                // not set by before_save when inserted this way
+                created_at: Set(now),
+                ..Default::default()
+            };
+            // queued already if the event is relayed again
+            n += webhook_delivery::Entity::insert(delivery)
+                .on_conflict(
+                    OnConflict::columns([
+                        webhook_delivery::Column::EventId,
+                        webhook_delivery::Column::WebhookId,
+                    ])
+                    .do_nothing()
+                    .to_owned(),
+                )
+                .exec_without_returning(&txn)
+                .await?;
+        }
+        txn.commit().await?;
+        Ok(n)
+    }
+
+    async fn claim_due(
+        &self,
+        limit: u64,
+        lease: Duration,
+    ) -> Result<Vec<(WebhookDelivery, Webhook)>> {
+        let now = utils::get_current_time();
+        let due = webhook_delivery::Entity::find()
+            .find_also_related(webhook::Entity)
+            .filter(webhook_delivery::Column::Status.eq(DeliveryStatus::Pending))
+            .filter(webhook_delivery::Column::NextAttemptAt.lte(now))
+            .order_by_asc(webhook_delivery::Column::NextAttemptAt)
+            .limit(limit)
+            .all(&self.db)
+            .await?;
+        let mut ret = Vec::with_capacity(due.len());
+        for (delivery, hook) in due {
+            let Some(hook) = hook else { continue };
+            // taken only if nobody else moved it meanwhile
+            let res = webhook_delivery::Entity::update_many()
+                .col_expr(
+                    webhook_delivery::Column::NextAttemptAt,
+                    Expr::value(Some(now + lease)),
+                )
+                .filter(webhook_delivery::Column::Id.eq(delivery.id))
+                .filter(webhook_delivery::Column::Status.eq(DeliveryStatus::Pending))
+                .filter(webhook_delivery::Column::NextAttemptAt.eq(delivery.next_attempt_at))
+                .exec(&self.db)
+                .await?;
+            if res.rows_affected == 1 {
+                ret.push((delivery, hook));
+            }
+        }
+        Ok(ret)
+    }
+
+    async fn record(&self, id: i32, attempt: DeliveryAttempt) -> Result<()> {
+        debug!(?id, ?attempt, "record webhook delivery attempt");
+        let delivered_at =
+            (attempt.status == DeliveryStatus::Succeeded).then(utils::get_current_time);
+        webhook_delivery::Entity::update_many()
+            .col_expr(
+                webhook_delivery::Column::Attempts,
+                Expr::col(webhook_delivery::Column::Attempts).add(1),
+            )
+            .col_expr(
+                webhook_delivery::Column::Status,
+                Expr::value(attempt.status),
+            )
+            .col_expr(
+                webhook_delivery::Column::ResponseStatus,
+                Expr::value(attempt.response_status),
+            )
+            .col_expr(webhook_delivery::Column::Error, Expr::value(attempt.error))
+            .col_expr(
+                webhook_delivery::Column::NextAttemptAt,
+                Expr::value(attempt.next_attempt_at),
+            )
+            .col_expr(
+                webhook_delivery::Column::DeliveredAt,
+                Expr::value(delivered_at),
+            )
+            .filter(webhook_delivery::Column::Id.eq(id))
+            .exec(&self.db)
+            .await?;
+        Ok(())
+    }
+
+    async fn deliveries(&self, params: DeliveryQuery) -> Result<(Vec<WebhookDelivery>, u64)> {
+        info!(?params, "query webhook deliveries");
+        let page = params.page.unwrap_or(1);
+        let size = params.size.unwrap_or(10);
+
+        let mut cur = webhook_delivery::Entity::find();
+        if let Some(v) = params.webhook_id {
+            cur = cur.filter(webhook_delivery::Column::WebhookId.eq(v));
+        }
+        if let Some(v) = params.status {
+            cur = cur.filter(webhook_delivery::Column::Status.eq(v));
+        }
+        if let Some(v) = params.event {
+            cur = cur.filter(webhook_delivery::Column::Event.eq(v));
+        }
+        let paginator = cur
+            .order_by_desc(webhook_delivery::Column::Id)
+            .paginate(&self.db, size);
+
+        let total = paginator.num_items().await?;
+        let res = paginator.fetch_page(page - 1).await?;
+        Ok((res, total))
+    }
+}
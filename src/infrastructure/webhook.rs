@@ -0,0 +1,248 @@
+use std::{sync::Arc, time::Duration};
+
+use futures::{stream, StreamExt};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use tokio::sync::{
+    broadcast::{self, error::RecvError},
+    Notify,
+};
+
+use crate::{
+    app::{log::*, utils},
+    entity::{DeliveryStatus, Webhook, WebhookDelivery},
+    event::DomainEvent,
+    repository::{DeliveryAttempt, Result},
+};
+
+use super::{config, event_bus::EventBus, persistence::WebhookStore};
+
+const DEFAULT_MAX_ATTEMPTS: u32 = 8;
+const DEFAULT_RETRY_BASE: Duration = Duration::from_secs(10);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(3600);
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// deliveries taken at a time
+const BATCH: u64 = 100;
+/// deliveries in flight at a time
+const CONCURRENCY: usize = 8;
+
+/// kind of the event, e.g. `post.created`
+const EVENT_HEADER: &str = "x-webhook-event";
+/// id of the event, the same for all attempts
+const EVENT_ID_HEADER: &str = "x-webhook-event-id";
+/// unix seconds of the attempt
+const TIMESTAMP_HEADER: &str = "x-webhook-timestamp";
+/// `sha256=` and hex HMAC-SHA256 of `{timestamp}.{body}`, keyed by the webhook secret
+const SIGNATURE_HEADER: &str = "x-webhook-signature";
+
+/// Queues deliveries of events, waking the dispatcher to send them
+#[derive(Clone)]
+pub struct WebhookQueue {
+    store: WebhookStore,
+    wake: Arc<Notify>,
+}
+
+impl WebhookQueue {
+    /// Queue deliveries of the event, once per webhook however often it is pushed
+    pub async fn push(&self, event: &DomainEvent) -> Result<()> {
+        if self.store.enqueue(event).await? > 0 {
+            self.wake.notify_one();
+        }
+        Ok(())
+    }
+}
+
+/// Queue deliveries of published events, and send them with retries in background.
+/// Post events come from the outbox relay instead, pushed to the returned queue.
+pub fn spawn_dispatcher(store: WebhookStore, bus: &EventBus) -> WebhookQueue {
+    let queue = WebhookQueue {
+        store: store.clone(),
+        wake: Arc::new(Notify::new()),
+    };
+    tokio::spawn(enqueue_events(queue.clone(), bus.subscribe()));
+    tokio::spawn(send_due(store, queue.wake.clone()));
+    queue
+}
+
+async fn enqueue_events(queue: WebhookQueue, mut events: broadcast::Receiver<DomainEvent>) {
+    loop {
+        match events.recv().await {
+            Ok(event) => {
+                if let Err(e) = queue.push(&event).await {
+                    error!(%e, event_id = event.id, "queue webhook deliveries failed");
+                }
+            }
+            Err(RecvError::Lagged(n)) => warn!(n, "webhook dispatcher missed events"),
+            Err(RecvError::Closed) => return,
+        }
+    }
+}
+
+async fn send_due(store: WebhookStore, wake: Arc<Notify>) {
+    let client = reqwest::Client::new();
+    loop {
+        let conf = config::peek_config()
+            .map(|c| c.webhook.clone())
+            .unwrap_or_default();
+        let timeout = conf
+            .timeout_secs
+            .map_or(DEFAULT_TIMEOUT, Duration::from_secs);
+        let policy = RetryPolicy {
+            max_attempts: conf.max_attempts.unwrap_or(DEFAULT_MAX_ATTEMPTS),
+            base: conf
+                .retry_base_secs
+                .map_or(DEFAULT_RETRY_BASE, Duration::from_secs),
+        };
+
+        // leased long enough for the request to finish
+        let due = store
+            .claim_due(BATCH, timeout * 2)
+            .await
+            .unwrap_or_else(|e| {
+                error!(%e, "take due webhook deliveries failed");
+                Vec::new()
+            });
+        let n = due.len();
+        stream::iter(due)
+            .for_each_concurrent(CONCURRENCY, |(delivery, hook)| {
+                let (client, store) = (&client, &store);
+                async move {
+                    let attempt = deliver(client, &delivery, &hook, timeout, policy).await;
+                    if let Err(e) = store.record(delivery.id, attempt).await {
+                        error!(%e, id = delivery.id, "record webhook delivery failed");
+                    }
+                }
+            })
+            .await;
+
+        if n < BATCH as usize {
+            let poll = conf
+                .poll_secs
+                .filter(|&v| v > 0)
+                .map_or(DEFAULT_POLL_INTERVAL, Duration::from_secs);
+            tokio::select! {
+                _ = wake.notified() => {}
+                _ = tokio::time::sleep(poll) => {}
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct RetryPolicy {
+    max_attempts: u32,
+    base: Duration,
+}
+
+impl RetryPolicy {
+    /// Delay after the failed attempt, or None to give up
+    fn delay(&self, attempts: u32) -> Option<Duration> {
+        (attempts < self.max_attempts).then(|| {
+            self.base
+                .saturating_mul(1 << attempts.saturating_sub(1).min(16))
+                .min(MAX_RETRY_DELAY)
+        })
+    }
+}
+
+async fn deliver(
+    client: &reqwest::Client,
+    delivery: &WebhookDelivery,
+    hook: &Webhook,
+    timeout: Duration,
+    policy: RetryPolicy,
+) -> DeliveryAttempt {
+    let ts = utils::get_current_time().unix_timestamp();
+    let res = client
+        .post(&hook.url)
+        .timeout(timeout)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(EVENT_HEADER, &delivery.event)
+        .header(EVENT_ID_HEADER, &delivery.event_id)
+        .header(TIMESTAMP_HEADER, ts)
+        .header(
+            SIGNATURE_HEADER,
+            format!("sha256={}", sign(&hook.secret, ts, &delivery.payload)),
+        )
+        .body(delivery.payload.clone())
+        .send()
+        .await;
+
+    let (response_status, error) = match res {
+        Ok(r) if r.status().is_success() => {
+            debug!(id = delivery.id, url = hook.url, "webhook delivered");
+            return DeliveryAttempt {
+                status: DeliveryStatus::Succeeded,
+                response_status: Some(r.status().as_u16() as i32),
+                error: None,
+                next_attempt_at: None,
+            };
+        }
+        Ok(r) => (
+            Some(r.status().as_u16() as i32),
+            format!("unexpected response status {}", r.status()),
+        ),
+        Err(e) => (None, e.to_string()),
+    };
+    let attempts = delivery.attempts as u32 + 1;
+    let delay = policy.delay(attempts);
+    warn!(
+        id = delivery.id,
+        url = hook.url,
+        attempts,
+        error,
+        ?delay,
+        "webhook delivery failed"
+    );
+    DeliveryAttempt {
+        status: match delay {
+            Some(_) => DeliveryStatus::Pending,
+            None => DeliveryStatus::Failed,
+        },
+        response_status,
+        error: Some(error),
+        next_attempt_at: delay.map(|d| utils::get_current_time() + d),
+    }
+}
+
+/// Hex HMAC-SHA256 of `{timestamp}.{body}`
+pub fn sign(secret: &str, ts: i64, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any size");
+    mac.update(ts.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+    format!("{:x}", mac.finalize().into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retry_backoff() {
+        let policy = RetryPolicy {
+            max_attempts: 4,
+            base: Duration::from_secs(10),
+        };
+        assert_eq!(policy.delay(1), Some(Duration::from_secs(10)));
+        assert_eq!(policy.delay(2), Some(Duration::from_secs(20)));
+        assert_eq!(policy.delay(3), Some(Duration::from_secs(40)));
+        assert_eq!(policy.delay(4), None);
+
+        let policy = RetryPolicy {
+            max_attempts: 100,
+            base: Duration::from_secs(10),
+        };
+        assert_eq!(policy.delay(50), Some(MAX_RETRY_DELAY));
+    }
+
+    #[test]
+    fn hmac_signature() {
+        assert_eq!(
+            sign("secret", 1700000000, r#"{"id":"x"}"#),
+            "2f7852138f9dbd8d61c07c2cfb0b8ac96a46a32d78d4527788fb42fcb409a493"
+        );
+    }
+}
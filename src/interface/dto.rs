@@ -1,7 +1,45 @@
-use utoipa::ToSchema;
+use serde::Deserialize;
+use utoipa::{IntoParams, ToSchema};
 
+#[allow(dead_code)]
 #[derive(ToSchema)]
 pub struct MultipartFile {
     // #[schema(value_type = String, format = Binary)]
     pub file: Vec<u8>,
 }
+
+/// Format of Post import and export data
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum DataFormat {
+    /// one JSON object per line
+    #[default]
+    Ndjson,
+    /// comma-separated values with a header row
+    Csv,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct TransferQuery {
+    /// data format, ndjson if not set
+    #[param(inline)]
+    pub format: Option<DataFormat>,
+}
+
+/// Format of Post content in responses
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentFormat {
+    /// Markdown as written
+    #[default]
+    Raw,
+    /// also render Markdown as sanitized HTML, with excerpt and table of contents
+    Html,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ContentQuery {
+    /// content format, raw if not set
+    #[param(inline)]
+    pub format: Option<ContentFormat>,
+}
@@ -0,0 +1,154 @@
+use std::{
+    convert::Infallible,
+    net::{IpAddr, SocketAddr},
+};
+
+use async_trait::async_trait;
+use axum::{
+    extract::{ConnectInfo, FromRef, FromRequestParts},
+    http::{header, request::Parts},
+};
+use ipnet::IpNet;
+use tower_http::request_id::RequestId;
+
+use crate::{
+    app::log::*,
+    entity::User,
+    infrastructure::{config, persistence::UserStore},
+    repository::{AuditContext, Error, Result},
+};
+
+/// Client ip, the peer address, or the one forwarded by a trusted proxy
+pub struct ClientIp(pub Option<String>);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for ClientIp {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        _state: &S,
+    ) -> std::result::Result<Self, Self::Rejection> {
+        let Some(peer) = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip())
+        else {
+            return Ok(Self(None));
+        };
+        let trusted = trusted_proxies();
+        let is_trusted = |ip: &IpAddr| trusted.iter().any(|x| x.contains(ip));
+        if !is_trusted(&peer) {
+            return Ok(Self(Some(peer.to_string())));
+        }
+        let headers = &parts.headers;
+        let forwarded: Vec<IpAddr> = headers
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .filter_map(|v| v.trim().parse().ok())
+            .collect();
+        // the nearest hop not of our proxies, as those before it could be made up by the client
+        let ip = forwarded
+            .iter()
+            .rev()
+            .find(|x| !is_trusted(x))
+            .or(forwarded.first())
+            .copied()
+            .or_else(|| {
+                headers
+                    .get("x-real-ip")
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|v| v.trim().parse().ok())
+            })
+            .unwrap_or(peer);
+        Ok(Self(Some(ip.to_string())))
+    }
+}
+
+/// Configured reverse proxies, single addresses taken as networks of their own
+fn trusted_proxies() -> Vec<IpNet> {
+    let proxies = config::peek_config()
+        .map(|c| c.server.trusted_proxies.clone())
+        .unwrap_or_default();
+    proxies
+        .iter()
+        .filter_map(|x| {
+            let net = x
+                .parse()
+                .or_else(|_| x.parse::<IpAddr>().map(IpNet::from))
+                .ok();
+            if net.is_none() {
+                warn!(proxy = x, "invalid trusted proxy, ignored");
+            }
+            net
+        })
+        .collect()
+}
+
+/// User authenticated by `Authorization: Bearer <token>`
+pub struct CurrentUser(pub User);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for CurrentUser
+where
+    UserStore: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self> {
+        let token = parts
+            .headers
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .map(str::trim)
+            .ok_or(Error::Unauthorized)?;
+        let store = UserStore::from_ref(state);
+        match store.authenticate(token).await? {
+            Some(user) => Ok(Self(user)),
+            None => Err(Error::Unauthorized),
+        }
+    }
+}
+
+/// Who makes the request and from where, for stores to audit its changes by
+pub struct Auditor {
+    request_id: Option<String>,
+    client_ip: Option<String>,
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Auditor {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &S,
+    ) -> std::result::Result<Self, Self::Rejection> {
+        let request_id = parts
+            .extensions
+            .get::<RequestId>()
+            .and_then(|v| v.header_value().to_str().ok())
+            .map(str::to_owned);
+        let ClientIp(client_ip) = ClientIp::from_request_parts(parts, state).await?;
+        Ok(Self {
+            request_id,
+            client_ip,
+        })
+    }
+}
+
+impl Auditor {
+    /// The actor and this request, for a store to record its change by
+    pub fn context(&self, actor: Option<&User>, action: &str) -> AuditContext {
+        AuditContext {
+            actor_id: actor.map(|u| u.id),
+            action: action.to_owned(),
+            request_id: self.request_id.clone(),
+            client_ip: self.client_ip.clone(),
+        }
+    }
+}
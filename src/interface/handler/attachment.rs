@@ -0,0 +1,271 @@
+use std::path::PathBuf;
+
+use axum::{
+    extract::{Multipart, Path, Request, State},
+    http::{header, HeaderValue},
+    response::IntoResponse,
+    Json,
+};
+use tokio::{fs, io::AsyncReadExt};
+use tower::ServiceExt;
+use tower_http::services::ServeFile;
+
+use crate::{
+    app::{log::*, utils},
+    infrastructure::{
+        config,
+        persistence::{AttachmentStore, PostStore},
+    },
+    interface::{dto::MultipartFile, extract::CurrentUser, resp::*},
+    repository::{AttachmentNew, Error, Result},
+};
+
+use super::{check_post_authors, check_post_visible, ok_resp, save_field};
+
+const DEFAULT_DIR: &str = "attachments";
+const DEFAULT_MAX_FILE_SIZE: u64 = 10 * 1024 * 1024;
+const DEFAULT_MAX_POST_SIZE: u64 = 100 * 1024 * 1024;
+/// Types safe to show in the browser, recognized by their content
+const INLINE_TYPES: [(&str, &[u8]); 5] = [
+    ("image/png", b"\x89PNG\r\n\x1a\n"),
+    ("image/jpeg", b"\xff\xd8\xff"),
+    ("image/gif", b"GIF8"),
+    ("image/webp", b"WEBP"),
+    ("application/pdf", b"%PDF-"),
+];
+
+/// List attachments of a Post
+///
+/// List files attached to a Post, oldest first. Those of unpublished Posts only to the author or an admin.
+#[utoipa::path(
+        get,
+        path = "/post/{id}/attachments",
+        params(
+            ("id" = i32, Path, description = "Post item id")
+        ),
+        responses(
+            (status = 200, description = "List attachments of the Post", body = AttachmentListRes)
+        )
+    )]
+pub async fn list(
+    post_store: State<PostStore>,
+    store: State<AttachmentStore>,
+    user: Option<CurrentUser>,
+    Path(post_id): Path<i32>,
+) -> Result<Json<AttachmentListRes>> {
+    check_post_visible(&post_store, user.as_ref().map(|x| &x.0), post_id).await?;
+    let attachments = store.list(post_id).await?;
+    Ok(Json(Response::new(attachments)))
+}
+
+/// Upload attachments to a Post
+///
+/// Attach the files in `file` fields to a Post. Identical files are stored only once.
+/// The content type is told from the content and the file name, never from the request.
+#[utoipa::path(
+        post,
+        path = "/post/{id}/attachments",
+        params(
+            ("id" = i32, Path, description = "Post item id")
+        ),
+        request_body(content = inline(MultipartFile), description = "files to attach", content_type = "multipart/form-data"),
+        responses(
+            (status = 200, description = "Files attached successfully", body = AttachmentListRes)
+        ),
+        security(("token" = []))
+    )]
+pub async fn upload(
+    post_store: State<PostStore>,
+    store: State<AttachmentStore>,
+    CurrentUser(user): CurrentUser,
+    Path(post_id): Path<i32>,
+    mut multipart: Multipart,
+) -> Result<Json<AttachmentListRes>> {
+    if post_store.authors(vec![post_id]).await?.is_empty() {
+        return Err(Error::IdNotFound { id: post_id });
+    }
+    check_post_authors(&post_store, &user, &[post_id]).await?;
+
+    let conf = config::peek_config()?.attachment.clone();
+    let dir = PathBuf::from(conf.dir.as_deref().unwrap_or(DEFAULT_DIR));
+    let max_file_size = conf.max_file_size.unwrap_or(DEFAULT_MAX_FILE_SIZE);
+    let max_post_size = conf.max_post_size.unwrap_or(DEFAULT_MAX_POST_SIZE);
+    let tmp_dir = dir.join("tmp");
+    fs::create_dir_all(&tmp_dir).await?;
+
+    let mut used = store.total_size(post_id).await?;
+    let mut ret = Vec::new();
+    while let Some(field) = multipart.next_field().await? {
+        if !matches!(field.name(), Some("file")) {
+            continue;
+        }
+        let name = field
+            .file_name()
+            .and_then(|v| v.rsplit(['/', '\\']).next())
+            .filter(|v| !v.is_empty())
+            .unwrap_or("file")
+            .to_owned();
+        let limit = max_file_size.min(max_post_size.saturating_sub(used));
+        let tmp = tmp_dir.join(utils::get_uuid_str());
+        let (size, sha256) = save_field(field, &tmp, Some(limit)).await?;
+        if size == 0 {
+            fs::remove_file(&tmp).await.ok();
+            continue;
+        }
+        let content_type = detect_content_type(&tmp, &name).await?;
+
+        let path = match store.find_path(&sha256).await? {
+            Some(p) if fs::try_exists(&p).await.unwrap_or_default() => {
+                fs::remove_file(&tmp).await.ok();
+                p
+            }
+            _ => {
+                let p = dir.join(&sha256[..2]).join(&sha256);
+                fs::create_dir_all(dir.join(&sha256[..2])).await?;
+                fs::rename(&tmp, &p).await?;
+                p.to_string_lossy().into_owned()
+            }
+        };
+        info!(?post_id, ?name, ?size, ?path, "attachment stored");
+        used += size;
+        ret.push(
+            store
+                .create(AttachmentNew {
+                    post_id,
+                    name,
+                    content_type,
+                    size,
+                    sha256,
+                    path,
+                })
+                .await?,
+        );
+    }
+    if ret.is_empty() {
+        return Err(Error::BadRequest);
+    }
+    Ok(Json(Response::new(ret)))
+}
+
+/// Download an attachment
+///
+/// Download the content of an attachment, supporting range requests.
+/// Only images and PDFs are shown inline, others are always downloaded as files.
+#[utoipa::path(
+        get,
+        path = "/post/{id}/attachments/{attachment_id}",
+        params(
+            ("id" = i32, Path, description = "Post item id"),
+            ("attachment_id" = i32, Path, description = "Attachment id")
+        ),
+        responses(
+            (status = 200, description = "Content of the attachment"),
+            (status = 206, description = "Requested range of the content")
+        )
+    )]
+pub async fn download(
+    post_store: State<PostStore>,
+    store: State<AttachmentStore>,
+    user: Option<CurrentUser>,
+    Path((post_id, id)): Path<(i32, i32)>,
+    req: Request,
+) -> Result<axum::response::Response> {
+    check_post_visible(&post_store, user.as_ref().map(|x| &x.0), post_id).await?;
+    let item = store.fetch(post_id, id).await?;
+    let mime = item
+        .content_type
+        .parse()
+        .unwrap_or(mime::APPLICATION_OCTET_STREAM);
+    let mut res = ServeFile::new_with_mime(&item.path, &mime)
+        .oneshot(req)
+        .await
+        .unwrap_or_else(|e| match e {})
+        .into_response();
+    let disposition = if INLINE_TYPES.iter().any(|(t, _)| *t == item.content_type) {
+        "inline"
+    } else {
+        "attachment"
+    };
+    let headers = res.headers_mut();
+    if let Ok(v) = HeaderValue::from_str(&format!(
+        "{disposition}; filename*=UTF-8''{}",
+        encode_filename(&item.name)
+    )) {
+        headers.insert(header::CONTENT_DISPOSITION, v);
+    }
+    headers.insert(
+        header::X_CONTENT_TYPE_OPTIONS,
+        HeaderValue::from_static("nosniff"),
+    );
+    Ok(res)
+}
+
+/// Delete an attachment
+///
+/// Delete an attachment of a Post, and its file if no other attachment shares it.
+#[utoipa::path(
+        delete,
+        path = "/post/{id}/attachments/{attachment_id}",
+        params(
+            ("id" = i32, Path, description = "Post item id"),
+            ("attachment_id" = i32, Path, description = "Attachment id")
+        ),
+        responses(
+            (status = 200, description = "Attachment deleted successfully", body = VoidRes)
+        ),
+        security(("token" = []))
+    )]
+pub async fn delete(
+    post_store: State<PostStore>,
+    store: State<AttachmentStore>,
+    CurrentUser(user): CurrentUser,
+    Path((post_id, id)): Path<(i32, i32)>,
+) -> Result<Json<VoidRes>> {
+    check_post_authors(&post_store, &user, &[post_id]).await?;
+    if let Some(path) = store.delete(post_id, id).await? {
+        info!(?path, "remove attachment file");
+        fs::remove_file(path).await.ok();
+    }
+    Ok(Json(ok_resp()))
+}
+
+/// Content type of the saved file, by its leading bytes for the types shown inline, else by its name
+async fn detect_content_type(path: &std::path::Path, name: &str) -> Result<String> {
+    let mut head = [0u8; 16];
+    let mut file = fs::File::open(path).await?;
+    let mut n = 0;
+    while n < head.len() {
+        match file.read(&mut head[n..]).await? {
+            0 => break,
+            m => n += m,
+        }
+    }
+    let head = &head[..n];
+    let sniffed = INLINE_TYPES.iter().find(|(t, magic)| match *t {
+        // RIFF container
+        "image/webp" => head.starts_with(b"RIFF") && head[8.min(n)..].starts_with(magic),
+        _ => head.starts_with(magic),
+    });
+    if let Some((t, _)) = sniffed {
+        return Ok(t.to_string());
+    }
+    let guess = mime_guess::from_path(name).first_or_octet_stream();
+    if INLINE_TYPES.iter().any(|(t, _)| *t == guess.essence_str()) {
+        // named like an image whose content is not one
+        return Ok(mime::APPLICATION_OCTET_STREAM.to_string());
+    }
+    Ok(guess.to_string())
+}
+
+/// percent-encode file name for `Content-Disposition`
+fn encode_filename(name: &str) -> String {
+    let mut ret = String::with_capacity(name.len());
+    for b in name.bytes() {
+        if b.is_ascii_alphanumeric() || b"!#$&+-.^_`|~".contains(&b) {
+            ret.push(b as char);
+        } else {
+            ret.push_str(&format!("%{b:02X}"));
+        }
+    }
+    ret
+}
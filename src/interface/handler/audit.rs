@@ -0,0 +1,34 @@
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+
+use crate::{
+    infrastructure::persistence::AuditStore,
+    interface::{extract::CurrentUser, resp::*},
+    repository::{AuditQuery, Error, Result},
+};
+
+/// Query audit log
+///
+/// Query records of changes to Posts and Todos, newest first, for admins only.
+#[utoipa::path(
+        get,
+        path = "/audit",
+        params(AuditQuery),
+        responses(
+            (status = 200, description = "List matching audit records", body = AuditLogListRes)
+        ),
+        security(("token" = []))
+    )]
+pub async fn list(
+    store: State<AuditStore>,
+    CurrentUser(user): CurrentUser,
+    Query(params): Query<AuditQuery>,
+) -> Result<Json<AuditLogListRes>> {
+    if !user.is_admin() {
+        return Err(Error::Forbidden);
+    }
+    let records = store.query(params).await?;
+    Ok(Json(Response::new(records.into())))
+}
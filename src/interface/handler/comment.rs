@@ -0,0 +1,141 @@
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+
+use crate::{
+    entity::User,
+    infrastructure::persistence::{CommentStore, PostStore},
+    interface::{extract::CurrentUser, resp::*},
+    repository::{CommentNew, CommentQuery, CommentUpdate, Error, Result},
+};
+
+use super::{check_post_visible, ok_resp};
+
+/// Query Comments of a Post
+///
+/// List top-level Comments of a Post, or replies to a Comment, oldest first.
+/// Unpublished Posts are seen only by their authors and admins.
+#[utoipa::path(
+        get,
+        path = "/post/{id}/comments",
+        params(
+            ("id" = i32, Path, description = "Post item id"),
+            CommentQuery
+        ),
+        responses(
+            (status = 200, description = "List matching Comments by query", body = CommentListRes)
+        )
+    )]
+pub async fn list(
+    post_store: State<PostStore>,
+    store: State<CommentStore>,
+    user: Option<CurrentUser>,
+    Path(post_id): Path<i32>,
+    Query(params): Query<CommentQuery>,
+) -> Result<Json<CommentListRes>> {
+    check_post_visible(&post_store, user.as_ref().map(|x| &x.0), post_id).await?;
+    let comments = store.query(post_id, params).await?;
+    Ok(Json(Response::new(comments.into())))
+}
+
+/// Create new Comment
+///
+/// Comment on a Post, or reply to a Comment of it.
+/// Unpublished Posts are seen only by their authors and admins.
+#[utoipa::path(
+        post,
+        path = "/post/{id}/comments",
+        params(
+            ("id" = i32, Path, description = "Post item id")
+        ),
+        request_body = CommentNew,
+        responses(
+            (status = 200, description = "Comment created successfully", body = IdRes)
+        )
+    )]
+pub async fn create(
+    post_store: State<PostStore>,
+    store: State<CommentStore>,
+    user: Option<CurrentUser>,
+    Path(post_id): Path<i32>,
+    Json(mut comment): Json<CommentNew>,
+) -> Result<Json<IdRes>> {
+    if comment.content.is_empty() {
+        return Err(Error::BadRequest);
+    }
+    check_post_visible(&post_store, user.as_ref().map(|x| &x.0), post_id).await?;
+    comment.author_id = user.map(|x| x.0.id);
+    let new_id = store.create(post_id, comment).await?;
+    Ok(Json(Response::new(IdData { id: new_id })))
+}
+
+/// Edit Comment by id
+///
+/// Edit content of a Comment by given id, by its author or an admin.
+#[utoipa::path(
+        put,
+        path = "/post/{id}/comments/{comment_id}",
+        params(
+            ("id" = i32, Path, description = "Post item id"),
+            ("comment_id" = i32, Path, description = "Comment id")
+        ),
+        request_body = CommentUpdate,
+        responses(
+            (status = 200, description = "Comment edited successfully", body = VoidRes)
+        ),
+        security(("token" = []))
+    )]
+pub async fn edit(
+    store: State<CommentStore>,
+    CurrentUser(user): CurrentUser,
+    Path((post_id, id)): Path<(i32, i32)>,
+    Json(mut comment): Json<CommentUpdate>,
+) -> Result<Json<VoidRes>> {
+    if comment.content.is_empty() {
+        return Err(Error::BadRequest);
+    }
+    check_comment_author(&store, &user, post_id, id).await?;
+    comment.id = id;
+    store.update(post_id, comment).await?;
+    Ok(Json(ok_resp()))
+}
+
+/// Delete Comment by id
+///
+/// Delete a Comment and all replies to it, by its author or an admin.
+#[utoipa::path(
+        delete,
+        path = "/post/{id}/comments/{comment_id}",
+        params(
+            ("id" = i32, Path, description = "Post item id"),
+            ("comment_id" = i32, Path, description = "Comment id")
+        ),
+        responses(
+            (status = 200, description = "Comment deleted successfully", body = VoidRes)
+        ),
+        security(("token" = []))
+    )]
+pub async fn delete(
+    store: State<CommentStore>,
+    CurrentUser(user): CurrentUser,
+    Path((post_id, id)): Path<(i32, i32)>,
+) -> Result<Json<VoidRes>> {
+    check_comment_author(&store, &user, post_id, id).await?;
+    store.delete(post_id, id).await?;
+    Ok(Json(ok_resp()))
+}
+
+/// Make sure the User may change the Comment
+async fn check_comment_author(
+    store: &CommentStore,
+    user: &User,
+    post_id: i32,
+    id: i32,
+) -> Result<()> {
+    if user.can_edit(store.author(post_id, id).await?) {
+        Ok(())
+    } else {
+        Err(Error::Forbidden)
+    }
+}
@@ -0,0 +1,27 @@
+use axum::{extract::State, http::StatusCode, Json};
+
+use crate::{infrastructure::persistence::DbHealth, interface::resp::*};
+
+use super::ok_resp;
+
+/// Readiness probe
+///
+/// Ready when the database is connected and migrated, otherwise responds with 503.
+#[utoipa::path(
+        get,
+        path = "/health/ready",
+        responses(
+            (status = 200, description = "Ready to serve", body = VoidRes),
+            (status = 503, description = "Database not ready", body = VoidRes)
+        )
+    )]
+pub async fn ready(health: State<DbHealth>) -> (StatusCode, Json<VoidRes>) {
+    if health.is_ready().await {
+        (StatusCode::OK, Json(ok_resp()))
+    } else {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(Response::new_err(Void {}, "database not ready".to_owned())),
+        )
+    }
+}
@@ -1,10 +1,31 @@
+pub mod attachment;
+pub mod audit;
+pub mod comment;
+pub mod health;
 pub mod post;
+pub mod post_transfer;
 pub mod read_xls;
+pub mod tag;
 pub mod todo;
+pub mod user;
+pub mod webhook;
 
-use axum::{response::IntoResponse, Json};
+use std::{io, path::Path};
 
-use crate::repository::{Error, Result};
+use axum::{extract::multipart::Field, response::IntoResponse, Json};
+use futures::TryStreamExt;
+use sha2::{Digest, Sha256};
+use tokio::{
+    fs::{self, File},
+    io::{AsyncReadExt, BufWriter},
+};
+use tokio_util::io::StreamReader;
+
+use crate::{
+    entity::{PostStatus, User},
+    infrastructure::persistence::PostStore,
+    repository::{Error, Result},
+};
 
 use super::resp::{ObjectRes, Response, Void, VoidRes};
 
@@ -12,6 +33,17 @@ impl IntoResponse for Error {
     fn into_response(self) -> axum::response::Response {
         match self {
             Self::BadRequest => Json(Response::new_bad(Void {})).into_response(),
+            Self::Unauthorized => Json(Response::new_unauthorized(Void {})).into_response(),
+            Self::Forbidden => Json(Response::new_forbidden(Void {})).into_response(),
+            Self::InvalidTransition { .. }
+            | Self::InvalidPublishAt
+            // moving a Todo under itself or one of its subtasks
+            | Self::TodoCycle { .. }
+            | Self::SlugTaken { .. }
+            | Self::FileTooLarge { .. }
+            | Self::BulkItemFailed { .. } => {
+                Json(Response::new_bad_msg(Void {}, self.to_string())).into_response()
+            }
             _ => Json(Response::new_err(Void {}, self.to_string())).into_response(),
         }
     }
@@ -24,3 +56,66 @@ fn ok_resp() -> VoidRes {
 fn to_raw_resp(s: String) -> Result<ObjectRes> {
     Ok(Response::new(serde_json::from_str(&s)?))
 }
+
+/// Make sure the User may change all the Posts
+async fn check_post_authors(store: &PostStore, user: &User, ids: &[i32]) -> Result<()> {
+    if user.is_admin() {
+        return Ok(());
+    }
+    let authors = store.authors(ids.to_vec()).await?;
+    if authors
+        .iter()
+        .all(|(_, author_id)| user.can_edit(*author_id))
+    {
+        Ok(())
+    } else {
+        Err(Error::Forbidden)
+    }
+}
+
+/// Make sure the Post is published, or the User is its author or an admin
+async fn check_post_visible(store: &PostStore, user: Option<&User>, id: i32) -> Result<()> {
+    let post = store
+        .snapshot(vec![id])
+        .await?
+        .pop()
+        .ok_or(Error::IdNotFound { id })?;
+    if post.status == PostStatus::Published || user.is_some_and(|u| u.can_edit(post.author_id)) {
+        Ok(())
+    } else {
+        // not to tell drafts from missing Posts
+        Err(Error::IdNotFound { id })
+    }
+}
+
+/// Stream a multipart field to the file, returning its size and sha256 hex digest.
+/// The file is removed if it fails, or turns out larger than the limit.
+async fn save_field(field: Field<'_>, path: &Path, limit: Option<u64>) -> Result<(u64, String)> {
+    let mut hasher = Sha256::new();
+    let ret = async {
+        // stream to file, save lots of memory if file is big
+        let body_with_io_error = field
+            .inspect_ok(|chunk| hasher.update(chunk))
+            .map_err(io::Error::other);
+        let body_reader = StreamReader::new(body_with_io_error);
+        futures::pin_mut!(body_reader);
+
+        let mut file = BufWriter::new(File::create(path).await?);
+        let size = match limit {
+            Some(limit) => tokio::io::copy(&mut body_reader.take(limit + 1), &mut file).await?,
+            None => tokio::io::copy(&mut body_reader, &mut file).await?,
+        };
+        match limit {
+            Some(limit) if size > limit => Err(Error::FileTooLarge { limit }),
+            _ => Ok(size),
+        }
+    }
+    .await;
+    match ret {
+        Ok(size) => Ok((size, format!("{:x}", hasher.finalize()))),
+        Err(e) => {
+            fs::remove_file(path).await.ok();
+            Err(e)
+        }
+    }
+}
@@ -1,25 +1,40 @@
 use axum::{
-    extract::{Path, Query, State},
+    extract::{Path, Query, RawQuery, State},
+    response::{IntoResponse, Redirect},
     Json,
 };
+use tokio::fs;
 
 use crate::{
-    app::utils,
-    infrastructure::persistence::PostStore,
-    interface::resp::*,
-    repository::{Error, PostDelete, PostNew, PostQuery, PostUpdate, Result},
+    app::{log::*, utils},
+    entity::{Post, PostStatus},
+    infrastructure::{
+        config,
+        persistence::{AttachmentStore, PostStore},
+    },
+    interface::{
+        dto::{ContentFormat, ContentQuery},
+        extract::{Auditor, ClientIp, CurrentUser},
+        resp::*,
+    },
+    repository::{
+        Error, PostBulkNew, PostBulkUpdate, PostDelete, PostNew, PostQuery, PostTransition,
+        PostUpdate, Result, RevisionDiffQuery, RevisionQuery,
+    },
 };
 
-use super::ok_resp;
+use super::{check_post_authors, check_post_visible, ok_resp};
 
 /// Query Post items
 ///
-/// Query Post items from database.
+/// Query published Post items from database, optionally with content rendered as HTML.
+/// Signed-in Users may list their own Posts in other status, admins anyone's.
 #[utoipa::path(
         get,
         path = "/post",
         params(
-            PostQuery
+            PostQuery,
+            ContentQuery
         ),
         responses(
             (status = 200, description = "List matching Post items by query", body = PostListRes)
@@ -27,49 +42,148 @@ use super::ok_resp;
     )]
 pub async fn list(
     store: State<PostStore>,
-    Query(params): Query<PostQuery>,
+    user: Option<CurrentUser>,
+    Query(mut params): Query<PostQuery>,
+    Query(content): Query<ContentQuery>,
 ) -> Result<Json<PostListRes>> {
-    let posts = store.query(params).await?;
+    match (params.status, user) {
+        (None | Some(PostStatus::Published), _) => params.status = Some(PostStatus::Published),
+        (Some(_), None) => return Err(Error::Unauthorized),
+        (Some(_), Some(CurrentUser(user))) if !user.is_admin() => {
+            if params.author_id.is_some_and(|id| id != user.id) {
+                return Err(Error::Forbidden);
+            }
+            params.author_id = Some(user.id);
+        }
+        _ => {}
+    }
+    let mut posts = store.query(params).await?;
+    render_content(&mut posts.0, content.format);
     Ok(Json(Response::new(posts.into())))
 }
 
 /// Get a Post
 ///
-/// Get detail of a Post item by id.
+/// Get detail of a published Post item by id, or of an unpublished one for its author or an admin.
+/// With `format=html` the Markdown content is also rendered as sanitized HTML, with excerpt and table of contents.
 #[utoipa::path(
     get,
     path = "/post/{id}",
     params(
-        ("id" = i32, Path, description = "Post item id")
+        ("id" = i32, Path, description = "Post item id"),
+        ContentQuery
     ),
     responses(
         (status = 200, description = "Post item fetch successfully", body = PostRes)
     )
 )]
-pub async fn get(store: State<PostStore>, Path(id): Path<i32>) -> Result<Json<PostRes>> {
-    let post = store.fetch(id).await?;
+pub async fn get(
+    store: State<PostStore>,
+    user: Option<CurrentUser>,
+    Path(id): Path<i32>,
+    ClientIp(ip): ClientIp,
+    Query(content): Query<ContentQuery>,
+) -> Result<Json<PostRes>> {
+    let mut post = fetch_visible(&store, user, id, ip).await?;
+    render_content(std::slice::from_mut(&mut post), content.format);
     Ok(Json(Response::new(post)))
 }
 
+/// Get a Post by slug
+///
+/// Get detail of a published Post item by its slug, or of an unpublished one for its author or an admin.
+/// A former slug of the Post is permanently redirected to the current one.
+#[utoipa::path(
+    get,
+    path = "/post/by-slug/{slug}",
+    params(
+        ("slug" = String, Path, description = "Post slug"),
+        ContentQuery
+    ),
+    responses(
+        (status = 200, description = "Post item fetch successfully", body = PostRes),
+        (status = 308, description = "Post moved to its current slug")
+    )
+)]
+pub async fn get_by_slug(
+    store: State<PostStore>,
+    user: Option<CurrentUser>,
+    Path(slug): Path<String>,
+    ClientIp(ip): ClientIp,
+    RawQuery(query): RawQuery,
+    Query(content): Query<ContentQuery>,
+) -> Result<axum::response::Response> {
+    let (id, current) = store.find_slug(slug.clone()).await?;
+    if current != slug {
+        let mut uri = format!("{}/post/by-slug/{current}", config::BASE_PATH);
+        if let Some(q) = query {
+            uri = uri + "?" + &q;
+        }
+        return Ok(Redirect::permanent(&uri).into_response());
+    }
+    let mut post = fetch_visible(&store, user, id, ip).await?;
+    render_content(std::slice::from_mut(&mut post), content.format);
+    Ok(Json(Response::new(post)).into_response())
+}
+
 /// Create new Post
 ///
-/// Try to create a new Post item to database.
+/// Try to create a new Post item to database, authored by the current User.
 #[utoipa::path(
         post,
         path = "/post",
         request_body = PostNew,
         responses(
             (status = 200, description = "Post item created successfully", body = IdRes)
-        )
+        ),
+        security(("token" = []))
     )]
-pub async fn create(store: State<PostStore>, Json(post): Json<PostNew>) -> Result<Json<IdRes>> {
+pub async fn create(
+    store: State<PostStore>,
+    CurrentUser(user): CurrentUser,
+    audit: Auditor,
+    Json(mut post): Json<PostNew>,
+) -> Result<Json<IdRes>> {
     if post.title.is_empty() {
         return Err(Error::BadRequest);
     }
-    let new_id = store.create(post).await?;
+    post.author_id = Some(user.id);
+    let new_id = store
+        .create(post, Some(audit.context(Some(&user), "create")))
+        .await?;
     Ok(Json(Response::new(IdData { id: new_id })))
 }
 
+/// Change publishing status of a Post
+///
+/// Move a Post between draft, scheduled, published and archived.
+/// Scheduling requires a `publish_at` in the future, and the Post is published when it arrives.
+#[utoipa::path(
+        put,
+        path = "/post/{id}/status",
+        params(
+            ("id" = i32, Path, description = "Post item id")
+        ),
+        request_body = PostTransition,
+        responses(
+            (status = 200, description = "Post status changed successfully", body = VoidRes)
+        ),
+        security(("token" = []))
+    )]
+pub async fn transit(
+    store: State<PostStore>,
+    CurrentUser(user): CurrentUser,
+    audit: Auditor,
+    Path(id): Path<i32>,
+    Json(to): Json<PostTransition>,
+) -> Result<Json<VoidRes>> {
+    check_post_authors(&store, &user, &[id]).await?;
+    store
+        .transition(id, to, Some(audit.context(Some(&user), "transit")))
+        .await?;
+    Ok(Json(ok_resp()))
+}
+
 /// Edit Post item value by id
 ///
 /// Edit Post item value by given id.
@@ -79,16 +193,26 @@ pub async fn create(store: State<PostStore>, Json(post): Json<PostNew>) -> Resul
         request_body = PostUpdate,
         responses(
             (status = 200, description = "Post item edited successfully", body = VoidRes)
-        )
+        ),
+        security(("token" = []))
     )]
-pub async fn edit(store: State<PostStore>, Json(post): Json<PostUpdate>) -> Result<Json<VoidRes>> {
-    store.update(post).await?;
+pub async fn edit(
+    store: State<PostStore>,
+    CurrentUser(user): CurrentUser,
+    audit: Auditor,
+    Json(post): Json<PostUpdate>,
+) -> Result<Json<VoidRes>> {
+    let id = post.id;
+    check_post_authors(&store, &user, &[id]).await?;
+    store
+        .update(post, Some(audit.context(Some(&user), "edit")))
+        .await?;
     Ok(Json(ok_resp()))
 }
 
 /// Delete Post items by id
 ///
-/// Delete Post items from database by comma-separated ids.
+/// Delete Post items from database by comma-separated ids, reporting those not found.
 #[utoipa::path(
         delete,
         path = "/post",
@@ -96,13 +220,253 @@ pub async fn edit(store: State<PostStore>, Json(post): Json<PostUpdate>) -> Resu
             PostDelete
         ),
         responses(
-            (status = 200, description = "Post items deleted successfully", body = VoidRes)
-        )
+            (status = 200, description = "Post items deleted successfully", body = DeleteRes)
+        ),
+        security(("token" = []))
     )]
 pub async fn delete(
     store: State<PostStore>,
+    attachment_store: State<AttachmentStore>,
+    CurrentUser(user): CurrentUser,
+    audit: Auditor,
     Query(params): Query<PostDelete>,
-) -> Result<Json<VoidRes>> {
-    store.delete(utils::get_ids_from_str(&params.ids)).await?;
-    Ok(Json(ok_resp()))
+) -> Result<Json<DeleteRes>> {
+    let ids = utils::get_ids_from_str(&params.ids);
+    check_post_authors(&store, &user, &ids).await?;
+    let paths = attachment_store.paths(ids.clone()).await?;
+    let report = store
+        .delete(ids, Some(audit.context(Some(&user), "delete")))
+        .await?;
+    if !report.deleted.is_empty() {
+        // attachments go with the Posts, their files only if no other Post shares them
+        for path in attachment_store.unused(paths).await? {
+            info!(?path, "remove attachment file");
+            fs::remove_file(path).await.ok();
+        }
+    }
+    Ok(Json(Response::new(report)))
+}
+
+/// Create Post items in bulk
+///
+/// Create Post items in one transaction, authored by the current User.
+/// In `all_or_nothing` mode any failure rolls back all items; in `best_effort` mode the failed items are reported.
+#[utoipa::path(
+        post,
+        path = "/post/bulk",
+        request_body = PostBulkNew,
+        responses(
+            (status = 200, description = "Post items created", body = BulkRes)
+        ),
+        security(("token" = []))
+    )]
+pub async fn bulk_create(
+    store: State<PostStore>,
+    CurrentUser(user): CurrentUser,
+    audit: Auditor,
+    Json(mut bulk): Json<PostBulkNew>,
+) -> Result<Json<BulkRes>> {
+    for post in bulk.items.iter_mut() {
+        post.author_id = Some(user.id);
+    }
+    let audit = audit.context(Some(&user), "bulk_create");
+    let ret = store
+        .create_many(bulk.items, bulk.mode, Some(audit))
+        .await?;
+    Ok(Json(Response::new(ret)))
+}
+
+/// Edit Post items in bulk
+///
+/// Edit Post items in one transaction; the current User must be allowed to edit all of them.
+/// In `all_or_nothing` mode any failure rolls back all items; in `best_effort` mode the failed items are reported.
+#[utoipa::path(
+        put,
+        path = "/post/bulk",
+        request_body = PostBulkUpdate,
+        responses(
+            (status = 200, description = "Post items edited", body = BulkRes)
+        ),
+        security(("token" = []))
+    )]
+pub async fn bulk_edit(
+    store: State<PostStore>,
+    CurrentUser(user): CurrentUser,
+    audit: Auditor,
+    Json(bulk): Json<PostBulkUpdate>,
+) -> Result<Json<BulkRes>> {
+    let ids: Vec<i32> = bulk.items.iter().map(|x| x.id).collect();
+    check_post_authors(&store, &user, &ids).await?;
+    let audit = audit.context(Some(&user), "bulk_edit");
+    let ret = store
+        .update_many(bulk.items, bulk.mode, Some(audit))
+        .await?;
+    Ok(Json(Response::new(ret)))
+}
+
+/// Post cache statistics
+///
+/// Show settings and hit/miss counters of the Post cache, for admins only.
+#[utoipa::path(
+        get,
+        path = "/post/cache",
+        responses(
+            (status = 200, description = "Post cache statistics", body = CacheStatsRes)
+        ),
+        security(("token" = []))
+    )]
+pub async fn cache_stats(
+    store: State<PostStore>,
+    CurrentUser(user): CurrentUser,
+) -> Result<Json<CacheStatsRes>> {
+    if !user.is_admin() {
+        return Err(Error::Forbidden);
+    }
+    Ok(Json(Response::new(store.cache_stats().unwrap_or_default())))
+}
+
+/// Query Post revisions
+///
+/// List revisions of a Post, latest first.
+/// Unpublished Posts are seen only by their authors and admins.
+#[utoipa::path(
+        get,
+        path = "/post/{id}/revisions",
+        params(
+            ("id" = i32, Path, description = "Post item id"),
+            RevisionQuery
+        ),
+        responses(
+            (status = 200, description = "List revisions of the Post", body = PostRevisionListRes)
+        )
+    )]
+pub async fn revisions(
+    store: State<PostStore>,
+    user: Option<CurrentUser>,
+    Path(id): Path<i32>,
+    Query(params): Query<RevisionQuery>,
+) -> Result<Json<PostRevisionListRes>> {
+    check_post_visible(&store, user.as_ref().map(|x| &x.0), id).await?;
+    let revisions = store.revisions(id, params).await?;
+    Ok(Json(Response::new(revisions.into())))
+}
+
+/// Get a Post revision
+///
+/// Get a revision of a Post by version.
+/// Unpublished Posts are seen only by their authors and admins.
+#[utoipa::path(
+        get,
+        path = "/post/{id}/revisions/{version}",
+        params(
+            ("id" = i32, Path, description = "Post item id"),
+            ("version" = i32, Path, description = "Post revision version")
+        ),
+        responses(
+            (status = 200, description = "Post revision fetch successfully", body = PostRevisionRes)
+        )
+    )]
+pub async fn revision(
+    store: State<PostStore>,
+    user: Option<CurrentUser>,
+    Path((id, version)): Path<(i32, i32)>,
+) -> Result<Json<PostRevisionRes>> {
+    check_post_visible(&store, user.as_ref().map(|x| &x.0), id).await?;
+    let revision = store.revision(id, version).await?;
+    Ok(Json(Response::new(revision)))
+}
+
+/// Diff two Post revisions
+///
+/// Show line diff of title and content between two revisions of a Post.
+/// Unpublished Posts are seen only by their authors and admins.
+#[utoipa::path(
+        get,
+        path = "/post/{id}/revisions/diff",
+        params(
+            ("id" = i32, Path, description = "Post item id"),
+            RevisionDiffQuery
+        ),
+        responses(
+            (status = 200, description = "Post revisions compared successfully", body = RevisionDiffRes)
+        )
+    )]
+pub async fn diff(
+    store: State<PostStore>,
+    user: Option<CurrentUser>,
+    Path(id): Path<i32>,
+    Query(params): Query<RevisionDiffQuery>,
+) -> Result<Json<RevisionDiffRes>> {
+    check_post_visible(&store, user.as_ref().map(|x| &x.0), id).await?;
+    let old = store.revision(id, params.from).await?;
+    let new = store.revision(id, params.to).await?;
+    Ok(Json(Response::new(RevisionDiff {
+        post_id: id,
+        from: params.from,
+        to: params.to,
+        title: utils::diff_lines(&old.title, &new.title),
+        content: utils::diff_lines(&old.content, &new.content),
+    })))
+}
+
+/// Revert a Post to a revision
+///
+/// Restore title and content of a Post from an earlier revision, which is recorded as a new revision.
+#[utoipa::path(
+        post,
+        path = "/post/{id}/revisions/{version}/revert",
+        params(
+            ("id" = i32, Path, description = "Post item id"),
+            ("version" = i32, Path, description = "Post revision version to restore")
+        ),
+        responses(
+            (status = 200, description = "Post reverted successfully", body = VersionRes)
+        ),
+        security(("token" = []))
+    )]
+pub async fn revert(
+    store: State<PostStore>,
+    CurrentUser(user): CurrentUser,
+    audit: Auditor,
+    Path((id, version)): Path<(i32, i32)>,
+) -> Result<Json<VersionRes>> {
+    check_post_authors(&store, &user, &[id]).await?;
+    let version = store
+        .revert(id, version, Some(audit.context(Some(&user), "revert")))
+        .await?;
+    Ok(Json(Response::new(VersionData { version })))
+}
+
+/// Fetch the Post if published, or if the User may edit it
+async fn fetch_visible(
+    store: &PostStore,
+    user: Option<CurrentUser>,
+    id: i32,
+    viewer: Option<String>,
+) -> Result<Post> {
+    match store.fetch(id, viewer).await {
+        Err(Error::IdNotFound { .. }) if user.is_some() => {
+            let post = store.fetch_unpublished(id).await?;
+            match user {
+                Some(CurrentUser(u)) if u.can_edit(post.author_id) => Ok(post),
+                // not to tell drafts from missing Posts
+                _ => Err(Error::IdNotFound { id }),
+            }
+        }
+        ret => ret,
+    }
+}
+
+/// Fill in rendered content of the Posts if asked
+fn render_content(posts: &mut [Post], format: Option<ContentFormat>) {
+    if format != Some(ContentFormat::Html) {
+        return;
+    }
+    for p in posts {
+        p.rendered = Some(utils::markdown::render_cached(
+            p.id,
+            p.updated_at,
+            &p.content,
+        ));
+    }
 }
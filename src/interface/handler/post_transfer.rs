@@ -0,0 +1,337 @@
+use std::{io, mem};
+
+use axum::{
+    body::{Body, Bytes},
+    extract::{Query, State},
+    http::header,
+    response::IntoResponse,
+    Json,
+};
+use futures::{stream, StreamExt, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt};
+use tokio_util::io::StreamReader;
+
+use crate::{
+    app::{log::*, utils},
+    entity::{DateTimeTZ, Post, PostStatus},
+    infrastructure::persistence::PostStore,
+    interface::{
+        dto::{DataFormat, TransferQuery},
+        extract::{Auditor, CurrentUser},
+        resp::*,
+    },
+    repository::{AuditContext, BulkMode, Error, PostNew, PostQuery, Result},
+};
+
+/// Posts read from database per round when exporting
+const EXPORT_BATCH: u64 = 500;
+/// Posts inserted per transaction when importing
+const IMPORT_BATCH: usize = 100;
+/// Failed rows reported in detail
+const MAX_IMPORT_ERRORS: usize = 100;
+
+/// Export Post items
+///
+/// Stream Post items matching the query as NDJSON or CSV.
+/// Admins export Posts of all statuses, others only published ones.
+#[utoipa::path(
+        get,
+        path = "/post/export",
+        params(
+            PostQuery,
+            TransferQuery
+        ),
+        responses(
+            (status = 200, description = "Post items streamed", content_type = ["application/x-ndjson", "text/csv"])
+        )
+    )]
+pub async fn export(
+    store: State<PostStore>,
+    user: Option<CurrentUser>,
+    Query(mut params): Query<PostQuery>,
+    Query(transfer): Query<TransferQuery>,
+) -> impl IntoResponse {
+    if !matches!(user, Some(CurrentUser(u)) if u.is_admin()) {
+        params.status = Some(PostStatus::Published);
+    }
+    let format = transfer.format.unwrap_or_default();
+    info!(?params, ?format, "export posts");
+    let store = store.0;
+    let batches = stream::try_unfold(Some(0), move |after| {
+        let store = store.clone();
+        let params = params.clone();
+        async move {
+            let Some(after) = after else {
+                return Ok(None);
+            };
+            let posts = store.scan(params, after, EXPORT_BATCH).await?;
+            let next = match posts.last() {
+                Some(p) if posts.len() as u64 == EXPORT_BATCH => Some(p.id),
+                _ => None,
+            };
+            Result::Ok(Some((posts, next)))
+        }
+    });
+    let (content_type, filename, body) = match format {
+        DataFormat::Ndjson => (
+            "application/x-ndjson",
+            "posts.ndjson",
+            Body::from_stream(batches.map(|x| x.and_then(|posts| to_ndjson(&posts)))),
+        ),
+        DataFormat::Csv => {
+            let header = stream::once(async { csv_header() });
+            let rows = batches.map(|x| x.and_then(|posts| to_csv(&posts)));
+            (
+                "text/csv",
+                "posts.csv",
+                Body::from_stream(header.chain(rows)),
+            )
+        }
+    };
+    (
+        [
+            (header::CONTENT_TYPE, content_type.to_owned()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!(r#"attachment; filename="{filename}""#),
+            ),
+        ],
+        body,
+    )
+}
+
+/// Import Post items
+///
+/// Stream NDJSON or CSV Post records in the request body, authored by the current User.
+/// Records are validated and inserted in batches; blank rows are skipped and failed rows are reported.
+/// CSV columns are `title`, `slug`, `content`, `tags` (comma-separated), `status` and `publish_at`.
+/// Status and publish time are kept as exported, so archived Posts import as well.
+#[utoipa::path(
+        post,
+        path = "/post/import",
+        params(
+            TransferQuery
+        ),
+        request_body(content = String, description = "NDJSON or CSV records", content_type = "application/x-ndjson"),
+        responses(
+            (status = 200, description = "Post items imported", body = ImportRes)
+        ),
+        security(("token" = []))
+    )]
+pub async fn import(
+    store: State<PostStore>,
+    CurrentUser(user): CurrentUser,
+    audit: Auditor,
+    Query(transfer): Query<TransferQuery>,
+    body: Body,
+) -> Result<Json<ImportRes>> {
+    let format = transfer.format.unwrap_or_default();
+    info!(?format, user = user.id, "import posts");
+    let reader = StreamReader::new(body.into_data_stream().map_err(io::Error::other));
+    let mut importer = Importer {
+        store: store.0,
+        author_id: user.id,
+        audit: audit.context(Some(&user), "import"),
+        batch: Vec::with_capacity(IMPORT_BATCH),
+        summary: ImportSummary::default(),
+    };
+    match format {
+        DataFormat::Ndjson => importer.read_ndjson(reader).await?,
+        DataFormat::Csv => importer.read_csv(reader).await?,
+    }
+    importer.flush().await?;
+    info!(
+        inserted = importer.summary.inserted,
+        skipped = importer.summary.skipped,
+        failed = importer.summary.failed,
+        "posts imported"
+    );
+    Ok(Json(Response::new(importer.summary)))
+}
+
+/// Flat Post row of CSV export
+#[derive(Serialize)]
+struct CsvPost<'a> {
+    id: i32,
+    title: &'a str,
+    slug: &'a str,
+    content: &'a str,
+    tags: String,
+    status: PostStatus,
+    #[serde(with = "utils::mtime::option")]
+    publish_at: Option<DateTimeTZ>,
+    author_id: Option<i32>,
+    views: i32,
+    #[serde(with = "utils::mtime")]
+    created_at: DateTimeTZ,
+    #[serde(with = "utils::mtime")]
+    updated_at: DateTimeTZ,
+}
+
+/// Post record of CSV import
+#[derive(Deserialize)]
+struct CsvRecord {
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    content: String,
+    slug: Option<String>,
+    tags: Option<String>,
+    status: Option<PostStatus>,
+    #[serde(default, with = "utils::mtime::option")]
+    publish_at: Option<DateTimeTZ>,
+}
+
+impl From<CsvRecord> for PostNew {
+    fn from(value: CsvRecord) -> Self {
+        Self {
+            title: value.title,
+            content: value.content,
+            slug: value.slug,
+            tags: value
+                .tags
+                .map(|v| v.split(',').map(str::to_owned).collect())
+                .unwrap_or_default(),
+            status: value.status,
+            publish_at: value.publish_at,
+            author_id: None,
+            imported: true,
+        }
+    }
+}
+
+fn to_ndjson(posts: &[Post]) -> Result<Bytes> {
+    let mut buf = Vec::new();
+    for p in posts {
+        serde_json::to_writer(&mut buf, p)?;
+        buf.push(b'\n');
+    }
+    Ok(buf.into())
+}
+
+fn csv_header() -> Result<Bytes> {
+    Ok(Bytes::from_static(
+        b"id,title,slug,content,tags,status,publish_at,author_id,views,created_at,updated_at\n",
+    ))
+}
+
+fn to_csv(posts: &[Post]) -> Result<Bytes> {
+    let mut w = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(Vec::new());
+    for p in posts {
+        w.serialize(CsvPost {
+            id: p.id,
+            title: &p.title,
+            slug: &p.slug,
+            content: &p.content,
+            tags: p.tags.join(","),
+            status: p.status,
+            publish_at: p.publish_at,
+            author_id: p.author_id,
+            views: p.views,
+            created_at: p.created_at,
+            updated_at: p.updated_at,
+        })
+        .map_err(|e| Error::EncodingError(e.to_string()))?;
+    }
+    let buf = w
+        .into_inner()
+        .map_err(|e| Error::EncodingError(e.to_string()))?;
+    Ok(buf.into())
+}
+
+/// Collect imported records into batches and insert them
+struct Importer {
+    store: PostStore,
+    author_id: i32,
+    /// recorded with each batch inserted
+    audit: AuditContext,
+    /// pending records with their line numbers
+    batch: Vec<(u64, PostNew)>,
+    summary: ImportSummary,
+}
+
+impl Importer {
+    async fn read_ndjson<R: AsyncBufRead + Unpin>(&mut self, reader: R) -> Result<()> {
+        let mut lines = reader.lines();
+        let mut line = 0;
+        while let Some(text) = lines.next_line().await? {
+            line += 1;
+            if text.trim().is_empty() {
+                self.summary.skipped += 1;
+                continue;
+            }
+            match serde_json::from_str::<PostNew>(&text) {
+                Ok(post) => self.push(line, post).await?,
+                Err(e) => self.fail(line, e.to_string()),
+            }
+        }
+        Ok(())
+    }
+
+    async fn read_csv<R: tokio::io::AsyncRead + Unpin + Send>(&mut self, reader: R) -> Result<()> {
+        let mut rdr = csv_async::AsyncReaderBuilder::new()
+            .flexible(true)
+            .create_deserializer(reader);
+        let mut records = rdr.deserialize_with_pos::<CsvRecord>();
+        while let Some((record, pos)) = records.next().await {
+            match record {
+                Ok(record) => self.push(pos.line(), record.into()).await?,
+                Err(e) => {
+                    if let csv_async::ErrorKind::Io(e) = e.kind() {
+                        return Err(Error::IoError(e.to_string()));
+                    }
+                    self.fail(pos.line(), e.to_string());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn push(&mut self, line: u64, mut post: PostNew) -> Result<()> {
+        if post.title.trim().is_empty() {
+            if post.content.trim().is_empty() && post.tags.is_empty() {
+                self.summary.skipped += 1;
+            } else {
+                self.fail(line, "title is empty".to_owned());
+            }
+            return Ok(());
+        }
+        post.author_id = Some(self.author_id);
+        post.imported = true;
+        self.batch.push((line, post));
+        if self.batch.len() >= IMPORT_BATCH {
+            self.flush().await?;
+        }
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> Result<()> {
+        if self.batch.is_empty() {
+            return Ok(());
+        }
+        let (lines, posts): (Vec<u64>, Vec<PostNew>) =
+            mem::take(&mut self.batch).into_iter().unzip();
+        let items = self
+            .store
+            .create_many(posts, BulkMode::BestEffort, Some(self.audit.clone()))
+            .await?;
+        for item in items {
+            match item.error {
+                None => self.summary.inserted += 1,
+                Some(reason) => self.fail(lines[item.index], reason),
+            }
+        }
+        Ok(())
+    }
+
+    fn fail(&mut self, line: u64, reason: String) {
+        debug!(?line, ?reason, "import row failed");
+        self.summary.failed += 1;
+        if self.summary.errors.len() < MAX_IMPORT_ERRORS {
+            self.summary.errors.push(ImportError { line, reason });
+        }
+    }
+}
@@ -1,15 +1,10 @@
-use std::io;
+use std::path::Path;
 
 use axum::{
     extract::{Multipart, State},
     Json,
 };
-use futures::TryStreamExt;
-use tokio::{
-    fs::{self, File},
-    io::BufWriter,
-};
-use tokio_util::io::StreamReader;
+use tokio::fs;
 
 use crate::{
     app::{log::*, utils},
@@ -18,7 +13,7 @@ use crate::{
     repository::{Error, Result},
 };
 
-use super::to_raw_resp;
+use super::{save_field, to_raw_resp};
 
 /// Parse xls File
 ///
@@ -36,7 +31,6 @@ pub async fn parse(
     mut multipart: Multipart,
 ) -> Result<Json<ObjectRes>> {
     let filename = utils::get_uuid_str() + ".xls";
-    let mut created_file = false;
     let mut file_size = 0;
     while let Some(field) = multipart.next_field().await? {
         if matches!(field.name(), Some("file")) {
@@ -45,21 +39,12 @@ pub async fn parse(
             //     filename = utils::get_uuid_str() + ".xls";
             //     fs::write(&filename, data).await?;
             // }
-            // stream to file, save lots of memory if file is big
-            let body_with_io_error = field.map_err(|err| io::Error::new(io::ErrorKind::Other, err));
-            let body_reader = StreamReader::new(body_with_io_error);
-            futures::pin_mut!(body_reader);
-
-            let mut file = BufWriter::new(File::create(&filename).await?);
-            created_file = true;
-            file_size = tokio::io::copy(&mut body_reader, &mut file).await?;
+            (file_size, _) = save_field(field, Path::new(&filename), None).await?;
             break;
         }
     }
     if file_size == 0 {
-        if created_file {
-            fs::remove_file(filename).await.ok();
-        }
+        fs::remove_file(filename).await.ok();
         return Err(Error::BadRequest);
     }
     info!(?filename, "processing xls file");
@@ -0,0 +1,18 @@
+use axum::{extract::State, Json};
+
+use crate::{infrastructure::persistence::TagStore, interface::resp::*, repository::Result};
+
+/// List Tags
+///
+/// List all Tags with the number of Posts using each.
+#[utoipa::path(
+        get,
+        path = "/tag",
+        responses(
+            (status = 200, description = "List Tags with Post counts", body = TagListRes)
+        )
+    )]
+pub async fn list(store: State<TagStore>) -> Result<Json<TagListRes>> {
+    let tags = store.list().await?;
+    Ok(Json(Response::new(tags)))
+}
@@ -7,15 +7,20 @@ use crate::{
     app::utils,
     entity::Todo,
     infrastructure::persistence::TodoStore,
-    interface::resp::*,
-    repository::{Error, Result, TodoDelete, TodoQuery, TodoUpdate},
+    interface::{
+        extract::{Auditor, CurrentUser},
+        resp::*,
+    },
+    repository::{
+        Error, Result, TodoDelete, TodoDoneQuery, TodoMove, TodoQuery, TodoReorder, TodoUpdate,
+    },
 };
 
 use super::ok_resp;
 
 /// Query Todo items
 ///
-/// Query Todo items from in-memory storage.
+/// Query a page of Todo items from storage, in manual order unless sorted by another key.
 #[utoipa::path(
         get,
         path = "/todo",
@@ -30,13 +35,13 @@ pub async fn list(
     store: State<TodoStore>,
     Query(params): Query<TodoQuery>,
 ) -> Result<Json<TodoListRes>> {
-    let todos = store.query(params)?;
-    Ok(Json(Response::new(todos)))
+    let todos = store.query(params).await?;
+    Ok(Json(Response::new(todos.into())))
 }
 
 /// Create new Todo
 ///
-/// Try to create a new Todo item to in-memory storage.
+/// Try to create a new Todo item to storage.
 #[utoipa::path(
         post,
         path = "/todo",
@@ -45,39 +50,136 @@ pub async fn list(
             (status = 200, description = "Todo item created successfully", body = IdRes)
         )
     )]
-pub async fn create(store: State<TodoStore>, Json(todo): Json<Todo>) -> Result<Json<IdRes>> {
+pub async fn create(
+    store: State<TodoStore>,
+    user: Option<CurrentUser>,
+    audit: Auditor,
+    Json(todo): Json<Todo>,
+) -> Result<Json<IdRes>> {
     if todo.value.is_empty() {
         return Err(Error::BadRequest);
     }
-    let new_id = store.create(todo)?;
+    let audit = audit.context(user.as_ref().map(|u| &u.0), "create");
+    let (new_id, _) = store.create(todo, Some(audit)).await?;
     Ok(Json(Response::new(IdData { id: new_id })))
 }
 
 /// Mark Todo item done by id
 ///
-/// Mark Todo item done by given id.
+/// Mark Todo item done by given id, with all its subtasks if `cascade` is set.
+/// Parents set to auto-complete are done once all their subtasks are.
 #[utoipa::path(
         put,
         path = "/todo/{id}",
         params(
-            ("id" = i32, Path, description = "Todo item id")
+            ("id" = i32, Path, description = "Todo item id"),
+            TodoDoneQuery
         ),
         responses(
             (status = 200, description = "Todo marked done successfully", body = VoidRes)
         )
     )]
-pub async fn mark_done(store: State<TodoStore>, Path(id): Path<i32>) -> Result<Json<VoidRes>> {
-    store.update(TodoUpdate {
-        id,
-        value: None,
-        done: Some(true),
-    })?;
+pub async fn mark_done(
+    store: State<TodoStore>,
+    user: Option<CurrentUser>,
+    audit: Auditor,
+    Path(id): Path<i32>,
+    Query(params): Query<TodoDoneQuery>,
+) -> Result<Json<VoidRes>> {
+    let audit = audit.context(user.as_ref().map(|u| &u.0), "mark_done");
+    store
+        .update(
+            TodoUpdate {
+                id,
+                value: None,
+                done: Some(true),
+                due_at: None,
+                priority: None,
+                auto_complete: None,
+                cascade: params.cascade.unwrap_or_default(),
+            },
+            Some(audit),
+        )
+        .await?;
+    Ok(Json(ok_resp()))
+}
+
+/// Move Todo item in the manual order
+///
+/// Move Todo item to a new position from 1, shifting the items between in one step.
+/// Positions beyond the list move it to the end.
+#[utoipa::path(
+        put,
+        path = "/todo/{id}/position",
+        params(
+            ("id" = i32, Path, description = "Todo item id")
+        ),
+        request_body = TodoReorder,
+        responses(
+            (status = 200, description = "Todo moved successfully", body = VoidRes)
+        )
+    )]
+pub async fn reorder(
+    store: State<TodoStore>,
+    user: Option<CurrentUser>,
+    audit: Auditor,
+    Path(id): Path<i32>,
+    Json(to): Json<TodoReorder>,
+) -> Result<Json<VoidRes>> {
+    let audit = audit.context(user.as_ref().map(|u| &u.0), "reorder");
+    store.reorder(id, to.position, Some(audit)).await?;
+    Ok(Json(ok_resp()))
+}
+
+/// Get Todo item tree
+///
+/// Get Todo item by id with its subtasks at any depth, and the progress of each.
+#[utoipa::path(
+        get,
+        path = "/todo/{id}/tree",
+        params(
+            ("id" = i32, Path, description = "Todo item id")
+        ),
+        responses(
+            (status = 200, description = "Todo item with its subtasks", body = TodoTreeRes)
+        )
+    )]
+pub async fn tree(store: State<TodoStore>, Path(id): Path<i32>) -> Result<Json<TodoTreeRes>> {
+    let todos = store.subtree(id).await?;
+    let tree = TodoNode::tree(todos).ok_or(Error::IdNotFound { id })?;
+    Ok(Json(Response::new(tree)))
+}
+
+/// Move Todo item under another parent
+///
+/// Move Todo item with its subtasks under another Todo, or to top level.
+/// It cannot go under itself or one of its subtasks.
+#[utoipa::path(
+        put,
+        path = "/todo/{id}/parent",
+        params(
+            ("id" = i32, Path, description = "Todo item id")
+        ),
+        request_body = TodoMove,
+        responses(
+            (status = 200, description = "Todo moved successfully", body = VoidRes)
+        )
+    )]
+pub async fn reparent(
+    store: State<TodoStore>,
+    user: Option<CurrentUser>,
+    audit: Auditor,
+    Path(id): Path<i32>,
+    Json(to): Json<TodoMove>,
+) -> Result<Json<VoidRes>> {
+    let audit = audit.context(user.as_ref().map(|u| &u.0), "reparent");
+    store.reparent(id, to.parent_id, Some(audit)).await?;
     Ok(Json(ok_resp()))
 }
 
 /// Edit Todo item value by id
 ///
-/// Edit Todo item value by given id.
+/// Edit value, done status, due time or priority of Todo item by given id.
 #[utoipa::path(
         put,
         path = "/todo",
@@ -86,14 +188,20 @@ pub async fn mark_done(store: State<TodoStore>, Path(id): Path<i32>) -> Result<J
             (status = 200, description = "Todo marked done successfully", body = VoidRes)
         )
     )]
-pub async fn edit(store: State<TodoStore>, Json(todo): Json<TodoUpdate>) -> Result<Json<VoidRes>> {
-    store.update(todo)?;
+pub async fn edit(
+    store: State<TodoStore>,
+    user: Option<CurrentUser>,
+    audit: Auditor,
+    Json(todo): Json<TodoUpdate>,
+) -> Result<Json<VoidRes>> {
+    let audit = audit.context(user.as_ref().map(|u| &u.0), "edit");
+    store.update(todo, Some(audit)).await?;
     Ok(Json(ok_resp()))
 }
 
 /// Delete Todo items by id
 ///
-/// Delete Todo items from in-memory storage by comma-separated ids.
+/// Delete Todo items with all their subtasks from storage by comma-separated ids.
 #[utoipa::path(
         delete,
         path = "/todo",
@@ -106,8 +214,12 @@ pub async fn edit(store: State<TodoStore>, Json(todo): Json<TodoUpdate>) -> Resu
     )]
 pub async fn delete(
     store: State<TodoStore>,
+    user: Option<CurrentUser>,
+    audit: Auditor,
     Query(params): Query<TodoDelete>,
 ) -> Result<Json<VoidRes>> {
-    store.delete(utils::get_ids_from_str(&params.ids))?;
+    let ids = utils::get_ids_from_str(&params.ids);
+    let audit = audit.context(user.as_ref().map(|u| &u.0), "delete");
+    store.delete(ids, Some(audit)).await?;
     Ok(Json(ok_resp()))
 }
@@ -0,0 +1,68 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+
+use crate::{
+    infrastructure::persistence::UserStore,
+    interface::{extract::CurrentUser, resp::*},
+    repository::{Error, Result, UserNew},
+};
+
+/// Create new User
+///
+/// Register a new User and get the token to authenticate as it.
+/// Creating an admin requires an admin caller, unless there are no Users yet.
+#[utoipa::path(
+        post,
+        path = "/user",
+        request_body = UserNew,
+        responses(
+            (status = 200, description = "User created successfully", body = UserTokenRes)
+        )
+    )]
+pub async fn create(
+    store: State<UserStore>,
+    caller: Option<CurrentUser>,
+    Json(user): Json<UserNew>,
+) -> Result<Json<UserTokenRes>> {
+    if user.name.is_empty() {
+        return Err(Error::BadRequest);
+    }
+    let by_admin = matches!(&caller, Some(CurrentUser(c)) if c.is_admin());
+    let (user, token) = store.create(user, by_admin).await?;
+    Ok(Json(Response::new(UserToken { id: user.id, token })))
+}
+
+/// Get current User
+///
+/// Get the User authenticated by the request token.
+#[utoipa::path(
+        get,
+        path = "/user/me",
+        responses(
+            (status = 200, description = "Current User fetch successfully", body = UserRes)
+        ),
+        security(("token" = []))
+    )]
+pub async fn me(CurrentUser(user): CurrentUser) -> Result<Json<UserRes>> {
+    Ok(Json(Response::new(user)))
+}
+
+/// Get a User
+///
+/// Get a User by id.
+#[utoipa::path(
+        get,
+        path = "/user/{id}",
+        params(
+            ("id" = i32, Path, description = "User id")
+        ),
+        responses(
+            (status = 200, description = "User fetch successfully", body = UserRes)
+        )
+    )]
+pub async fn get(store: State<UserStore>, Path(id): Path<i32>) -> Result<Json<UserRes>> {
+    let user = store.fetch(id).await?;
+    Ok(Json(Response::new(user)))
+}
@@ -0,0 +1,127 @@
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+
+use crate::{
+    entity::User,
+    event::EVENT_KINDS,
+    infrastructure::persistence::WebhookStore,
+    interface::{extract::CurrentUser, resp::*},
+    repository::{DeliveryQuery, Error, Result, WebhookNew},
+};
+
+use super::ok_resp;
+
+/// List webhooks
+///
+/// List all webhook subscriptions, for admins only.
+#[utoipa::path(
+        get,
+        path = "/webhook",
+        responses(
+            (status = 200, description = "List webhooks", body = WebhookListRes)
+        ),
+        security(("token" = []))
+    )]
+pub async fn list(
+    store: State<WebhookStore>,
+    CurrentUser(user): CurrentUser,
+) -> Result<Json<WebhookListRes>> {
+    check_admin(&user)?;
+    let hooks = store.list().await?;
+    Ok(Json(Response::new(hooks)))
+}
+
+/// Create new webhook
+///
+/// Subscribe a url to domain events, for admins only.
+/// Each event is POSTed as JSON, with headers `X-Webhook-Event` of the event kind,
+/// `X-Webhook-Event-Id`, `X-Webhook-Timestamp` of unix seconds, and `X-Webhook-Signature`
+/// of `sha256=` and the hex HMAC-SHA256 of `{timestamp}.{body}` keyed by the secret.
+/// Failed deliveries are retried with backoff.
+#[utoipa::path(
+        post,
+        path = "/webhook",
+        request_body = WebhookNew,
+        responses(
+            (status = 200, description = "Webhook created successfully", body = WebhookSecretRes)
+        ),
+        security(("token" = []))
+    )]
+pub async fn create(
+    store: State<WebhookStore>,
+    CurrentUser(user): CurrentUser,
+    Json(hook): Json<WebhookNew>,
+) -> Result<Json<WebhookSecretRes>> {
+    check_admin(&user)?;
+    let valid_url = hook.url.starts_with("http://") || hook.url.starts_with("https://");
+    if !valid_url
+        || hook.secret.as_ref().is_some_and(|v| v.is_empty())
+        || hook
+            .events
+            .iter()
+            .any(|v| !EVENT_KINDS.contains(&v.as_str()))
+    {
+        return Err(Error::BadRequest);
+    }
+    let hook = store.create(hook).await?;
+    Ok(Json(Response::new(WebhookSecret {
+        id: hook.id,
+        secret: hook.secret,
+    })))
+}
+
+/// Delete webhook
+///
+/// Delete a webhook subscription with its delivery log, for admins only.
+#[utoipa::path(
+        delete,
+        path = "/webhook/{id}",
+        params(
+            ("id" = i32, Path, description = "Webhook id")
+        ),
+        responses(
+            (status = 200, description = "Webhook deleted successfully", body = VoidRes)
+        ),
+        security(("token" = []))
+    )]
+pub async fn delete(
+    store: State<WebhookStore>,
+    CurrentUser(user): CurrentUser,
+    Path(id): Path<i32>,
+) -> Result<Json<VoidRes>> {
+    check_admin(&user)?;
+    store.delete(id).await?;
+    Ok(Json(ok_resp()))
+}
+
+/// Query webhook deliveries
+///
+/// Query the delivery log of webhooks, newest first, for admins only.
+#[utoipa::path(
+        get,
+        path = "/webhook/deliveries",
+        params(DeliveryQuery),
+        responses(
+            (status = 200, description = "List webhook deliveries", body = WebhookDeliveryListRes)
+        ),
+        security(("token" = []))
+    )]
+pub async fn deliveries(
+    store: State<WebhookStore>,
+    CurrentUser(user): CurrentUser,
+    Query(params): Query<DeliveryQuery>,
+) -> Result<Json<WebhookDeliveryListRes>> {
+    check_admin(&user)?;
+    let deliveries = store.deliveries(params).await?;
+    Ok(Json(Response::new(deliveries.into())))
+}
+
+fn check_admin(user: &User) -> Result<()> {
+    if user.is_admin() {
+        Ok(())
+    } else {
+        Err(Error::Forbidden)
+    }
+}
@@ -1,4 +1,8 @@
 pub mod dto;
+pub mod extract;
 pub mod handler;
 pub mod resp;
 pub mod route;
+
+#[cfg(test)]
+mod tests;
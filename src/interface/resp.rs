@@ -1,14 +1,24 @@
+use std::collections::HashMap;
+
 use serde::Serialize;
 use utoipa::ToSchema;
 
-use crate::entity::*;
+use crate::{
+    app::utils::DiffLine,
+    entity::*,
+    repository::{BulkItem, CacheStats, DeleteReport, TagCount},
+};
 
 const STATUS_OK: i32 = 200;
 const STATUS_BAD: i32 = 400;
+const STATUS_UNAUTHORIZED: i32 = 401;
+const STATUS_FORBIDDEN: i32 = 403;
 const STATUS_ERR: i32 = 500;
 
 const MSG_OK: &str = "ok";
 const MSG_BAD: &str = "bad request";
+const MSG_UNAUTHORIZED: &str = "unauthorized";
+const MSG_FORBIDDEN: &str = "forbidden";
 const MSG_ERR: &str = "error";
 
 #[derive(Serialize, ToSchema)]
@@ -19,8 +29,107 @@ pub struct IdData {
     pub id: i32,
 }
 
+/// Newly created User with the token to authenticate as it
+#[derive(Serialize, ToSchema)]
+pub struct UserToken {
+    pub id: i32,
+    pub token: String,
+}
+
+/// Newly created webhook with the secret to verify its signatures
+#[derive(Serialize, ToSchema)]
+pub struct WebhookSecret {
+    pub id: i32,
+    pub secret: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct VersionData {
+    pub version: i32,
+}
+
+/// Line diff between two revisions of a Post
+#[derive(Serialize, ToSchema)]
+pub struct RevisionDiff {
+    pub post_id: i32,
+    pub from: i32,
+    pub to: i32,
+    pub title: Vec<DiffLine>,
+    pub content: Vec<DiffLine>,
+}
+
+/// Rejected row of a Post import
+#[derive(Serialize, ToSchema)]
+pub struct ImportError {
+    /// line number in the uploaded data, starting from 1
+    pub line: u64,
+    pub reason: String,
+}
+
+/// Outcome of a Post import
+#[derive(Default, Serialize, ToSchema)]
+pub struct ImportSummary {
+    pub inserted: u64,
+    /// blank rows
+    pub skipped: u64,
+    pub failed: u64,
+    /// reasons of the first failed rows
+    pub errors: Vec<ImportError>,
+}
+
+/// Todo with its subtasks, in manual order
+#[derive(Serialize, ToSchema)]
+pub struct TodoNode {
+    #[serde(flatten)]
+    pub todo: Todo,
+    /// of its direct subtasks, None without any
+    pub progress: Option<TodoProgress>,
+    pub children: Vec<TodoNode>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct TodoProgress {
+    pub done: usize,
+    pub total: usize,
+}
+
+impl TodoNode {
+    /// Tree of a Todo and its subtasks, listed parents first
+    pub fn tree(todos: Vec<Todo>) -> Option<Self> {
+        let mut todos = todos.into_iter();
+        let root = todos.next()?;
+        let mut children: HashMap<i32, Vec<Todo>> = HashMap::new();
+        for x in todos {
+            if let Some(parent) = x.parent_id {
+                children.entry(parent).or_default().push(x);
+            }
+        }
+        Some(Self::node(root, &mut children))
+    }
+
+    fn node(todo: Todo, children: &mut HashMap<i32, Vec<Todo>>) -> Self {
+        let mut subtasks = children.remove(&todo.id).unwrap_or_default();
+        subtasks.sort_unstable_by_key(|x| (x.position, x.id));
+        let subtasks: Vec<Self> = subtasks
+            .into_iter()
+            .map(|x| Self::node(x, children))
+            .collect();
+        let progress = (!subtasks.is_empty()).then(|| TodoProgress {
+            done: subtasks.iter().filter(|x| x.todo.done).count(),
+            total: subtasks.len(),
+        });
+        Self {
+            todo,
+            progress,
+            children: subtasks,
+        }
+    }
+}
+
 #[derive(Serialize, ToSchema)]
-#[aliases(PostList = ListData<Post>)]
+#[aliases(TodoList = ListData<Todo>, PostList = ListData<Post>, PostRevisionList = ListData<PostRevision>,
+    CommentList = ListData<Comment>, WebhookDeliveryList = ListData<WebhookDelivery>,
+    AuditLogList = ListData<AuditLog>)]
 pub struct ListData<T> {
     pub list: Vec<T>,
     pub total: u64,
@@ -29,10 +138,21 @@ pub struct ListData<T> {
 #[derive(Serialize, ToSchema)]
 #[aliases(VoidRes = Response<Void>, IdRes = Response<IdData>,
      ObjectRes = Response<Box<serde_json::value::RawValue>>,
-     TodoRes = Response<Todo>, TodoListRes = Response<Vec<Todo>>,
-     PostRes = Response<Post>, PostListRes = Response<PostList>)]
+     TodoRes = Response<Todo>, TodoListRes = Response<TodoList>,
+     TodoTreeRes = Response<TodoNode>,
+     PostRes = Response<Post>, PostListRes = Response<PostList>,
+     VersionRes = Response<VersionData>, PostRevisionRes = Response<PostRevision>,
+     PostRevisionListRes = Response<PostRevisionList>, RevisionDiffRes = Response<RevisionDiff>,
+     TagListRes = Response<Vec<TagCount>>, CommentListRes = Response<CommentList>,
+     UserRes = Response<User>, UserTokenRes = Response<UserToken>,
+     DeleteRes = Response<DeleteReport>, BulkRes = Response<Vec<BulkItem>>,
+     ImportRes = Response<ImportSummary>, AttachmentListRes = Response<Vec<Attachment>>,
+     CacheStatsRes = Response<CacheStats>, WebhookListRes = Response<Vec<Webhook>>,
+     WebhookSecretRes = Response<WebhookSecret>,
+     WebhookDeliveryListRes = Response<WebhookDeliveryList>,
+     AuditLogListRes = Response<AuditLogList>)]
 pub struct Response<T> {
-    /// response code: 200 - ok; 400 - bad request; 500 - error
+    /// response code: 200 - ok; 400 - bad request; 401 - unauthorized; 403 - forbidden; 500 - error
     #[schema(example = 200)]
     code: i32,
     #[schema(example = "ok")]
@@ -57,6 +177,30 @@ impl<T: Serialize> Response<T> {
         }
     }
 
+    pub fn new_bad_msg(data: T, msg: String) -> Self {
+        Self {
+            code: STATUS_BAD,
+            msg,
+            data,
+        }
+    }
+
+    pub fn new_unauthorized(data: T) -> Self {
+        Self {
+            code: STATUS_UNAUTHORIZED,
+            msg: MSG_UNAUTHORIZED.to_owned(),
+            data,
+        }
+    }
+
+    pub fn new_forbidden(data: T) -> Self {
+        Self {
+            code: STATUS_FORBIDDEN,
+            msg: MSG_FORBIDDEN.to_owned(),
+            data,
+        }
+    }
+
     pub fn new_err(data: T, msg: String) -> Self {
         Self {
             code: STATUS_ERR,
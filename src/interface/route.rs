@@ -1,9 +1,18 @@
-use std::net::Ipv4Addr;
-
-use axum::{extract::DefaultBodyLimit, routing, Router};
+use std::net::{Ipv4Addr, SocketAddr};
+
+use axum::{
+    extract::{DefaultBodyLimit, Request},
+    http::Method,
+    middleware::{self, Next},
+    response::Response,
+    routing, Router,
+};
 use const_format::concatcp;
 use tokio::{net::TcpListener, signal};
-use tower_http::trace::{DefaultOnRequest, DefaultOnResponse, TraceLayer};
+use tower_http::{
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+    trace::{DefaultOnRequest, DefaultOnResponse, TraceLayer},
+};
 use tracing::Level;
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
@@ -11,15 +20,40 @@ use utoipa_swagger_ui::SwaggerUi;
 use crate::{
     app::{self, log::*},
     doc::ApiDoc,
-    infrastructure::{config, persistence::Db, shell::ChildWorkers},
-    interface::handler::{post, todo},
+    infrastructure::{
+        config,
+        persistence::{with_primary_reads, Db},
+        shell::ChildWorkers,
+    },
+    interface::handler::{
+        attachment, audit, comment, health, post, post_transfer, tag, todo, user, webhook,
+    },
 };
 
 use super::handler::read_xls;
 
 const MAX_BODY_SIZE: usize = 1024 * 1024 * 128;
+/// request header asking to read from the primary database, e.g. right after a write
+const READ_PRIMARY_HEADER: &str = "x-read-primary";
 
 pub async fn serve(db: Db, child_workers: ChildWorkers) -> app::Result<()> {
+    let app = router(db, child_workers);
+
+    let server_conf = config::peek_config()?.server.clone();
+    let port = server_conf.port.unwrap_or(config::DEFAULT_PORT);
+
+    let listener = TcpListener::bind((Ipv4Addr::UNSPECIFIED, port)).await?;
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown_signal())
+    .await?;
+    Ok(())
+}
+
+/// All routes of the service, under the base path
+pub fn router(db: Db, child_workers: ChildWorkers) -> Router {
     let todo_handler = Router::new()
         .route(
             "/",
@@ -29,7 +63,26 @@ pub async fn serve(db: Db, child_workers: ChildWorkers) -> app::Result<()> {
                 .delete(todo::delete),
         )
         .route("/:id", routing::put(todo::mark_done))
-        .with_state(db.todo.clone());
+        .route("/:id/position", routing::put(todo::reorder))
+        .route("/:id/parent", routing::put(todo::reparent))
+        .route("/:id/tree", routing::get(todo::tree))
+        .with_state(db.clone());
+
+    let comment_handler = Router::new()
+        .route("/", routing::get(comment::list).post(comment::create))
+        .route(
+            "/:comment_id",
+            routing::put(comment::edit).delete(comment::delete),
+        )
+        .with_state(db.clone());
+
+    let attachment_handler = Router::new()
+        .route("/", routing::get(attachment::list).post(attachment::upload))
+        .route(
+            "/:attachment_id",
+            routing::get(attachment::download).delete(attachment::delete),
+        )
+        .with_state(db.clone());
 
     let post_handler = Router::new()
         .route(
@@ -39,8 +92,50 @@ pub async fn serve(db: Db, child_workers: ChildWorkers) -> app::Result<()> {
                 .put(post::edit)
                 .delete(post::delete),
         )
+        .route(
+            "/bulk",
+            routing::post(post::bulk_create).put(post::bulk_edit),
+        )
+        .route("/cache", routing::get(post::cache_stats))
+        .route("/export", routing::get(post_transfer::export))
+        .route("/import", routing::post(post_transfer::import))
+        .route("/by-slug/:slug", routing::get(post::get_by_slug))
         .route("/:id", routing::get(post::get))
-        .with_state(db.post.clone());
+        .route("/:id/status", routing::put(post::transit))
+        .route("/:id/revisions", routing::get(post::revisions))
+        .route("/:id/revisions/diff", routing::get(post::diff))
+        .route("/:id/revisions/:version", routing::get(post::revision))
+        .route(
+            "/:id/revisions/:version/revert",
+            routing::post(post::revert),
+        )
+        .nest("/:id/comments", comment_handler)
+        .nest("/:id/attachments", attachment_handler)
+        .with_state(db.clone());
+
+    let user_handler = Router::new()
+        .route("/", routing::post(user::create))
+        .route("/me", routing::get(user::me))
+        .route("/:id", routing::get(user::get))
+        .with_state(db.user.clone());
+
+    let tag_handler = Router::new()
+        .route("/", routing::get(tag::list))
+        .with_state(db.tag.clone());
+
+    let webhook_handler = Router::new()
+        .route("/", routing::get(webhook::list).post(webhook::create))
+        .route("/deliveries", routing::get(webhook::deliveries))
+        .route("/:id", routing::delete(webhook::delete))
+        .with_state(db.clone());
+
+    let audit_handler = Router::new()
+        .route("/", routing::get(audit::list))
+        .with_state(db.clone());
+
+    let health_handler = Router::new()
+        .route("/ready", routing::get(health::ready))
+        .with_state(db.health.clone());
 
     let read_xls_handler = Router::new()
         .route("/parse", routing::post(read_xls::parse))
@@ -49,29 +144,42 @@ pub async fn serve(db: Db, child_workers: ChildWorkers) -> app::Result<()> {
     let root = Router::new()
         .nest("/todo", todo_handler)
         .nest("/post", post_handler)
+        .nest("/tag", tag_handler)
+        .nest("/user", user_handler)
+        .nest("/webhook", webhook_handler)
+        .nest("/audit", audit_handler)
         .nest("/xls", read_xls_handler)
+        .nest("/health", health_handler)
         .layer(DefaultBodyLimit::max(MAX_BODY_SIZE))
+        .layer(middleware::from_fn(read_primary))
         .layer(
             TraceLayer::new_for_http()
                 .on_request(DefaultOnRequest::new().level(Level::TRACE))
                 .on_response(DefaultOnResponse::new().level(Level::INFO)),
-        );
+        )
+        // the id given by the client or a new one, echoed in the response
+        .layer(PropagateRequestIdLayer::x_request_id())
+        .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid));
 
-    let app = Router::new()
+    Router::new()
         .merge(
             SwaggerUi::new(concatcp!(config::BASE_PATH, "/swagger-ui"))
                 .url("/api-docs/openapi.json", ApiDoc::openapi()),
         )
-        .nest(config::BASE_PATH, root);
-
-    let server_conf = config::peek_config()?.server.clone();
-    let port = server_conf.port.unwrap_or(config::DEFAULT_PORT);
+        .nest(config::BASE_PATH, root)
+}
 
-    let listener = TcpListener::bind((Ipv4Addr::UNSPECIFIED, port)).await?;
-    axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal())
-        .await?;
-    Ok(())
+/// Keep reads of writing requests, and of requests asking for it, off the replicas
+async fn read_primary(req: Request, next: Next) -> Response {
+    let forced = req
+        .headers()
+        .get(READ_PRIMARY_HEADER)
+        .is_some_and(|v| v.as_bytes().eq_ignore_ascii_case(b"true") || v == "1");
+    if forced || !matches!(*req.method(), Method::GET | Method::HEAD) {
+        with_primary_reads(next.run(req)).await
+    } else {
+        next.run(req).await
+    }
 }
 
 async fn shutdown_signal() {
@@ -0,0 +1,688 @@
+//! Handlers against in-memory SQLite
+
+use std::sync::Once;
+
+use axum::{
+    body::{self, Body},
+    http::{header, Method, Request, StatusCode},
+    Router,
+};
+use serde_json::{json, Value};
+use tower::ServiceExt;
+
+use crate::infrastructure::{config, persistence::Db, shell::ChildWorkers};
+
+use super::route;
+
+const TEST_CONF: &str = r#"
+[db]
+url = "sqlite::memory:"
+auto_migrate = true
+
+[post_cache]
+size = 0
+
+[attachment]
+dir = "target/test-attachments"
+"#;
+
+static INIT: Once = Once::new();
+
+/// Service on a fresh database
+async fn app() -> Router {
+    app_with_db().await.0
+}
+
+/// Service on a fresh database, with the stores behind it
+async fn app_with_db() -> (Router, Db) {
+    INIT.call_once(|| config::init_test(TEST_CONF).unwrap());
+    let db = Db::setup().await.unwrap();
+    while !db.health.is_ready().await {
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    let app = route::router(db.clone(), ChildWorkers::setup().await.unwrap());
+    (app, db)
+}
+
+async fn call(app: &Router, method: Method, uri: &str, token: Option<&str>, body: Value) -> Value {
+    let mut req = Request::builder()
+        .method(method)
+        .uri(format!("{}{uri}", config::BASE_PATH))
+        .header(header::CONTENT_TYPE, "application/json");
+    if let Some(token) = token {
+        req = req.header(header::AUTHORIZATION, format!("Bearer {token}"));
+    }
+    let res = app
+        .clone()
+        .oneshot(req.body(Body::from(body.to_string())).unwrap())
+        .await
+        .unwrap();
+    match res.status() {
+        StatusCode::PERMANENT_REDIRECT => json!({
+            "location": res.headers()[header::LOCATION].to_str().unwrap(),
+        }),
+        _ => {
+            let bytes = body::to_bytes(res.into_body(), usize::MAX).await.unwrap();
+            serde_json::from_slice(&bytes).unwrap()
+        }
+    }
+}
+
+/// Create a User, returning its token
+async fn user(app: &Router, name: &str, role: &str) -> String {
+    let res = call(
+        app,
+        Method::POST,
+        "/user",
+        None,
+        json!({"name": name, "role": role}),
+    )
+    .await;
+    res["data"]["token"].as_str().unwrap().to_owned()
+}
+
+/// Create an undone Todo, returning its id
+async fn todo(app: &Router, mut body: Value) -> i64 {
+    body["done"] = json!(false);
+    let res = call(app, Method::POST, "/todo", None, body).await;
+    res["data"]["id"].as_i64().unwrap()
+}
+
+/// Comment on the Post, returning the Comment id
+async fn comment(app: &Router, post_id: i64, token: Option<&str>, body: Value) -> i64 {
+    let uri = format!("/post/{post_id}/comments");
+    let res = call(app, Method::POST, &uri, token, body).await;
+    res["data"]["id"].as_i64().unwrap()
+}
+
+#[tokio::test]
+async fn create_and_get_post() {
+    let app = app().await;
+    let token = user(&app, "alice", "admin").await;
+
+    let res = call(
+        &app,
+        Method::POST,
+        "/post",
+        Some(&token),
+        json!({"title": "Hello World", "content": "# Hi", "tags": ["rust", "web"], "status": "published"}),
+    )
+    .await;
+    assert_eq!(res["code"], 200);
+    let id = res["data"]["id"].as_i64().unwrap();
+
+    let res = call(&app, Method::GET, &format!("/post/{id}"), None, Value::Null).await;
+    assert_eq!(res["code"], 200);
+    assert_eq!(res["data"]["title"], "Hello World");
+    assert_eq!(res["data"]["slug"], "hello-world");
+    assert_eq!(res["data"]["tags"], json!(["rust", "web"]));
+
+    let res = call(&app, Method::GET, "/post?tags=web", None, Value::Null).await;
+    assert_eq!(res["data"]["total"], 1);
+    assert_eq!(res["data"]["list"][0]["id"], id);
+}
+
+#[tokio::test]
+async fn create_requires_user() {
+    let app = app().await;
+    let res = call(
+        &app,
+        Method::POST,
+        "/post",
+        None,
+        json!({"title": "t", "content": "c"}),
+    )
+    .await;
+    assert_eq!(res["code"], 401);
+}
+
+#[tokio::test]
+async fn only_first_user_becomes_admin_unasked() {
+    let app = app().await;
+    let admin = user(&app, "hank", "admin").await;
+    let res = call(&app, Method::GET, "/user/me", Some(&admin), Value::Null).await;
+    assert_eq!(res["data"]["role"], "admin");
+
+    let body = json!({"name": "ivan", "role": "admin"});
+    let res = call(&app, Method::POST, "/user", None, body.clone()).await;
+    assert_eq!(res["code"], 403);
+    let res = call(&app, Method::POST, "/user", Some(&admin), body).await;
+    assert_eq!(res["code"], 200);
+}
+
+#[tokio::test]
+async fn edit_post_keeps_revisions_and_old_slug() {
+    let app = app().await;
+    let token = user(&app, "bob", "user").await;
+    let res = call(
+        &app,
+        Method::POST,
+        "/post",
+        Some(&token),
+        json!({"title": "First", "content": "one"}),
+    )
+    .await;
+    let id = res["data"]["id"].as_i64().unwrap();
+
+    let res = call(
+        &app,
+        Method::PUT,
+        "/post",
+        Some(&token),
+        json!({"id": id, "title": "Second", "content": "two"}),
+    )
+    .await;
+    assert_eq!(res["code"], 200);
+
+    let res = call(
+        &app,
+        Method::GET,
+        &format!("/post/{id}/revisions?page=0"),
+        Some(&token),
+        Value::Null,
+    )
+    .await;
+    assert_eq!(res["data"]["total"], 2);
+    assert_eq!(res["data"]["list"][1]["title"], "First");
+
+    let res = call(&app, Method::GET, "/post/by-slug/first", None, Value::Null).await;
+    assert_eq!(
+        res["location"],
+        format!("{}/post/by-slug/second", config::BASE_PATH)
+    );
+}
+
+#[tokio::test]
+async fn drafts_visible_to_their_author() {
+    let app = app().await;
+    let admin = user(&app, "erin", "admin").await;
+    let author = user(&app, "frank", "user").await;
+    let other = user(&app, "grace", "user").await;
+    let res = call(
+        &app,
+        Method::POST,
+        "/post",
+        Some(&author),
+        json!({"title": "Draft", "content": "c"}),
+    )
+    .await;
+    let id = res["data"]["id"].as_i64().unwrap();
+
+    let uri = format!("/post/{id}");
+    for (token, code) in [
+        (None, 500),
+        (Some(&other), 500),
+        (Some(&author), 200),
+        (Some(&admin), 200),
+    ] {
+        let res = call(
+            &app,
+            Method::GET,
+            &uri,
+            token.map(String::as_str),
+            Value::Null,
+        )
+        .await;
+        assert_eq!(res["code"], code);
+    }
+
+    let res = call(&app, Method::GET, "/post", Some(&author), Value::Null).await;
+    assert_eq!(res["data"]["total"], 0);
+    let res = call(&app, Method::GET, "/post?status=draft", None, Value::Null).await;
+    assert_eq!(res["code"], 401);
+    let res = call(
+        &app,
+        Method::GET,
+        "/post?status=draft",
+        Some(&other),
+        Value::Null,
+    )
+    .await;
+    assert_eq!(res["data"]["total"], 0);
+    let res = call(
+        &app,
+        Method::GET,
+        "/post?status=draft",
+        Some(&author),
+        Value::Null,
+    )
+    .await;
+    assert_eq!(res["data"]["list"][0]["id"], id);
+    let res = call(
+        &app,
+        Method::GET,
+        "/post?status=draft",
+        Some(&admin),
+        Value::Null,
+    )
+    .await;
+    assert_eq!(res["data"]["total"], 1);
+}
+
+#[tokio::test]
+async fn draft_revisions_and_comments_hidden() {
+    let app = app().await;
+    let author = user(&app, "ivy", "admin").await;
+    let other = user(&app, "jack", "user").await;
+    let res = call(
+        &app,
+        Method::POST,
+        "/post",
+        Some(&author),
+        json!({"title": "Secret", "content": "c"}),
+    )
+    .await;
+    let id = res["data"]["id"].as_i64().unwrap();
+
+    let reads = [
+        format!("/post/{id}/revisions"),
+        format!("/post/{id}/revisions/1"),
+        format!("/post/{id}/revisions/diff?from=1&to=1"),
+        format!("/post/{id}/comments"),
+    ];
+    let comment = json!({"content": "psst"});
+    let uri = format!("/post/{id}/comments");
+    for token in [None, Some(other.as_str())] {
+        for read in &reads {
+            let res = call(&app, Method::GET, read, token, Value::Null).await;
+            assert_eq!(res["code"], 500, "{read}");
+        }
+        let res = call(&app, Method::POST, &uri, token, comment.clone()).await;
+        assert_eq!(res["code"], 500);
+    }
+    for read in &reads {
+        let res = call(&app, Method::GET, read, Some(&author), Value::Null).await;
+        assert_eq!(res["code"], 200, "{read}");
+    }
+    let res = call(&app, Method::POST, &uri, Some(&author), comment).await;
+    assert_eq!(res["code"], 200);
+}
+
+#[tokio::test]
+async fn comments_thread_and_count() {
+    let (app, db) = app_with_db().await;
+    let admin = user(&app, "kate", "admin").await;
+    let author = user(&app, "liam", "user").await;
+    let other = user(&app, "mia", "user").await;
+    let res = call(
+        &app,
+        Method::POST,
+        "/post",
+        Some(&admin),
+        json!({"title": "Chatty", "content": "c", "status": "published"}),
+    )
+    .await;
+    let id = res["data"]["id"].as_i64().unwrap();
+    let uri = format!("/post/{id}/comments");
+    let top = comment(&app, id, Some(&author), json!({"content": "first"})).await;
+    let anon = comment(&app, id, None, json!({"content": "second"})).await;
+    comment(&app, id, None, json!({"content": "re", "parent_id": top})).await;
+    comment(
+        &app,
+        id,
+        Some(&other),
+        json!({"content": "re", "parent_id": top}),
+    )
+    .await;
+
+    let res = call(&app, Method::GET, &uri, None, Value::Null).await;
+    assert_eq!(res["data"]["total"], 2);
+    assert_eq!(res["data"]["list"][0]["replies"], 2);
+    assert_eq!(res["data"]["list"][1]["author_id"], Value::Null);
+    let res = call(
+        &app,
+        Method::GET,
+        &format!("{uri}?parent_id={top}&page=0"),
+        None,
+        Value::Null,
+    )
+    .await;
+    assert_eq!(res["data"]["total"], 2);
+    assert_eq!(res["data"]["list"][0]["replies"], 0);
+    let res = call(&app, Method::GET, "/post", None, Value::Null).await;
+    assert_eq!(res["data"]["list"][0]["comment_count"], 4);
+
+    let edit = json!({"content": "edited"});
+    let top_uri = format!("{uri}/{top}");
+    let res = call(&app, Method::PUT, &top_uri, None, edit.clone()).await;
+    assert_eq!(res["code"], 401);
+    let res = call(&app, Method::PUT, &top_uri, Some(&other), edit.clone()).await;
+    assert_eq!(res["code"], 403);
+    let res = call(&app, Method::PUT, &top_uri, Some(&author), edit).await;
+    assert_eq!(res["code"], 200);
+    let anon_uri = format!("{uri}/{anon}");
+    let res = call(&app, Method::DELETE, &anon_uri, Some(&author), Value::Null).await;
+    assert_eq!(res["code"], 403);
+    let res = call(&app, Method::DELETE, &anon_uri, Some(&admin), Value::Null).await;
+    assert_eq!(res["code"], 200);
+
+    // replies go with the comment
+    let res = call(&app, Method::DELETE, &top_uri, Some(&author), Value::Null).await;
+    assert_eq!(res["code"], 200);
+    let res = call(&app, Method::GET, &format!("/post/{id}"), None, Value::Null).await;
+    assert_eq!(res["data"]["comment_count"], 0);
+
+    // and comments with the Post
+    comment(&app, id, None, json!({"content": "again"})).await;
+    let res = call(
+        &app,
+        Method::DELETE,
+        &format!("/post?ids={id}"),
+        Some(&admin),
+        Value::Null,
+    )
+    .await;
+    assert_eq!(res["data"]["deleted"], json!([id]));
+    let query = serde_json::from_value(json!({})).unwrap();
+    let (_, total) = db.comment.query(id as i32, query).await.unwrap();
+    assert_eq!(total, 0);
+}
+
+#[tokio::test]
+async fn import_keeps_status_and_publish_at() {
+    let app = app().await;
+    let token = user(&app, "heidi", "admin").await;
+    let body = [
+        json!({"title": "Old", "content": "c", "status": "archived", "publish_at": "2020-01-02 03:04:05"}),
+        json!({"title": "Live", "content": "c", "status": "published", "publish_at": "2021-01-02 03:04:05"}),
+    ]
+    .map(|v| v.to_string())
+    .join("\n");
+    let req = Request::builder()
+        .method(Method::POST)
+        .uri(format!("{}/post/import", config::BASE_PATH))
+        .header(header::CONTENT_TYPE, "application/x-ndjson")
+        .header(header::AUTHORIZATION, format!("Bearer {token}"))
+        .body(Body::from(body))
+        .unwrap();
+    let res = app.clone().oneshot(req).await.unwrap();
+    let bytes = body::to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    let res: Value = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(res["data"]["inserted"], 2, "{res}");
+
+    let res = call(
+        &app,
+        Method::GET,
+        "/post?status=archived",
+        Some(&token),
+        Value::Null,
+    )
+    .await;
+    assert_eq!(res["data"]["list"][0]["title"], "Old");
+    assert_eq!(res["data"]["list"][0]["publish_at"], "2020-01-02 03:04:05");
+    let res = call(&app, Method::GET, "/post", None, Value::Null).await;
+    assert_eq!(res["data"]["list"][0]["title"], "Live");
+    assert_eq!(res["data"]["list"][0]["publish_at"], "2021-01-02 03:04:05");
+
+    let uri = "/audit?resource=post&action=import";
+    let res = call(&app, Method::GET, uri, Some(&token), Value::Null).await;
+    assert_eq!(res["data"]["total"], 1);
+    assert_eq!(res["data"]["list"][0]["after"][1]["title"], "Live");
+}
+
+#[tokio::test]
+async fn delete_posts_of_others_forbidden() {
+    let app = app().await;
+    let owner = user(&app, "carol", "user").await;
+    let other = user(&app, "dave", "user").await;
+    let res = call(
+        &app,
+        Method::POST,
+        "/post",
+        Some(&owner),
+        json!({"title": "Mine", "content": "c"}),
+    )
+    .await;
+    let id = res["data"]["id"].as_i64().unwrap();
+
+    let uri = format!("/post?ids={id},{}", id + 100);
+    let res = call(&app, Method::DELETE, &uri, Some(&other), Value::Null).await;
+    assert_eq!(res["code"], 403);
+
+    let res = call(&app, Method::DELETE, &uri, Some(&owner), Value::Null).await;
+    assert_eq!(res["data"]["deleted"], json!([id]));
+    assert_eq!(res["data"]["not_found"], json!([id + 100]));
+}
+
+#[tokio::test]
+async fn deleting_post_is_audited() {
+    let app = app().await;
+    let admin = user(&app, "erin", "admin").await;
+    let res = call(
+        &app,
+        Method::POST,
+        "/post",
+        Some(&admin),
+        json!({"title": "Doomed", "content": "c"}),
+    )
+    .await;
+    let id = res["data"]["id"].as_i64().unwrap();
+    let res = call(
+        &app,
+        Method::DELETE,
+        &format!("/post?ids={id}"),
+        Some(&admin),
+        Value::Null,
+    )
+    .await;
+    assert_eq!(res["code"], 200);
+
+    let uri = format!("/audit?resource=post&resource_id={id}");
+    let res = call(&app, Method::GET, &uri, Some(&admin), Value::Null).await;
+    assert_eq!(res["data"]["total"], 2);
+    let deleted = &res["data"]["list"][0];
+    assert_eq!(deleted["action"], "delete");
+    assert_eq!(deleted["before"][0]["title"], "Doomed");
+    assert_eq!(deleted["after"], Value::Null);
+    assert!(deleted["request_id"].is_string());
+    assert_eq!(res["data"]["list"][1]["action"], "create");
+
+    let other = user(&app, "frank", "user").await;
+    let res = call(&app, Method::GET, &uri, Some(&other), Value::Null).await;
+    assert_eq!(res["code"], 403);
+}
+
+/// Upload a file to the Post as the client says it is, returning the response body
+async fn upload(app: &Router, token: &str, post_id: i64, name: &str, content: &[u8]) -> Value {
+    let boundary = "test-boundary";
+    let mut body = format!(
+        "--{boundary}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{name}\"\r\n\
+         Content-Type: image/png\r\n\r\n"
+    )
+    .into_bytes();
+    body.extend_from_slice(content);
+    body.extend_from_slice(format!("\r\n--{boundary}--\r\n").as_bytes());
+    let req = Request::builder()
+        .method(Method::POST)
+        .uri(format!("{}/post/{post_id}/attachments", config::BASE_PATH))
+        .header(
+            header::CONTENT_TYPE,
+            format!("multipart/form-data; boundary={boundary}"),
+        )
+        .header(header::AUTHORIZATION, format!("Bearer {token}"))
+        .body(Body::from(body))
+        .unwrap();
+    let res = app.clone().oneshot(req).await.unwrap();
+    let bytes = body::to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    serde_json::from_slice(&bytes).unwrap()
+}
+
+#[tokio::test]
+async fn attachments_served_safely() {
+    let app = app().await;
+    let author = user(&app, "gina", "user").await;
+    let res = call(
+        &app,
+        Method::POST,
+        "/post",
+        Some(&author),
+        json!({"title": "Draft with files", "content": "c", "status": "draft"}),
+    )
+    .await;
+    let post_id = res["data"]["id"].as_i64().unwrap();
+
+    let page = b"<html><script>alert('attachments_served_safely')</script></html>";
+    let res = upload(&app, &author, post_id, "page.png", page).await;
+    assert_eq!(res["data"][0]["content_type"], "application/octet-stream");
+    let page_id = res["data"][0]["id"].as_i64().unwrap();
+    let mut image = b"\x89PNG\r\n\x1a\n".to_vec();
+    image.extend_from_slice(b"attachments_served_safely");
+    let res = upload(&app, &author, post_id, "image.bin", &image).await;
+    assert_eq!(res["data"][0]["content_type"], "image/png");
+    let image_id = res["data"][0]["id"].as_i64().unwrap();
+
+    let download = |id: i64, token: Option<&str>| {
+        let mut req = Request::builder().uri(format!(
+            "{}/post/{post_id}/attachments/{id}",
+            config::BASE_PATH
+        ));
+        if let Some(token) = token {
+            req = req.header(header::AUTHORIZATION, format!("Bearer {token}"));
+        }
+        app.clone().oneshot(req.body(Body::empty()).unwrap())
+    };
+    let res = download(page_id, Some(&author)).await.unwrap();
+    let headers = res.headers();
+    let disposition = headers[header::CONTENT_DISPOSITION].to_str().unwrap();
+    assert!(disposition.starts_with("attachment;"));
+    assert_eq!(headers[header::X_CONTENT_TYPE_OPTIONS], "nosniff");
+    let res = download(image_id, Some(&author)).await.unwrap();
+    let disposition = res.headers()[header::CONTENT_DISPOSITION].to_str().unwrap();
+    assert!(disposition.starts_with("inline;"));
+
+    // files of a draft are the author's only
+    let res = download(image_id, None).await.unwrap();
+    let bytes = body::to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    let res: Value = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(res["code"], 500);
+    let uri = format!("/post/{post_id}/attachments");
+    let res = call(&app, Method::GET, &uri, None, Value::Null).await;
+    assert_eq!(res["code"], 500);
+
+    let res = call(&app, Method::GET, &uri, Some(&author), Value::Null).await;
+    let stored: Vec<_> = res["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|x| {
+            let sha256 = x["sha256"].as_str().unwrap();
+            format!("target/test-attachments/{}/{sha256}", &sha256[..2])
+        })
+        .collect();
+    assert_eq!(stored.len(), 2);
+    assert!(stored.iter().all(|x| std::path::Path::new(x).exists()));
+    let res = call(
+        &app,
+        Method::DELETE,
+        &format!("/post?ids={post_id}"),
+        Some(&author),
+        Value::Null,
+    )
+    .await;
+    assert_eq!(res["code"], 200);
+    for path in stored {
+        assert!(!std::path::Path::new(&path).exists());
+    }
+}
+
+#[tokio::test]
+async fn todo_list_pages_by_sort_key() {
+    let app = app().await;
+    for (value, priority) in [("a", "low"), ("b", "urgent"), ("c", "high")] {
+        let body = json!({"value": value, "done": false, "priority": priority});
+        call(&app, Method::POST, "/todo", None, body).await;
+    }
+
+    let res = call(&app, Method::GET, "/todo?size=2", None, Value::Null).await;
+    assert_eq!(res["data"]["total"], 3);
+    assert_eq!(res["data"]["list"][1]["value"], "b");
+
+    let uri = "/todo?sort=priority&size=2&page=2";
+    let res = call(&app, Method::GET, uri, None, Value::Null).await;
+    assert_eq!(res["data"]["list"][0]["value"], "a");
+
+    let uri = "/todo?sort=priority&order=asc";
+    let res = call(&app, Method::GET, uri, None, Value::Null).await;
+    let values: Vec<_> = res["data"]["list"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|x| x["value"].as_str().unwrap())
+        .collect();
+    assert_eq!(values, ["a", "c", "b"]);
+}
+
+#[tokio::test]
+async fn todo_subtasks_complete_their_parent() {
+    let app = app().await;
+    let trip = todo(&app, json!({"value": "trip", "auto_complete": true})).await;
+    let pack = todo(&app, json!({"value": "pack", "parent_id": trip})).await;
+    let book = todo(&app, json!({"value": "book", "parent_id": trip})).await;
+    let socks = todo(&app, json!({"value": "socks", "parent_id": pack})).await;
+
+    let uri = format!("/todo/{pack}?cascade=true");
+    call(&app, Method::PUT, &uri, None, Value::Null).await;
+    let res = call(
+        &app,
+        Method::GET,
+        &format!("/todo/{trip}/tree"),
+        None,
+        Value::Null,
+    )
+    .await;
+    let tree = &res["data"];
+    assert_eq!(tree["progress"], json!({"done": 1, "total": 2}));
+    assert_eq!(tree["children"][0]["children"][0]["done"], true);
+    assert_eq!(tree["done"], false);
+
+    call(
+        &app,
+        Method::PUT,
+        &format!("/todo/{book}"),
+        None,
+        Value::Null,
+    )
+    .await;
+    let res = call(
+        &app,
+        Method::GET,
+        &format!("/todo/{trip}/tree"),
+        None,
+        Value::Null,
+    )
+    .await;
+    assert_eq!(res["data"]["done"], true);
+
+    // side effects are audited along with the Todo changed
+    let admin = user(&app, "ivan", "admin").await;
+    let uri = "/audit?resource=todo&action=mark_done";
+    let res = call(&app, Method::GET, uri, Some(&admin), Value::Null).await;
+    let list = &res["data"]["list"];
+    assert_eq!(list[0]["resource_ids"], format!("{trip},{book}"));
+    assert_eq!(list[0]["before"][0]["done"], false);
+    assert_eq!(list[0]["after"][0]["done"], true);
+    assert_eq!(list[1]["resource_ids"], format!("{pack},{socks}"));
+
+    let uri = format!("/todo/{trip}/parent");
+    let res = call(&app, Method::PUT, &uri, None, json!({"parent_id": socks})).await;
+    assert_eq!(res["code"], 400);
+
+    call(
+        &app,
+        Method::DELETE,
+        &format!("/todo?ids={trip}"),
+        None,
+        Value::Null,
+    )
+    .await;
+    let res = call(&app, Method::GET, "/todo", None, Value::Null).await;
+    assert_eq!(res["data"]["total"], 0);
+    let uri = "/audit?resource=todo&action=delete";
+    let res = call(&app, Method::GET, uri, Some(&admin), Value::Null).await;
+    let list = &res["data"]["list"];
+    assert_eq!(
+        list[0]["resource_ids"],
+        format!("{trip},{pack},{book},{socks}")
+    );
+    assert_eq!(list[0]["after"], Value::Null);
+}
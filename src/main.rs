@@ -7,7 +7,7 @@ mod domain;
 mod infrastructure;
 mod interface;
 
-pub use domain::{entity, repository};
+pub use domain::{entity, event, repository};
 
 use app::log::*;
 